@@ -1,4 +1,5 @@
 use crate::{
+    environment::Environment,
     error::Error,
     token::{Identifier, Token},
 };
@@ -18,6 +19,31 @@ pub enum ASTNodeIdentifier {
     NewLine,
     If,
     IfEnd,
+    Break,
+    Continue,
+    Comment,
+    Raw,
+    RawEnd,
+    Set,
+    Capture,
+    CaptureEnd,
+    Macro,
+    MacroEnd,
+    Call,
+    CallEnd,
+    Import,
+    FromImport,
+    Switch,
+    SwitchEnd,
+    Case,
+    Default,
+    Trans,
+    TransEnd,
+    Plural,
+    PluralEnd,
+    PluralForm,
+    Section,
+    SectionEnd,
 }
 
 #[derive(Debug, Clone)]
@@ -29,7 +55,7 @@ pub struct ASTNode {
 }
 
 fn assign_identity(ast_node: &mut ASTNode) -> Result<(), Error> {
-    let opening_chars = &ast_node.value[0..2];
+    let opening_chars = ast_node.value.get(0..2).unwrap_or_default();
     if opening_chars == "${" && ast_node.value.ends_with('}') {
         ast_node.identifier = ASTNodeIdentifier::Variable;
     } else if opening_chars == "{#" && ast_node.value.ends_with("#}") {
@@ -45,6 +71,56 @@ fn assign_identity(ast_node: &mut ASTNode) -> Result<(), Error> {
                 ast_node.children = Some(Ast { nodes: vec![] });
             }
             "{#endif#}" => ast_node.identifier = ASTNodeIdentifier::IfEnd,
+            "{#raw#}" | "{#noparse#}" => {
+                ast_node.identifier = ASTNodeIdentifier::Raw;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endraw#}" | "{#endnoparse#}" => ast_node.identifier = ASTNodeIdentifier::RawEnd,
+            "{#set" => ast_node.identifier = ASTNodeIdentifier::Set,
+            "{#capture" => {
+                ast_node.identifier = ASTNodeIdentifier::Capture;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endcapture#}" => ast_node.identifier = ASTNodeIdentifier::CaptureEnd,
+            "{#macro" => {
+                ast_node.identifier = ASTNodeIdentifier::Macro;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endmacro#}" => ast_node.identifier = ASTNodeIdentifier::MacroEnd,
+            "{#call" => {
+                ast_node.identifier = ASTNodeIdentifier::Call;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endcall#}" => ast_node.identifier = ASTNodeIdentifier::CallEnd,
+            "{#import" => ast_node.identifier = ASTNodeIdentifier::Import,
+            "{#switch" => {
+                ast_node.identifier = ASTNodeIdentifier::Switch;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endswitch#}" => ast_node.identifier = ASTNodeIdentifier::SwitchEnd,
+            "{#case" => ast_node.identifier = ASTNodeIdentifier::Case,
+            "{#default#}" => ast_node.identifier = ASTNodeIdentifier::Default,
+            "{#trans#}" | "{#trans" => {
+                ast_node.identifier = ASTNodeIdentifier::Trans;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endtrans#}" => ast_node.identifier = ASTNodeIdentifier::TransEnd,
+            "{#plural" => {
+                ast_node.identifier = ASTNodeIdentifier::Plural;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endplural#}" => ast_node.identifier = ASTNodeIdentifier::PluralEnd,
+            "{#section" => {
+                ast_node.identifier = ASTNodeIdentifier::Section;
+                ast_node.children = Some(Ast { nodes: vec![] });
+            }
+            "{#endsection#}" => ast_node.identifier = ASTNodeIdentifier::SectionEnd,
+            "{#zero#}" | "{#one#}" | "{#two#}" | "{#few#}" | "{#many#}" | "{#other#}" => {
+                ast_node.identifier = ASTNodeIdentifier::PluralForm
+            }
+            "{#from" => ast_node.identifier = ASTNodeIdentifier::FromImport,
+            "{#break#}" => ast_node.identifier = ASTNodeIdentifier::Break,
+            "{#continue#}" => ast_node.identifier = ASTNodeIdentifier::Continue,
             _ => {
                 let construct_token = ast_node.tokens.get(2).unwrap();
                 return Err(Error::InvalidSyntax(format!(
@@ -60,12 +136,155 @@ fn assign_identity(ast_node: &mut ASTNode) -> Result<(), Error> {
     };
     Ok(())
 }
-pub fn construct_ast(parsed_tokens: Vec<Vec<Token>>) -> Result<Ast, Error> {
-    let mut open_brace_count = 0;
+/// Removes `-` whitespace control markers from e.g. `{#-for x of y-#}` and `${- x -}`,
+/// returning whether whitespace should be trimmed to the left and right of the group.
+fn take_whitespace_markers(token_group: &mut [Token]) -> (bool, bool) {
+    let is_tag = token_group.len() > 2
+        && token_group.first().unwrap().identifier != Identifier::Text
+        && token_group.last().unwrap().identifier == Identifier::ClosedBracket;
+    if !is_tag {
+        return (false, false);
+    }
+
+    let text_positions: Vec<usize> = token_group
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| t.identifier == Identifier::Text)
+        .map(|(i, _)| i)
+        .collect();
+
+    let (first, last) = match (text_positions.first(), text_positions.last()) {
+        (Some(first), Some(last)) => (*first, *last),
+        _ => return (false, false),
+    };
+
+    // In a variable a `-` only trims when something like `${- a}` or `${-}` sets it
+    // apart, so that `${-a}` stays a negation
+    let is_variable = token_group[0].identifier == Identifier::Dollar;
+    let trim_left = match token_group[first].value.strip_prefix('-') {
+        Some(rest) => !is_variable || rest.is_empty() || rest.starts_with(char::is_whitespace),
+        None => false,
+    };
+    if trim_left {
+        token_group[first].value.remove(0);
+        token_group[first].pos_start += 1;
+
+        // So that `{#- if x#}` is read as `{#if x#}`
+        if !is_variable {
+            let text = &mut token_group[first];
+            let spaces = text.value.len() - text.value.trim_start().len();
+            text.value.drain(..spaces);
+            text.pos_start += spaces as i32;
+        }
+    }
+
+    let trim_right = token_group[last].value.ends_with('-');
+    if trim_right {
+        token_group[last].value.pop();
+        token_group[last].pos_end -= 1;
+
+        if !is_variable {
+            let text = &mut token_group[last];
+            let spaces = text.value.len() - text.value.trim_end().len();
+            text.value.truncate(text.value.len() - spaces);
+            text.pos_end -= spaces as i32;
+        }
+    }
+
+    (trim_left, trim_right)
+}
+
+fn is_block(ast_node: &ASTNode) -> bool {
+    matches!(
+        ast_node.identifier,
+        ASTNodeIdentifier::Loop
+            | ASTNodeIdentifier::LoopEnd
+            | ASTNodeIdentifier::If
+            | ASTNodeIdentifier::IfEnd
+            | ASTNodeIdentifier::Break
+            | ASTNodeIdentifier::Continue
+            | ASTNodeIdentifier::Comment
+            | ASTNodeIdentifier::Raw
+            | ASTNodeIdentifier::RawEnd
+            | ASTNodeIdentifier::Set
+            | ASTNodeIdentifier::Capture
+            | ASTNodeIdentifier::CaptureEnd
+            | ASTNodeIdentifier::Macro
+            | ASTNodeIdentifier::MacroEnd
+            | ASTNodeIdentifier::Call
+            | ASTNodeIdentifier::CallEnd
+            | ASTNodeIdentifier::Import
+            | ASTNodeIdentifier::FromImport
+            | ASTNodeIdentifier::Switch
+            | ASTNodeIdentifier::SwitchEnd
+            | ASTNodeIdentifier::Case
+            | ASTNodeIdentifier::Default
+            | ASTNodeIdentifier::Trans
+            | ASTNodeIdentifier::TransEnd
+            | ASTNodeIdentifier::Plural
+            | ASTNodeIdentifier::PluralEnd
+            | ASTNodeIdentifier::PluralForm
+            | ASTNodeIdentifier::Section
+            | ASTNodeIdentifier::SectionEnd
+    )
+}
+
+/// The construct that a closing construct such as `{#endfor#}` belongs to.
+fn opening_identifier(identifier: &ASTNodeIdentifier) -> Option<ASTNodeIdentifier> {
+    match identifier {
+        ASTNodeIdentifier::LoopEnd => Some(ASTNodeIdentifier::Loop),
+        ASTNodeIdentifier::IfEnd => Some(ASTNodeIdentifier::If),
+        ASTNodeIdentifier::RawEnd => Some(ASTNodeIdentifier::Raw),
+        ASTNodeIdentifier::CaptureEnd => Some(ASTNodeIdentifier::Capture),
+        ASTNodeIdentifier::MacroEnd => Some(ASTNodeIdentifier::Macro),
+        ASTNodeIdentifier::CallEnd => Some(ASTNodeIdentifier::Call),
+        ASTNodeIdentifier::SwitchEnd => Some(ASTNodeIdentifier::Switch),
+        ASTNodeIdentifier::TransEnd => Some(ASTNodeIdentifier::Trans),
+        ASTNodeIdentifier::PluralEnd => Some(ASTNodeIdentifier::Plural),
+        ASTNodeIdentifier::SectionEnd => Some(ASTNodeIdentifier::Section),
+        _ => None,
+    }
+}
+
+fn is_whitespace_text(ast_node: &ASTNode) -> bool {
+    ast_node.identifier == ASTNodeIdentifier::Text
+        && ast_node.value.chars().all(|c| c == ' ' || c == '\t')
+}
+
+/// Removes all whitespace, including new lines, from the end of what has been
+/// constructed so far.
+fn trim_preceding_whitespace(nodes: &mut Vec<ASTNode>) {
+    while let Some(last_node) = nodes.last_mut() {
+        match last_node.identifier {
+            ASTNodeIdentifier::NewLine => {
+                nodes.pop();
+            }
+            ASTNodeIdentifier::Text => {
+                let trimmed_length = last_node.value.trim_end().len();
+                last_node.value.truncate(trimmed_length);
+                if !last_node.value.is_empty() {
+                    return;
+                }
+                nodes.pop();
+            }
+            _ => return,
+        }
+    }
+}
+
+pub fn construct_ast(
+    parsed_tokens: Vec<Vec<Token>>,
+    environment: &Environment,
+) -> Result<Ast, Error> {
     let mut constructed_ast = Ast { nodes: vec![] };
+    let mut trim_following_whitespace = false;
+    let mut trim_following_newline = false;
+    let mut at_line_start = true;
+    let mut line_indentation: Option<usize> = None;
 
     // Iterate over groupings of tokens that make up something e.g. variable
-    for token_group in parsed_tokens {
+    for mut token_group in parsed_tokens {
+        let mut open_brace_count = 0;
         let mut ast_node = ASTNode {
             identifier: ASTNodeIdentifier::Unknown,
             value: String::new(),
@@ -74,6 +293,7 @@ pub fn construct_ast(parsed_tokens: Vec<Vec<Token>>) -> Result<Ast, Error> {
         };
 
         let mut bad_token = Token::new(Identifier::Text, 1, 1, 1, 1);
+        let (trim_left, trim_right) = take_whitespace_markers(&mut token_group);
 
         // Iterate over a group and construct to ASTNode
         for token in token_group {
@@ -81,6 +301,7 @@ pub fn construct_ast(parsed_tokens: Vec<Vec<Token>>) -> Result<Ast, Error> {
             match token.identifier {
                 Identifier::Text => ast_node.identifier = ASTNodeIdentifier::Text,
                 Identifier::NewLine => ast_node.identifier = ASTNodeIdentifier::NewLine,
+                Identifier::Comment => ast_node.identifier = ASTNodeIdentifier::Comment,
                 _ => {
                     if token.value == "{" {
                         open_brace_count += 1;
@@ -96,47 +317,89 @@ pub fn construct_ast(parsed_tokens: Vec<Vec<Token>>) -> Result<Ast, Error> {
             ast_node.tokens.push(token);
         }
 
-        // Can add other stuff here when it comes e.g. conditions
-        if ast_node.value.len() > 2 {
+        // Only groups that start with an opener are syntax, escaped openers are text
+        let opening_token = ast_node.tokens.first().unwrap();
+        if opening_token.identifier == Identifier::Dollar
+            || opening_token.identifier == Identifier::OpenBracket
+        {
+            if ast_node.tokens.last().unwrap().identifier != Identifier::ClosedBracket {
+                return Err(Error::InvalidSyntax(format!(
+                    "\n'{}' has no closing {}\nat line: {}:{}\n",
+                    ast_node.value.lines().next().unwrap_or_default(),
+                    match opening_token.identifier {
+                        Identifier::Dollar => "}",
+                        _ => "#}",
+                    },
+                    opening_token.line_start,
+                    opening_token.pos_start
+                )));
+            }
+
+            // Can add other stuff here when it comes e.g. conditions
             assign_identity(&mut ast_node)?;
         }
 
+        if ast_node.identifier == ASTNodeIdentifier::Comment && !ast_node.value.ends_with("--}") {
+            let comment_token = ast_node.tokens.first().unwrap();
+            return Err(Error::InvalidSyntax(format!(
+                "\nComment has no closing --}}\nat line: {}:{}\n",
+                comment_token.line_start, comment_token.pos_start
+            )));
+        }
+
         // Variable error handling
         if open_brace_count > 1 {
             return Err(Error::InvalidSyntax(format!(
                 "Error: Extra opening {{ found at line: {} position: {} in {}",
-                bad_token.line_start,
-                bad_token.pos_start - 1,
-                ast_node.value
+                bad_token.line_start, bad_token.pos_start, ast_node.value
             )));
         }
 
-        // Sanitise whitespace only tokens good idea?
-        let last_node = constructed_ast.nodes.last();
+        // Whitespace control, from either the markers or the environment settings.
+        // Whether something starts a line is based on the source, not on what is kept.
+        let starts_line = at_line_start;
+        at_line_start = ast_node.identifier == ASTNodeIdentifier::NewLine;
+        let indentation = line_indentation.take();
 
-        if let Some(last_node) = last_node {
-            if (ast_node.identifier == ASTNodeIdentifier::LoopEnd
-                || ast_node.identifier == ASTNodeIdentifier::Loop
-                || ast_node.identifier == ASTNodeIdentifier::If
-                || ast_node.identifier == ASTNodeIdentifier::IfEnd)
-                && last_node.value.replace(' ', "").is_empty()
-            {
-                constructed_ast.nodes.pop();
-            }
+        if ast_node.identifier == ASTNodeIdentifier::NewLine && trim_following_newline {
+            trim_following_newline = false;
+            continue;
         }
+        trim_following_newline = false;
 
-        match ast_node.identifier {
-            ASTNodeIdentifier::NewLine => {
-                let last_node = constructed_ast.nodes.last().unwrap().identifier.clone();
-                if last_node != ASTNodeIdentifier::LoopEnd && last_node != ASTNodeIdentifier::IfEnd
-                {
-                    constructed_ast.nodes.push(ast_node);
+        if trim_following_whitespace {
+            match ast_node.identifier {
+                ASTNodeIdentifier::NewLine => continue,
+                ASTNodeIdentifier::Text => {
+                    ast_node.value = ast_node.value.trim_start().to_string();
+                    if ast_node.value.is_empty() {
+                        continue;
+                    }
                 }
+                _ => {}
             }
-            _ => constructed_ast.nodes.push(ast_node),
         }
 
-        open_brace_count = 0;
+        if trim_left {
+            trim_preceding_whitespace(&mut constructed_ast.nodes);
+        } else if environment.lstrip_blocks
+            && is_block(&ast_node)
+            && indentation.is_some_and(|i| i + 1 == constructed_ast.nodes.len())
+        {
+            constructed_ast.nodes.pop();
+        }
+
+        if starts_line && is_whitespace_text(&ast_node) {
+            line_indentation = Some(constructed_ast.nodes.len());
+        }
+
+        trim_following_whitespace = trim_right;
+        trim_following_newline = environment.trim_blocks && is_block(&ast_node);
+
+        // Comments only take part in whitespace control, they never render
+        if ast_node.identifier != ASTNodeIdentifier::Comment {
+            constructed_ast.nodes.push(ast_node);
+        }
     }
 
     // Naive iteration to move children nodes into parent
@@ -145,39 +408,19 @@ pub fn construct_ast(parsed_tokens: Vec<Vec<Token>>) -> Result<Ast, Error> {
     let mut nodes_with_children: Vec<ASTNode> = vec![];
 
     for node in constructed_ast.nodes {
-        match node.identifier {
-            ASTNodeIdentifier::Loop => {
-                nodes_with_children.push(node);
-            }
-            ASTNodeIdentifier::LoopEnd => {
-                if nodes_with_children.len() > 1 {
-                    let length = nodes_with_children.len() - 1;
-                    let latest_node = nodes_with_children[length].clone();
+        let opening = opening_identifier(&node.identifier);
 
-                    let length_of_next = length - 1;
-                    let latest_node_next = &mut nodes_with_children[length_of_next]
-                        .children
-                        .as_mut()
-                        .unwrap();
-                    latest_node_next.nodes.push(latest_node);
-                    nodes_with_children.pop();
-                } else {
-                    new_ast
-                        .nodes
-                        .push(nodes_with_children.last().unwrap().clone());
-                    nodes_with_children.pop();
+        match node.identifier {
+            _ if node.children.is_some() => nodes_with_children.push(node),
+            _ if opening.is_some() => {
+                if nodes_with_children.last().map(|n| &n.identifier) != opening.as_ref() {
+                    let construct_token = node.tokens.first().unwrap();
+                    return Err(Error::InvalidSyntax(format!(
+                        "\n'{}' has no opening statement\nat line: {}:{}\n",
+                        node.value, construct_token.line_start, construct_token.pos_start
+                    )));
                 }
 
-                if !nodes_with_children.is_empty() {
-                    let length = nodes_with_children.len() - 1;
-                    let latest_node = &mut nodes_with_children[length].children.as_mut().unwrap();
-                    latest_node.nodes.push(node);
-                } else {
-                    new_ast.nodes.push(node);
-                }
-            }
-            ASTNodeIdentifier::If => nodes_with_children.push(node),
-            ASTNodeIdentifier::IfEnd => {
                 if nodes_with_children.len() > 1 {
                     let length = nodes_with_children.len() - 1;
                     let latest_node = nodes_with_children[length].clone();
@@ -204,20 +447,46 @@ pub fn construct_ast(parsed_tokens: Vec<Vec<Token>>) -> Result<Ast, Error> {
                     new_ast.nodes.push(node);
                 }
             }
+            ASTNodeIdentifier::Case
+            | ASTNodeIdentifier::Default
+            | ASTNodeIdentifier::PluralForm => {
+                // Cases and plural forms split up the contents of the block directly
+                // around them
+                let (parent, construct) = match node.identifier {
+                    ASTNodeIdentifier::PluralForm => (ASTNodeIdentifier::Plural, "plural"),
+                    _ => (ASTNodeIdentifier::Switch, "switch"),
+                };
+                if nodes_with_children.last().map(|n| &n.identifier) != Some(&parent) {
+                    let construct_token = node.tokens.first().unwrap();
+                    return Err(Error::InvalidSyntax(format!(
+                        "\n'{}' is not directly inside of a {}\nat line: {}:{}\n",
+                        node.value,
+                        construct,
+                        construct_token.line_start,
+                        construct_token.pos_start
+                    )));
+                }
 
-            ASTNodeIdentifier::NewLine => {
-                if !nodes_with_children.is_empty() {
-                    let length = nodes_with_children.len() - 1;
-                    let latest_node = &mut nodes_with_children[length].children.as_mut().unwrap();
-
-                    if new_ast.nodes.last().unwrap().identifier != ASTNodeIdentifier::NewLine
-                        || !latest_node.nodes.is_empty()
-                    {
-                        latest_node.nodes.push(node);
-                    }
-                } else {
-                    new_ast.nodes.push(node);
+                let length = nodes_with_children.len() - 1;
+                let latest_node = &mut nodes_with_children[length].children.as_mut().unwrap();
+                latest_node.nodes.push(node);
+            }
+            ASTNodeIdentifier::Break | ASTNodeIdentifier::Continue => {
+                // Loop control only makes sense with a loop somewhere above it
+                if !nodes_with_children
+                    .iter()
+                    .any(|n| n.identifier == ASTNodeIdentifier::Loop)
+                {
+                    let construct_token = node.tokens.first().unwrap();
+                    return Err(Error::InvalidSyntax(format!(
+                        "\n'{}' is not inside of a loop\nat line: {}:{}\n",
+                        node.value, construct_token.line_start, construct_token.pos_start
+                    )));
                 }
+
+                let length = nodes_with_children.len() - 1;
+                let latest_node = &mut nodes_with_children[length].children.as_mut().unwrap();
+                latest_node.nodes.push(node);
             }
             _ => {
                 if !nodes_with_children.is_empty() {
@@ -0,0 +1,593 @@
+mod plural_forms;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::error::Error;
+use plural_forms::PluralForms;
+
+/// Translated messages for one locale, by message id. Translations refer to variables
+/// the way templates do, e.g. `Hallo ${name}!`.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use kitamura::{Catalog, Environment};
+/// use serde_json::json;
+///
+/// let catalog = Catalog::from_po("msgid \"Hello ${name}\"\nmsgstr \"Hallo ${name}\"\n").unwrap();
+/// let environment = Environment::new().catalog("de", catalog).locale("de-AT");
+/// let params = HashMap::from([("name".to_owned(), json!("Joel"))]);
+/// let output = environment
+///     .render_template("{#trans#}Hello ${name}{#endtrans#}".to_owned(), params)
+///     .unwrap();
+/// assert_eq!(output, "Hallo Joel");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Catalog {
+    messages: HashMap<String, Translation>,
+    plural_forms: Option<PluralForms>,
+    /// The locale the catalog was registered for, whose plural rules Fluent uses.
+    pub(crate) locale: String,
+}
+
+/// The translation of one message.
+#[derive(Debug, Clone)]
+pub(crate) enum Translation {
+    /// Text with `${name}` placeholders.
+    Text(String),
+    /// The forms of a gettext plural message, by `msgstr[i]`.
+    Plural(Vec<String>),
+    /// A Fluent pattern with selectors in it.
+    Fluent(Vec<FluentPart>),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum FluentPart {
+    /// Text with `${name}` placeholders.
+    Text(String),
+    /// `{ $count -> [one] ... *[other] ... }`, which picks a variant by the value of a
+    /// variable, or else the default.
+    Select {
+        variable: String,
+        variants: Vec<(String, Vec<FluentPart>)>,
+        default: usize,
+    },
+}
+
+fn catalog_error(format: &str, line: usize, message: &str) -> Error {
+    Error::InvalidSyntax(format!(
+        "\nInvalid {} catalog at line {}:\n{}\n",
+        format, line, message
+    ))
+}
+
+/// Reads the contents of a quoted `.po` string such as `"Hello \"you\"\n"`.
+fn po_string(line: usize, quoted: &str) -> Result<String, Error> {
+    let inner = quoted
+        .trim()
+        .strip_prefix('"')
+        .and_then(|q| q.strip_suffix('"'))
+        .ok_or_else(|| catalog_error(".po", line, "expected a quoted string"))?;
+
+    let mut string = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => string.push('\n'),
+                Some('t') => string.push('\t'),
+                Some('r') => string.push('\r'),
+                Some(c @ ('"' | '\\')) => string.push(c),
+                _ => return Err(catalog_error(".po", line, "invalid escape in string")),
+            },
+            c => string.push(c),
+        }
+    }
+    Ok(string)
+}
+
+/// One entry of a `.po` file while it is being read.
+#[derive(Debug, Default)]
+struct PoEntry {
+    line: usize,
+    context: Option<String>,
+    id: Option<String>,
+    plural: bool,
+    translations: BTreeMap<usize, String>,
+    fuzzy: bool,
+}
+
+impl PoEntry {
+    /// Fuzzy and untranslated entries are left out, as gettext does. The header entry
+    /// with an empty id gives the `Plural-Forms` of the catalog. Entries with a
+    /// `msgctxt` are left out too, as templates have no way to give a context.
+    fn add_to(self, catalog: &mut Catalog) -> Result<(), Error> {
+        let id = match (self.id, self.context) {
+            (Some(id), None) if id.is_empty() => {
+                let header = self.translations.into_values().next().unwrap_or_default();
+                catalog.plural_forms = PluralForms::from_header(&header)
+                    .map_err(|e| catalog_error(".po", self.line, &e))?;
+                return Ok(());
+            }
+            (Some(id), None) => id,
+            (Some(_), Some(_)) | (None, _) => return Ok(()),
+        };
+
+        let untranslated = self.translations.values().any(String::is_empty);
+        if self.fuzzy || untranslated || self.translations.is_empty() {
+            return Ok(());
+        }
+        let translation = match self.plural {
+            true => Translation::Plural(self.translations.into_values().collect()),
+            false => Translation::Text(self.translations.into_values().next().unwrap()),
+        };
+        catalog.messages.insert(id, translation);
+        Ok(())
+    }
+}
+
+fn is_fluent_variable(variable: &str) -> bool {
+    !variable.is_empty()
+        && variable
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Converts Fluent patterns to translations, where `{ $name }` becomes `${name}`,
+/// terms and other messages are written out and selectors are kept to pick from when
+/// rendering.
+struct FluentReader<'a> {
+    entries: &'a HashMap<String, (usize, String)>,
+    /// The messages being read, to find references back to themselves.
+    seen: Vec<String>,
+}
+
+impl FluentReader<'_> {
+    fn message(&mut self, id: &str) -> Result<Vec<FluentPart>, Error> {
+        let (line, pattern) = &self.entries[id];
+        if self.seen.iter().any(|s| s == id) {
+            return Err(catalog_error(
+                ".ftl",
+                *line,
+                &format!("'{}' refers to itself", id),
+            ));
+        }
+
+        self.seen.push(id.to_owned());
+        let mut rest = pattern.as_str();
+        let parts = self.pattern(*line, &mut rest, false)?;
+        if let Some(extra) = rest.chars().next() {
+            return Err(catalog_error(
+                ".ftl",
+                *line,
+                &format!("'{}' has no opening {{", extra),
+            ));
+        }
+        self.seen.pop();
+
+        Ok(parts)
+    }
+
+    /// Reads text and placeables up to a `}`, or for a variant also up to the line of
+    /// the next variant.
+    fn pattern(
+        &mut self,
+        line: usize,
+        rest: &mut &str,
+        variant: bool,
+    ) -> Result<Vec<FluentPart>, Error> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        while let Some(c) = rest.chars().next() {
+            match c {
+                '}' => break,
+                '\n' if variant && rest[1..].trim_start().starts_with(['[', '*', '}']) => break,
+                '{' => {
+                    *rest = &rest[1..];
+                    for part in self.placeable(line, rest)? {
+                        match part {
+                            FluentPart::Text(placed) => text.push_str(&placed),
+                            select => {
+                                parts.push(FluentPart::Text(std::mem::take(&mut text)));
+                                parts.push(select);
+                            }
+                        }
+                    }
+                    continue;
+                }
+                c => text.push(c),
+            }
+            *rest = &rest[c.len_utf8()..];
+        }
+
+        parts.push(FluentPart::Text(text));
+        parts.retain(|part| !matches!(part, FluentPart::Text(text) if text.is_empty()));
+        Ok(parts)
+    }
+
+    /// Reads a placeable after its `{`, up to and including its `}`.
+    fn placeable(&mut self, line: usize, rest: &mut &str) -> Result<Vec<FluentPart>, Error> {
+        let error = |message: &str| catalog_error(".ftl", line, message);
+        let unclosed = || error("a { has no closing }");
+
+        *rest = rest.trim_start();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '}')
+            .ok_or_else(unclosed)?;
+        let (expression, after) = match rest.strip_prefix('"') {
+            Some(literal) => {
+                let end = literal.find('"').ok_or_else(unclosed)?;
+                (&rest[..end + 2], &literal[end + 1..])
+            }
+            None => rest.split_at(end),
+        };
+        *rest = after.trim_start();
+
+        if let Some(selector) = rest.strip_prefix("->") {
+            let variable = expression
+                .strip_prefix('$')
+                .filter(|v| is_fluent_variable(v))
+                .ok_or_else(|| {
+                    error(&format!(
+                        "only variables can select a variant, not '{}'",
+                        expression
+                    ))
+                })?;
+            *rest = selector;
+            return self.select(line, variable, rest);
+        }
+        *rest = rest.strip_prefix('}').ok_or_else(|| {
+            error(&format!(
+                "'{{ {}' is not supported or has no closing }}",
+                expression
+            ))
+        })?;
+
+        if let Some(variable) = expression.strip_prefix('$') {
+            if !is_fluent_variable(variable) {
+                return Err(error(&format!(
+                    "${} can only use letters, digits, _ and .",
+                    variable
+                )));
+            }
+            Ok(vec![FluentPart::Text(format!("${{{}}}", variable))])
+        } else if let Some(text) = expression
+            .strip_prefix('"')
+            .and_then(|e| e.strip_suffix('"'))
+        {
+            Ok(vec![FluentPart::Text(text.to_owned())])
+        } else if self.entries.contains_key(expression) {
+            self.message(expression)
+        } else {
+            Err(error(&format!(
+                "'{{ {} }}' is not supported or not defined",
+                expression
+            )))
+        }
+    }
+
+    /// Reads the variants of a selector after its `->`, up to and including its `}`.
+    fn select(
+        &mut self,
+        line: usize,
+        variable: &str,
+        rest: &mut &str,
+    ) -> Result<Vec<FluentPart>, Error> {
+        let error = |message: &str| catalog_error(".ftl", line, message);
+        let mut variants = vec![];
+        let mut default = None;
+
+        loop {
+            *rest = rest.trim_start();
+            if let Some(after) = rest.strip_prefix('}') {
+                *rest = after;
+                break;
+            }
+
+            if let Some(after) = rest.strip_prefix('*') {
+                if default.replace(variants.len()).is_some() {
+                    return Err(error("a selector can only have one default *[variant]"));
+                }
+                *rest = after;
+            }
+            let (key, after) = rest
+                .strip_prefix('[')
+                .and_then(|r| r.split_once(']'))
+                .ok_or_else(|| error("expected a variant such as [one] or a closing }"))?;
+            *rest = after.trim_start_matches([' ', '\t']);
+
+            let mut value = self.pattern(line, rest, true)?;
+            if let Some(FluentPart::Text(text)) = value.last_mut() {
+                let trimmed = text.trim_end().len();
+                text.truncate(trimmed);
+            }
+            variants.push((key.trim().to_owned(), value));
+        }
+
+        match default {
+            Some(default) => Ok(vec![FluentPart::Select {
+                variable: variable.to_owned(),
+                variants,
+                default,
+            }]),
+            None => Err(error("a selector needs a default *[variant]")),
+        }
+    }
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a translation, e.g. `.message("Hello ${name}", "Hallo ${name}")`.
+    pub fn message(mut self, id: &str, translation: &str) -> Self {
+        self.messages
+            .insert(id.to_owned(), Translation::Text(translation.to_owned()));
+        self
+    }
+
+    /// Reads a gettext `.po` file. Fuzzy and untranslated entries are skipped, and the
+    /// forms of plural messages are picked with the `Plural-Forms` of the header.
+    pub fn from_po(source: &str) -> Result<Catalog, Error> {
+        let mut catalog = Catalog::new();
+        let mut entry = PoEntry::default();
+        // The string that a continuation line such as `"more"` adds to
+        let mut field: Option<&str> = None;
+        let mut index = 0;
+
+        for (i, line) in source.lines().enumerate() {
+            let number = i + 1;
+            let line = line.trim();
+
+            if line.is_empty() {
+                std::mem::take(&mut entry).add_to(&mut catalog)?;
+                field = None;
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                // Comments come before the entry they belong to
+                if !entry.translations.is_empty() {
+                    std::mem::take(&mut entry).add_to(&mut catalog)?;
+                    field = None;
+                }
+                if comment.starts_with(',') && comment.contains("fuzzy") {
+                    entry.fuzzy = true;
+                }
+                continue;
+            }
+
+            if line.starts_with('"') {
+                let string = po_string(number, line)?;
+                let target = match field {
+                    Some("msgctxt") => entry.context.as_mut(),
+                    Some("msgid") => entry.id.as_mut(),
+                    Some("msgstr") => entry.translations.get_mut(&index),
+                    Some("msgid_plural") => continue,
+                    _ => None,
+                };
+                match target {
+                    Some(target) => target.push_str(&string),
+                    None => return Err(catalog_error(".po", number, "string outside of an entry")),
+                }
+                continue;
+            }
+
+            let (keyword, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| catalog_error(".po", number, "expected a keyword and a string"))?;
+            let value = po_string(number, value)?;
+
+            // A new entry starts with its context or id after a translation
+            if matches!(keyword, "msgctxt" | "msgid") && !entry.translations.is_empty() {
+                std::mem::take(&mut entry).add_to(&mut catalog)?;
+            }
+
+            match keyword {
+                "msgctxt" => entry.context = Some(value),
+                "msgid" => {
+                    entry.line = number;
+                    entry.id = Some(value);
+                }
+                "msgid_plural" => entry.plural = true,
+                "msgstr" => {
+                    index = 0;
+                    entry.translations.insert(0, value);
+                }
+                _ => {
+                    index = keyword
+                        .strip_prefix("msgstr[")
+                        .and_then(|k| k.strip_suffix(']'))
+                        .and_then(|k| k.parse().ok())
+                        .ok_or_else(|| {
+                            catalog_error(".po", number, &format!("unknown keyword '{}'", keyword))
+                        })?;
+                    entry.translations.insert(index, value);
+                }
+            }
+            field = match keyword {
+                "msgctxt" | "msgid" | "msgid_plural" => Some(keyword),
+                _ => Some("msgstr"),
+            };
+        }
+        entry.add_to(&mut catalog)?;
+
+        Ok(catalog)
+    }
+
+    /// Reads a Fluent `.ftl` file, with attributes such as `.title` available as
+    /// `login.title`. Variables `{ $name }`, selectors such as
+    /// `{ $count -> [one] ... *[other] ... }`, terms, message references and string
+    /// literals are supported.
+    pub fn from_ftl(source: &str) -> Result<Catalog, Error> {
+        // Patterns by id, with the line they start on
+        let mut entries: HashMap<String, (usize, String)> = HashMap::new();
+        let mut order: Vec<String> = vec![];
+        let mut message: Option<String> = None;
+        let mut current: Option<String> = None;
+
+        for (i, line) in source.lines().enumerate() {
+            let number = i + 1;
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                message = None;
+                current = None;
+                continue;
+            }
+
+            // The } of a selector may close it at the start of a line
+            let indented = line.starts_with(char::is_whitespace);
+            if (indented && !trimmed.starts_with('.')) || line.starts_with('}') {
+                match current.as_ref().and_then(|c| entries.get_mut(c)) {
+                    Some((_, pattern)) => {
+                        if !pattern.is_empty() {
+                            pattern.push('\n');
+                        }
+                        pattern.push_str(trimmed);
+                    }
+                    None => return Err(catalog_error(".ftl", number, "text outside of a message")),
+                }
+                continue;
+            }
+
+            let (id, value) = trimmed
+                .split_once('=')
+                .ok_or_else(|| catalog_error(".ftl", number, "expected 'id = value'"))?;
+            let id = id.trim();
+            let is_identifier = |id: &str| {
+                id.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            };
+
+            let id = match (indented, id.strip_prefix('.'), &message) {
+                (true, Some(attribute), Some(message)) if is_identifier(attribute) => {
+                    format!("{}.{}", message, attribute)
+                }
+                (false, None, _) if is_identifier(id.strip_prefix('-').unwrap_or(id)) => {
+                    message = Some(id.to_owned());
+                    id.to_owned()
+                }
+                _ => {
+                    return Err(catalog_error(
+                        ".ftl",
+                        number,
+                        &format!("'{}' is not a valid id", id),
+                    ))
+                }
+            };
+
+            entries.insert(id.clone(), (number, value.trim().to_owned()));
+            order.push(id.clone());
+            current = Some(id);
+        }
+
+        let mut catalog = Catalog::new();
+        let mut reader = FluentReader {
+            entries: &entries,
+            seen: vec![],
+        };
+        for id in order {
+            // Terms are only used from other messages
+            if id.starts_with('-') {
+                continue;
+            }
+            let translation = match reader.message(&id)?.as_slice() {
+                [] => continue,
+                [FluentPart::Text(text)] => Translation::Text(text.clone()),
+                parts => Translation::Fluent(parts.to_vec()),
+            };
+            catalog.messages.insert(id, translation);
+        }
+
+        Ok(catalog)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<&Translation> {
+        self.messages.get(id)
+    }
+
+    /// Which of the forms of a gettext plural message a count uses, if the header has
+    /// `Plural-Forms`.
+    pub(crate) fn plural_index(&self, count: f64) -> Option<usize> {
+        self.plural_forms
+            .as_ref()
+            .map(|plural_forms| plural_forms.index(count))
+    }
+}
+
+/// A translatable message found in a template by `extract_messages`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractedMessage {
+    pub id: String,
+    /// The plural of a `_n("...", "...", n)` message.
+    pub plural: Option<String>,
+    /// The text of a `{#trans "key"#}` block that has a key as its id.
+    pub default: Option<String>,
+    /// Where the message was found, e.g. `email.html:3`.
+    pub reference: String,
+}
+
+fn po_quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Writes messages as a gettext template (`.pot`), listing each id once with every
+/// place it is used and the plural of `_n` messages.
+///
+/// ```
+/// use kitamura::{extract_messages, messages_template};
+///
+/// let messages = extract_messages("email.html", "{#trans#}Hi ${name}{#endtrans#}").unwrap();
+/// let pot = messages_template(&messages);
+/// assert!(pot.ends_with("#: email.html:1\nmsgid \"Hi ${name}\"\nmsgstr \"\"\n"));
+/// ```
+pub fn messages_template(messages: &[ExtractedMessage]) -> String {
+    let mut ids: Vec<&str> = vec![];
+    let mut references: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut defaults: HashMap<&str, &str> = HashMap::new();
+    let mut plurals: HashMap<&str, &str> = HashMap::new();
+    for message in messages {
+        if !references.contains_key(message.id.as_str()) {
+            ids.push(&message.id);
+        }
+        references
+            .entry(&message.id)
+            .or_default()
+            .push(&message.reference);
+        if let Some(default) = &message.default {
+            defaults.entry(&message.id).or_insert(default);
+        }
+        if let Some(plural) = &message.plural {
+            plurals.entry(&message.id).or_insert(plural);
+        }
+    }
+
+    let mut pot =
+        String::from("msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    for id in ids {
+        pot.push('\n');
+        if let Some(default) = defaults.get(id) {
+            pot.push_str(&format!("#. {}\n", default.replace('\n', " ")));
+        }
+        pot.push_str(&format!("#: {}\n", references[id].join(" ")));
+        pot.push_str(&format!("msgid {}\n", po_quote(id)));
+        match plurals.get(id) {
+            Some(plural) => pot.push_str(&format!(
+                "msgid_plural {}\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n",
+                po_quote(plural)
+            )),
+            None => pot.push_str("msgstr \"\"\n"),
+        }
+    }
+    pot
+}
@@ -0,0 +1,189 @@
+/// The `plural=` expression of a `.po` header, such as
+/// `plural=(n%10==1 && n%100!=11 ? 0 : n != 0 ? 1 : 2);`, which picks the `msgstr[i]`
+/// of a plural message for a count.
+#[derive(Debug, Clone)]
+pub struct PluralForms {
+    expression: Expression,
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    Count,
+    Number(u64),
+    Not(Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+}
+
+/// Binary operators from the loosest to the tightest binding, as in C.
+const LEVELS: [&[&str]; 6] = [
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let length = match c {
+            '0'..='9' => rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len()),
+            _ if ["&&", "||", "==", "!=", "<=", ">="]
+                .iter()
+                .any(|o| rest.starts_with(o)) =>
+            {
+                2
+            }
+            'n' | '!' | '<' | '>' | '?' | ':' | '(' | ')' | '%' | '*' | '/' | '+' | '-' => 1,
+            _ => return Err(format!("'{}' can not be used in a plural expression", c)),
+        };
+        tokens.push(rest[..length].to_owned());
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), String> {
+        match self.peek() {
+            Some(t) if t == token => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(format!("expected '{}' in the plural expression", token)),
+        }
+    }
+
+    fn conditional(&mut self) -> Result<Expression, String> {
+        let condition = self.binary(0)?;
+        if self.peek() != Some("?") {
+            return Ok(condition);
+        }
+        self.position += 1;
+        let then = self.conditional()?;
+        self.expect(":")?;
+        let otherwise = self.conditional()?;
+        Ok(Expression::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut left = self.binary(level + 1)?;
+        while let Some(operator) = self
+            .peek()
+            .and_then(|t| LEVELS[level].iter().find(|o| **o == t))
+        {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        let token = self
+            .peek()
+            .ok_or("the plural expression ends too early")?
+            .to_owned();
+        self.position += 1;
+        match token.as_str() {
+            "!" => Ok(Expression::Not(Box::new(self.unary()?))),
+            "n" => Ok(Expression::Count),
+            "(" => {
+                let expression = self.conditional()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            number => number
+                .parse()
+                .map(Expression::Number)
+                .map_err(|_| format!("unexpected '{}' in the plural expression", number)),
+        }
+    }
+}
+
+fn evaluate(expression: &Expression, n: u64) -> u64 {
+    match expression {
+        Expression::Count => n,
+        Expression::Number(number) => *number,
+        Expression::Not(expression) => u64::from(evaluate(expression, n) == 0),
+        Expression::Conditional(condition, then, otherwise) => match evaluate(condition, n) {
+            0 => evaluate(otherwise, n),
+            _ => evaluate(then, n),
+        },
+        Expression::Binary(operator, left, right) => {
+            let (left, right) = (evaluate(left, n), evaluate(right, n));
+            match *operator {
+                "||" => u64::from(left != 0 || right != 0),
+                "&&" => u64::from(left != 0 && right != 0),
+                "==" => u64::from(left == right),
+                "!=" => u64::from(left != right),
+                "<=" => u64::from(left <= right),
+                ">=" => u64::from(left >= right),
+                "<" => u64::from(left < right),
+                ">" => u64::from(left > right),
+                "+" => left.wrapping_add(right),
+                "-" => left.wrapping_sub(right),
+                "*" => left.wrapping_mul(right),
+                "/" => left.checked_div(right).unwrap_or_default(),
+                _ => left.checked_rem(right).unwrap_or_default(),
+            }
+        }
+    }
+}
+
+impl PluralForms {
+    /// Reads the `Plural-Forms` line of a `.po` header, if it has one.
+    pub fn from_header(header: &str) -> Result<Option<PluralForms>, String> {
+        let forms = header.lines().find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("Plural-Forms")
+                .then_some(value)
+        });
+        let source = match forms {
+            Some(forms) => forms
+                .split(';')
+                .find_map(|part| part.trim().strip_prefix("plural="))
+                .ok_or("Plural-Forms has no plural= expression")?,
+            None => return Ok(None),
+        };
+
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let expression = parser.conditional()?;
+        match parser.peek() {
+            Some(token) => Err(format!("unexpected '{}' in the plural expression", token)),
+            None => Ok(Some(PluralForms { expression })),
+        }
+    }
+
+    /// Which form a count uses. gettext only counts whole numbers, so any fraction is
+    /// dropped.
+    pub fn index(&self, count: f64) -> usize {
+        evaluate(&self.expression, count.abs() as u64) as usize
+    }
+}
@@ -0,0 +1,322 @@
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc, time::SystemTime};
+
+use crate::{
+    catalog::{Catalog, Translation},
+    error::{Error, TemplateResult},
+    template::{self, CompiledTemplate},
+};
+
+/// Finds the source of a template by name.
+type Loader = dyn Fn(&str) -> Option<String>;
+
+/// Tells the current time.
+type Clock = dyn Fn() -> SystemTime;
+
+/// Compiled templates by `trim_blocks`, `lstrip_blocks` and source, which is what
+/// parsing them depends on.
+type Compiled = HashMap<(bool, bool, String), Rc<CompiledTemplate>>;
+
+/// What happens when a template uses a variable or property that is not in the
+/// parameters, in output, loop sources and conditions alike.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum UndefinedBehavior {
+    /// Fails with `Error::UndefinedVariable` and the line of the tag. A condition may
+    /// still test a value directly, so `{#if user.admin#}` is false when it is missing.
+    #[default]
+    Strict,
+    /// Renders nothing, loops over nothing and is false in conditions.
+    Lenient,
+    /// Like `Lenient`, but renders a marker such as `[[undefined: user.x]]` instead.
+    Debug,
+}
+
+/// Locales are matched case-insensitively, with `pt_BR` the same as `pt-BR`.
+fn catalog_key(locale: &str) -> String {
+    locale.replace('_', "-").to_lowercase()
+}
+
+/// Settings that apply to every template rendered through it.
+///
+/// ```
+/// use std::collections::HashMap;
+/// use kitamura::Environment;
+/// use serde_json::json;
+///
+/// let environment = Environment::new().trim_blocks(false).lstrip_blocks(false);
+/// let params = HashMap::from([("names".to_owned(), json!([{"name": "Joel"}]))]);
+/// let output = environment
+///     .render_template("{#for n of names#}\n  ${n.name}\n{#endfor#}\n".to_owned(), params)
+///     .unwrap();
+/// assert_eq!(output, "\n  Joel\n\n");
+/// ```
+#[derive(Clone)]
+pub struct Environment {
+    pub(crate) trim_blocks: bool,
+    pub(crate) lstrip_blocks: bool,
+    pub(crate) max_recursion_depth: usize,
+    pub(crate) undefined_behavior: UndefinedBehavior,
+    pub(crate) locale: String,
+    fallback_locales: Vec<String>,
+    catalogs: HashMap<String, Rc<Catalog>>,
+    templates: HashMap<String, String>,
+    loader: Option<Rc<Loader>>,
+    clock: Option<Rc<Clock>>,
+    compiled: RefCell<Compiled>,
+}
+
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment")
+            .field("trim_blocks", &self.trim_blocks)
+            .field("lstrip_blocks", &self.lstrip_blocks)
+            .field("max_recursion_depth", &self.max_recursion_depth)
+            .field("undefined_behavior", &self.undefined_behavior)
+            .field("locale", &self.locale)
+            .field("fallback_locales", &self.fallback_locales)
+            .field("catalogs", &self.catalogs.keys())
+            .field("templates", &self.templates.keys())
+            .field("loader", &self.loader.is_some())
+            .field("clock", &self.clock.is_some())
+            .finish()
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment {
+            trim_blocks: true,
+            lstrip_blocks: true,
+            max_recursion_depth: 64,
+            undefined_behavior: UndefinedBehavior::Strict,
+            locale: "en".to_owned(),
+            fallback_locales: vec![],
+            catalogs: HashMap::new(),
+            templates: HashMap::new(),
+            loader: None,
+            clock: None,
+            compiled: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes the first new line after a `{#...#}` block tag. On by default.
+    pub fn trim_blocks(mut self, trim_blocks: bool) -> Self {
+        self.trim_blocks = trim_blocks;
+        self
+    }
+
+    /// Removes spaces and tabs from the start of a line up to a `{#...#}` block tag.
+    /// On by default.
+    pub fn lstrip_blocks(mut self, lstrip_blocks: bool) -> Self {
+        self.lstrip_blocks = lstrip_blocks;
+        self
+    }
+
+    /// How deeply macros may call themselves or each other before rendering fails.
+    /// 64 by default.
+    pub fn max_recursion_depth(mut self, max_recursion_depth: usize) -> Self {
+        self.max_recursion_depth = max_recursion_depth;
+        self
+    }
+
+    /// How missing variables are handled, see `UndefinedBehavior`. Strict by default.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use kitamura::{Environment, UndefinedBehavior};
+    ///
+    /// let environment = Environment::new().undefined_behavior(UndefinedBehavior::Debug);
+    /// let output = environment
+    ///     .render_template("Hi ${user.name}".to_owned(), HashMap::new())
+    ///     .unwrap();
+    /// assert_eq!(output, "Hi [[undefined: user.name]]");
+    /// ```
+    pub fn undefined_behavior(mut self, undefined_behavior: UndefinedBehavior) -> Self {
+        self.undefined_behavior = undefined_behavior;
+        self
+    }
+
+    /// The locale numbers, dates and messages are formatted in, e.g. `de-DE`. English by default. Settings
+    /// are per environment, so a clone can render in another locale.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use kitamura::Environment;
+    /// use serde_json::json;
+    ///
+    /// let environment = Environment::new();
+    /// let html = "${amount?string(\"#,##0.00 €\")}";
+    /// let params = HashMap::from([("amount".to_owned(), json!(1234.5))]);
+    ///
+    /// let output = environment.render_template(html.to_owned(), params.clone()).unwrap();
+    /// assert_eq!(output, "1,234.50 €");
+    ///
+    /// let output = environment.clone().locale("de-DE").render_template(html.to_owned(), params);
+    /// assert_eq!(output.unwrap(), "1.234,50 €");
+    /// ```
+    pub fn locale(mut self, locale: &str) -> Self {
+        self.locale = locale.to_owned();
+        self
+    }
+
+    /// Locales whose catalogs are tried, in order, when a message is not translated for
+    /// the locale or its language. Without any the text of the template is used.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use kitamura::{Catalog, Environment};
+    ///
+    /// let environment = Environment::new()
+    ///     .catalog("fr", Catalog::new().message("welcome", "Bienvenue"))
+    ///     .catalog("de", Catalog::new().message("welcome", "Willkommen"))
+    ///     .fallback_locales(&["fr"])
+    ///     .locale("nl");
+    /// let output = environment
+    ///     .render_template("${_('welcome')}".to_owned(), HashMap::new())
+    ///     .unwrap();
+    /// assert_eq!(output, "Bienvenue");
+    /// ```
+    pub fn fallback_locales(mut self, locales: &[&str]) -> Self {
+        self.fallback_locales = locales.iter().map(|l| l.to_string()).collect();
+        self
+    }
+
+    /// Registers the translations for a locale such as `de` or `pt-BR`, which
+    /// `{#trans#}` blocks and `_("...")` look messages up in.
+    pub fn catalog(mut self, locale: &str, mut catalog: Catalog) -> Self {
+        catalog.locale = catalog_key(locale);
+        self.catalogs
+            .insert(catalog.locale.clone(), Rc::new(catalog));
+        self
+    }
+
+    /// The translation of a message in the locale, its language or the fallback locales,
+    /// with the catalog it was found in.
+    pub(crate) fn translate(&self, id: &str) -> Option<(&Catalog, &Translation)> {
+        std::iter::once(&self.locale)
+            .chain(&self.fallback_locales)
+            .flat_map(|locale| {
+                let locale = catalog_key(locale);
+                let language = locale.split('-').next().unwrap_or_default().to_owned();
+                [locale, language]
+            })
+            .find_map(|locale| {
+                let catalog = self.catalogs.get(&locale)?;
+                Some((catalog.as_ref(), catalog.get(id)?))
+            })
+    }
+
+    /// Registers a template by name so that it can be imported.
+    pub fn template(mut self, name: &str, source: &str) -> Self {
+        self.templates.insert(name.to_owned(), source.to_owned());
+        self
+    }
+
+    /// Loads templates that were not registered with `template`, e.g. from disk.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use kitamura::Environment;
+    ///
+    /// let environment = Environment::new().loader(|name| match name {
+    ///     "ui.html" => Some("{#macro badge(n)#}<b>${n}</b>{#endmacro#}".to_owned()),
+    ///     _ => None,
+    /// });
+    /// let output = environment
+    ///     .render_template("{#from \"ui.html\" import badge#}${badge(3)}".to_owned(), HashMap::new())
+    ///     .unwrap();
+    /// assert_eq!(output, "<b>3</b>");
+    /// ```
+    pub fn loader(mut self, loader: impl Fn(&str) -> Option<String> + 'static) -> Self {
+        self.loader = Some(Rc::new(loader));
+        self
+    }
+
+    /// Where relative dates such as `?relative` measure from, which is the system clock
+    /// unless another is given, e.g. a fixed time in tests.
+    ///
+    /// ```
+    /// use std::{collections::HashMap, time::{Duration, SystemTime}};
+    /// use kitamura::Environment;
+    /// use serde_json::json;
+    ///
+    /// let environment =
+    ///     Environment::new().clock(|| SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+    /// let params = HashMap::from([("sent".to_owned(), json!("2023-11-11T22:13:20Z"))]);
+    /// let output = environment
+    ///     .render_template("Sent ${sent?relative}".to_owned(), params)
+    ///     .unwrap();
+    /// assert_eq!(output, "Sent 3 days ago");
+    /// ```
+    pub fn clock(mut self, clock: impl Fn() -> SystemTime + 'static) -> Self {
+        self.clock = Some(Rc::new(clock));
+        self
+    }
+
+    pub(crate) fn now(&self) -> SystemTime {
+        match &self.clock {
+            Some(clock) => clock(),
+            None => SystemTime::now(),
+        }
+    }
+
+    /// Compiles a template once, after which the same source is reused.
+    pub(crate) fn compile(&self, source: String) -> Result<Rc<CompiledTemplate>, Error> {
+        let key = (self.trim_blocks, self.lstrip_blocks, source);
+        if let Some(template) = self.compiled.borrow().get(&key) {
+            return Ok(template.clone());
+        }
+
+        let template = Rc::new(template::compile_template(key.2.clone(), self)?);
+        self.compiled.borrow_mut().insert(key, template.clone());
+        Ok(template)
+    }
+
+    pub(crate) fn load_template(&self, name: &str) -> Option<String> {
+        match self.templates.get(name) {
+            Some(source) => Some(source.clone()),
+            None => self.loader.as_ref().and_then(|loader| loader(name)),
+        }
+    }
+
+    pub fn render_template(
+        &self,
+        html: String,
+        parameters: HashMap<String, serde_json::Value>,
+    ) -> TemplateResult {
+        template::render_template(html, parameters, self)
+    }
+
+    /// Renders a template once into its `{#section "name"#}...{#endsection#}` blocks by
+    /// name, with everything outside of them in the `"default"` section. Sections that
+    /// never render are left out.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use kitamura::Environment;
+    /// use serde_json::json;
+    ///
+    /// let html = "{#section \"subject\"#}Order ${id} shipped{#endsection#}\n\
+    ///             {#section \"html\"#}<p>Order ${id} is on its way</p>{#endsection#}\n\
+    ///             Order ${id} is on its way";
+    /// let params = HashMap::from([("id".to_owned(), json!(42))]);
+    /// let sections = Environment::new()
+    ///     .render_sections(html.to_owned(), params)
+    ///     .unwrap();
+    /// assert_eq!(sections["subject"], "Order 42 shipped");
+    /// assert_eq!(sections["html"], "<p>Order 42 is on its way</p>");
+    /// assert_eq!(sections["default"], "Order 42 is on its way");
+    /// ```
+    pub fn render_sections(
+        &self,
+        html: String,
+        parameters: HashMap<String, serde_json::Value>,
+    ) -> Result<HashMap<String, String>, Error> {
+        template::render_sections(html, parameters, self)
+    }
+}
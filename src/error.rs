@@ -1,8 +1,33 @@
+use std::fmt;
+
 #[derive(Debug)]
 pub enum Error {
     InvalidSyntax(String),
     InvalidApi(String),
     Unknown(String),
+    /// A variable or property that is not in the parameters.
+    UndefinedVariable(String),
+    DivisionByZero(String),
+    /// A number too large to represent, e.g. from integer arithmetic.
+    Overflow(String),
+    /// An operation on values of the wrong type, e.g. `'a' * 2`.
+    TypeError(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSyntax(message)
+            | Error::InvalidApi(message)
+            | Error::Unknown(message)
+            | Error::UndefinedVariable(message)
+            | Error::DivisionByZero(message)
+            | Error::Overflow(message)
+            | Error::TypeError(message) => write!(f, "{}", message.trim()),
+        }
+    }
 }
 
+impl std::error::Error for Error {}
+
 pub type TemplateResult = Result<String, Error>;
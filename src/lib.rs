@@ -26,14 +26,163 @@
 //! # Features
 //! Variables
 //!
-//! Lists
+//! Lists - loops support:
+//!- `{#break#}` and `{#continue#}`, from any depth of `{#if#}` inside the loop
+//!- filter clauses e.g. `{#for fruit of fruits if fruit.ripe#}`
+//!- modifiers applied in this order: `unique by sku`, `sorted by price desc`, `offset 20`,
+//!  `limit 10` and `grouped by category`, where each group is a `{key, items}` object.
+//!  Sorting is stable, and mixed types order as null < bool < number < string < array < object
+//!- chunking e.g. `{#for row of products?chunk(3)#}` where each row can be looped over,
+//!  and `?chunk(3, null)` pads the last row with the given value
+//!- loop metadata: `loop.index`, `loop.index0`, `loop.first`, `loop.last`, `loop.length`
+//!
+//! Literal text - only `${`, `{#` and `{!--` start template syntax, so inline CSS, JavaScript
+//! and prices such as `$5` are left alone. A backslash keeps an opener as text e.g. `\${name}`
+//!
+//! Raw blocks - everything between `{#raw#}` and `{#endraw#}` (or `{#noparse#}` and `{#endnoparse#}`)
+//! is output exactly as written, e.g. client side templates
+//!
+//! Comments - `{!-- notes for other template authors --}` can span lines, are never parsed
+//! and never render
+//!
+//! Whitespace control:
+//!- `{#-`, `-#}`, `${- ` and `-}` trim all whitespace, including new lines, on that side of the tag,
+//!  while `${-a}` is a negation
+//!- `Environment` settings `trim_blocks` (drop the new line after a block tag) and `lstrip_blocks`
+//!  (drop indentation before a block tag) are on by default, turn both off for byte-exact output
+//!
+//! Expressions - `${...}`, `{#if#}`, `{#set#}` and macro arguments share one grammar:
+//!- arithmetic `+ - * / %` with JSON number semantics, integers stay integers where exact
+//!- `~` joins values as text e.g. `${first_name ~ " " ~ last_name}`
+//!- `'admin' in user.roles` and `not in`, matching like `?contains`
+//!- comparisons `== != < <= > >=`, `&&`/`and`, `||`/`or`, `not` and parentheses
+//!- division by zero, overflow and wrong types are `Error::DivisionByZero`, `Error::Overflow`
+//!  and `Error::TypeError`
+//!- inline conditionals `${is_active ? "active" : ""}` or `${is_active?then("active", "")}`,
+//!  only evaluating the chosen value. Leave a space after the `?` so it is not read as a builtin
+//!- defaults for missing or null values, including missing parts of a path, with
+//!  `${nickname!"friend"}`, `${nickname ?? user.name}` or `${nickname!}` for empty text
+//!
+//! String builtins - transform strings anywhere in an expression e.g. `${name?trim?title}`, and are a
+//! `Error::TypeError` on anything else:
+//!- `?upper`, `?lower`, `?title`, `?capitalize`, `?trim`, `?trim_start`, `?trim_end` and `?slugify`
+//!- `?pad_left(5, '0')` and `?pad_right(10)` pad to a width with spaces or the given character
+//!- `?truncate(20)` shortens to at most 20 characters ending in `...`, with the keywords
+//!  `ellipsis='…'` and `words=true` to only cut between words
+//!- `?word_wrap(72)` and `?indent(4)` (every line but the first unless `first=true`)
+//!- `?replace('from', 'to')`, `?split(',')` into a list and `?substring(start, end)`
+//!- `?length` counts Unicode scalar values, so `'日本語'?length` is 3
+//!
+//! Number formatting - `?string` formats a number in the locale set with `Environment::locale`:
+//!- patterns such as `${amount?string("#,##0.00")}`, `?string("€ #,##0.00")` or `?string("0.#%")`,
+//!  where `,` and `.` become the grouping and decimal separators of the locale
+//!- `?string("number")` (the default), `?string("integer")`, `?string("percent")` and
+//!  `?string("compact")` for forms such as `1.2K`
+//!- rounding is exact and half even by default, or `rounding=` any of `half_up`, `half_down`, `up`,
+//!  `down`, `ceiling` and `floor`
+//!- `locale="de"` for a single value. Locale data is bundled for en, en-IN, de, de-CH, fr, es, it,
+//!  nl, pt, pl, ru, sv, ja and zh, and anything else falls back to its language and then English
+//!
+//! Dates - ISO-8601 strings such as `2024-05-01T08:30:00+02:00` and seconds since 1970 are dates:
+//!- `${sent?date_format("d MMM yyyy, HH:mm")}` takes a CLDR pattern, where text is quoted like
+//!  `'at'`, or a strftime pattern such as `?date_format("%d %B %Y")`. The default is `yyyy-MM-dd`
+//!- month and weekday names follow the locale, or `locale="fr"` for a single value
+//!- `?date_format("HH:mm z", timezone="Europe/Berlin")` formats in a timezone and
+//!  `?timezone("Asia/Tokyo")` converts to one, with tz data bundled
+//!- `?relative` gives forms such as `3 days ago` or `in 2 hours` in English, measured from the
+//!  `Environment::clock` when the render started
+//!- `?date_add(3, "days")` and `?date_diff(other, "months")` count seconds, minutes, hours, days,
+//!  weeks, months or years, and `?epoch` gives seconds since 1970
+//!- `{#if due?before#}` and `?after` compare with another date or with now, e.g.
+//!  `{#if shipped?after(ordered)#}`
+//!
+//! Collections - builtins for lists and objects, which can be chained e.g.
+//! `${users?map("name")?join(" & ")}`:
+//!- `?size` counts the items of a list or object, or the characters of a string, as does `?length`
+//!- `?join(", ")` joins strings, numbers and booleans, and `?map("address.city")` takes an attribute
+//!  path from each item
+//!- `?sum`, `?min` and `?max` take an optional attribute path, e.g. `${lines?sum(attribute="total")}`.
+//!  Integers are added exactly
+//!- `?filter("active")` keeps items whose attribute is true or non-empty, and
+//!  `?filter("role", "admin")` those where it is equal to a value
+//!- `?sort`, `?sort("name", reverse=true)`, `?unique`, `?first`, `?last` and `?reverse`
+//!- `?min`, `?max`, `?first` and `?last` of an empty list are undefined, e.g. `${scores?max!"-"}`
+//!- `?keys` and `?values` of an object
+//!
+//! Sections - `render_sections` renders a template once into a map of its
+//! `{#section "subject"#}...{#endsection#}` blocks by name, e.g. the subject, text and HTML of an
+//! email from the same data. Everything outside of them goes to the `"default"` section, a section
+//! that renders more than once adds to itself, and `render_template` renders them in place
+//!
+//! Undefined variables - `Environment::undefined_behavior` sets how missing variables are handled
+//! in output, loop sources and conditions:
+//!- `UndefinedBehavior::Strict` (the default) is an `Error::UndefinedVariable` with the line of the
+//!  tag, though a condition may test a value directly e.g. `{#if user.admin#}` is false when missing
+//!- `UndefinedBehavior::Lenient` renders nothing, loops over nothing and is false in conditions
+//!- `UndefinedBehavior::Debug` renders a marker such as `[[undefined: user.x]]` instead
+//!
+//! Assignments:
+//!- `{#set total = order.subtotal + order.tax#}` takes any expression
+//!- `{#capture title#}...{#endcapture#}` renders its contents into a variable
+//!- assignments are local to the current loop iteration, `{#set global x = 1#}` and
+//!  `{#capture global x#}` assign for the rest of the render
+//!
+//! Macros:
+//!- `{#macro field(name, label, type="text")#}...{#endmacro#}` defines a reusable fragment,
+//!  called with positional and keyword arguments e.g. `${field("email", label="Email")}`
+//!- `{#call panel("Title")#}...{#endcall#}` passes a body that the macro renders with `${caller()}`
+//!- macros see their arguments and the template parameters, and may recurse up to
+//!  `Environment::max_recursion_depth` (64 by default)
+//!
+//! Imports - templates registered with `Environment::template` or found by `Environment::loader`
+//! can share their macros, without rendering any of their own text:
+//!- `{#import "forms.html" as forms#}` then `${forms.field("email", "Email")}`
+//!- `{#from "ui.html" import button, badge as b#}` then `${button("Save")}`
+//!- import cycles are an error that shows the chain of imports
+//!
+//! Plurals - `{#plural n#}{#one#}${n} file{#other#}${n} files{#endplural#}` renders the form for
+//! the CLDR plural category of the count in the environment's locale, which
+//! is `zero`, `one`, `two`, `few`, `many` or `other`. Forms the language doesn't use fall back to
+//! `{#other#}`, which every plural needs, and `${count?plural(one="file", other="files")}` does the same
+//!
+//! Translations - messages are looked up in the `Catalog`s of the environment's locale, then its
+//! language, then `Environment::fallback_locales`, and otherwise render as written:
+//!- `{#trans#}Hello ${name}!{#endtrans#}` has the message `Hello ${name}!`, with whitespace collapsed.
+//!  Only text and variables can be used inside, and `{#trans "greeting"#}` gives the message a key
+//!- `${_("Read more")}` translates a message, and `${_("Hello ${name}!", name=user.first)}` fills
+//!  placeholders from its arguments, or else from the variables in scope
+//!- `Catalog::from_po` reads gettext files and `Catalog::from_ftl` reads Fluent files, where
+//!  `{ $name }` is a placeholder and attributes are used as e.g. `_("login.title")`. `.po`
+//!  entries with a `msgctxt` are skipped, as templates have no way to give a context
+//!- `${_n("One file", "${count} files", files?size)}` has plural forms, picked by the
+//!  `Plural-Forms` of a `.po` catalog or else the plural rules of its locale, with the count
+//!  available as `${count}`
+//!- Fluent selectors such as `{ $count -> [one] Un fichier *[other] { $count } fichiers }` pick
+//!  their variant by the value, or its plural category in the locale of the catalog
+//!- `extract_messages` finds the messages of a template and `messages_template` writes them as a
+//!  `.pot` file, as does `kitamura extract <template>...`
+//!
+//! Switch - `{#switch order.status#}{#case "paid"#}...{#case "refunded", "void"#}...{#default#}...{#endswitch#}`
+//! renders the first matching case, comparing with the same typed equality as `==` in conditions
+//!
+//! Conditionals - `{#if flag#}` tests a value directly: `null`, `false`, `0`, `""`, `[]` and `{}`
+//! are false, as is a missing value, and everything else is true
 //!
 //!Conditionals - builtins:
-//!- ==
-//!- !=
+//!- == and != with typed equality, numbers compare by value so `1 == 1.0` but `1 != '1'`
 //!- ?exists
-//!- ?not_empty
-//!- ?contains('some substring value')
+//!- ?not_empty, true for numbers and booleans, false for null and empty strings, lists or objects
+//!- ?is_empty for strings, lists and objects, also true when missing or null
+//!- ?is_string, ?is_number, ?is_bool, ?is_null, ?is_array and ?is_object
+//!- ?has_content, the value exists, is not null and is not an empty string, list or object
+//!- ?contains('value') looks for a substring of a string, an equal element of a list or a key
+//!  of an object
+//!- ?has_key('email') looks for a key of an object
+//!- `?matches('^[A-Z]{2}\\d+$')` with a regular expression, where `\\` is a backslash. Patterns
+//!  are compiled with the template, once per environment, and invalid ones are reported before
+//!  anything renders
+//!- ?starts_with('SKU-') and ?ends_with('.pdf')
+//!- `ignore_case=true` on any of the three e.g. `?ends_with('.pdf', ignore_case=true)`
 //! # Examples
 //! ```
 //! use std::collections::HashMap;
@@ -244,7 +393,13 @@ use std::collections::HashMap;
 
 use error::TemplateResult;
 
+pub use catalog::{messages_template, Catalog, ExtractedMessage};
+pub use environment::{Environment, UndefinedBehavior};
+pub use error::Error;
+
 mod ast;
+mod catalog;
+mod environment;
 mod error;
 mod template;
 mod token;
@@ -253,5 +408,20 @@ pub fn render_template(
     html: String,
     parameters: HashMap<String, serde_json::Value>,
 ) -> TemplateResult {
-    template::render_template(html, parameters)
+    Environment::default().render_template(html, parameters)
+}
+
+/// Renders a template into its `{#section "name"#}` blocks by name, with everything
+/// outside of them in the `"default"` section.
+pub fn render_sections(
+    html: String,
+    parameters: HashMap<String, serde_json::Value>,
+) -> Result<HashMap<String, String>, Error> {
+    Environment::default().render_sections(html, parameters)
+}
+
+/// Finds the messages of `{#trans#}` blocks and `_("...")` calls in a template, for
+/// `messages_template` to write out. `name` is how the template is referred to.
+pub fn extract_messages(name: &str, html: &str) -> Result<Vec<ExtractedMessage>, Error> {
+    template::extract_messages(name, html)
 }
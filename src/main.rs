@@ -1,2 +1,31 @@
+use std::{env, fs, process};
+
+/// `kitamura extract <template>...` writes the messages of the templates as a gettext
+/// template (`.pot`) to standard output.
 #[cfg(not(tarpaulin_include))]
-fn main() {}
+fn main() {
+    let arguments: Vec<String> = env::args().skip(1).collect();
+    let files = match arguments.split_first() {
+        Some((command, files)) if command == "extract" && !files.is_empty() => files,
+        _ => {
+            eprintln!("usage: kitamura extract <template>...");
+            process::exit(2);
+        }
+    };
+
+    let mut messages = vec![];
+    for file in files {
+        let extracted = fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|html| kitamura::extract_messages(file, &html).map_err(|e| e.to_string()));
+        match extracted {
+            Ok(extracted) => messages.extend(extracted),
+            Err(e) => {
+                eprintln!("{}: {}", file, e);
+                process::exit(1);
+            }
+        }
+    }
+
+    print!("{}", kitamura::messages_template(&messages));
+}
@@ -1,49 +1,172 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, rc::Rc};
 
+mod assignment;
+mod builtins;
 mod conditional;
+mod expression;
+mod imports;
+mod iteration;
+mod locale;
+mod macros;
+mod patterns;
+mod plurals;
+mod sections;
+mod switch;
+mod translation;
 
 use crate::{
     ast::{construct_ast, ASTNode, ASTNodeIdentifier, Ast},
+    catalog::ExtractedMessage,
+    environment::{Environment, UndefinedBehavior},
     error::{Error, TemplateResult},
     token::{generate_tokens, parse_tokens},
 };
 
-fn validate_iterator(
-    node: &ASTNode,
-    node_iterator_name: &String,
-    open_loop_stack: &[String],
-) -> Result<(), String> {
-    if !open_loop_stack.contains(node_iterator_name) {
-        return Err(format!(
-            "\nError with variable: {}\n{}{}\nDoes not exist: {}\n",
-            node.value,
-            " ".to_string().repeat(23),
-            "^".to_string().repeat(node_iterator_name.len()),
-            node_iterator_name
-        ));
+/// Compiled patterns of `?matches` by pattern and whether they ignore case.
+pub type Patterns = HashMap<(String, bool), Rc<regex::Regex>>;
+
+/// A parsed template with the literal patterns of its `?matches` compiled. An
+/// environment keeps these, so that rendering the same source again reuses them.
+#[derive(Debug)]
+pub struct CompiledTemplate {
+    pub ast: Ast,
+    pub patterns: Patterns,
+}
+
+pub fn compile_template(
+    template_html: String,
+    environment: &Environment,
+) -> Result<CompiledTemplate, Error> {
+    let ast = construct_ast(parse_tokens(generate_tokens(template_html)), environment)?;
+    let mut patterns = Patterns::new();
+    patterns::compile_patterns(&ast, &mut patterns)?;
+    Ok(CompiledTemplate { ast, patterns })
+}
+
+/// Signals raised by `{#break#}` and `{#continue#}` that travel back up to the
+/// nearest enclosing loop.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum LoopControl {
+    #[default]
+    None,
+    Break,
+    Continue,
+}
+
+/// State shared across the whole render, as opposed to the parameters which are
+/// scoped to where in the template we currently are.
+#[derive(Debug)]
+pub struct RenderContext<'a> {
+    pub environment: &'a Environment,
+    /// The parameters the render was started with.
+    pub globals: HashMap<String, serde_json::Value>,
+    pub loop_control: LoopControl,
+    /// Variables from `{#set#}` and `{#capture#}`, innermost scope last. The first
+    /// scope is global to the render.
+    pub scopes: Vec<HashMap<String, serde_json::Value>>,
+    /// Macros by name for each template, the rendered template being the first.
+    pub modules: Vec<HashMap<String, Rc<macros::Macro>>>,
+    /// The template whose macros are visible where we currently are.
+    pub module: usize,
+    /// Templates imported so far by name, and the chain of imports being loaded.
+    pub imported: HashMap<String, usize>,
+    pub import_chain: Vec<String>,
+    /// Bodies of the `{#call#}` blocks currently being rendered, innermost last.
+    pub callers: Vec<macros::Caller>,
+    pub depth: usize,
+    /// Compiled patterns of `?matches`, from the templates in use and from patterns
+    /// that are only known when rendering.
+    pub regexes: Patterns,
+    /// The time when the render started, which relative dates are measured from.
+    pub now: chrono::DateTime<chrono::Utc>,
+    /// Output of `{#section#}` blocks by name when rendering with `render_sections`.
+    pub sections: Option<HashMap<String, String>>,
+}
+
+impl<'a> RenderContext<'a> {
+    pub fn new(environment: &'a Environment, globals: HashMap<String, serde_json::Value>) -> Self {
+        RenderContext {
+            environment,
+            globals,
+            loop_control: LoopControl::None,
+            scopes: vec![HashMap::new()],
+            modules: vec![HashMap::new()],
+            module: 0,
+            imported: HashMap::new(),
+            import_chain: vec![],
+            callers: vec![],
+            depth: 0,
+            regexes: HashMap::new(),
+            now: environment.now().into(),
+            sections: None,
+        }
+    }
+
+    /// Looks up an assigned variable, starting from the innermost scope.
+    pub fn lookup_variable(&self, key: &str) -> Option<&serde_json::Value> {
+        let mut segments = key.split('.');
+        let root = segments.next()?;
+        let mut value = self.scopes.iter().rev().find_map(|s| s.get(root))?;
+
+        for segment in segments {
+            value = match segment.parse::<usize>() {
+                Ok(index) if value.is_array() => value.get(index)?,
+                _ => value.get(segment)?,
+            };
+        }
+
+        Some(value)
     }
-    Ok(())
 }
 
-fn validate_property(
-    node: &ASTNode,
-    node_iterator_name: &String,
-    node_property_name: &str,
-    params: &HashMap<String, serde_json::Value>,
-    parent_params: &HashMap<String, serde_json::Value>,
-) -> Result<(), String> {
-    let x = parent_params.get(node_iterator_name).unwrap();
-    if params.get(node_property_name).is_none() && x.get(node_property_name).is_none() {
-        return Err(format!(
-            "\nError with variable: {}\n{}{}\n'{}' is not a property of '{}'\n",
-            node.value,
-            " ".to_string().repeat(24 + node_iterator_name.len()),
-            "^".to_string().repeat(node_property_name.len()),
-            node_property_name,
-            node_iterator_name
-        ));
+/// Looks up a parameter by key, following dotted paths such as `fruit.name`
+/// through assigned variables, loop iterators and nested objects.
+pub fn lookup_parameter<'a>(
+    key: &str,
+    params: &'a HashMap<String, serde_json::Value>,
+    parent_params: &'a HashMap<String, serde_json::Value>,
+    ctx: &'a RenderContext,
+) -> Option<&'a serde_json::Value> {
+    if let Some(value) = ctx.lookup_variable(key) {
+        return Some(value);
+    }
+
+    if let Some(value) = params.get(key) {
+        return Some(value);
     }
-    Ok(())
+
+    let mut segments = key.split('.');
+    let root = segments.next()?;
+    let mut value = parent_params.get(root).or_else(|| params.get(root))?;
+
+    for segment in segments {
+        value = match segment.parse::<usize>() {
+            Ok(index) if value.is_array() => value.get(index)?,
+            _ => value.get(segment)?,
+        };
+    }
+
+    Some(value)
+}
+
+/// Whether a name can be assigned to or imported as, e.g. `total` but not `order.total`.
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// What is between `${` and `}`, which may itself contain braces in strings e.g.
+/// `${_("Hi ${name}")}`.
+fn variable_contents(node: &ASTNode) -> &str {
+    &node.value[2..node.value.len() - 1]
+}
+
+/// Whether a variable is only a path such as `fruit.name`, rather than an expression.
+fn is_plain_path(variable: &str) -> bool {
+    variable
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
 }
 
 fn validate_loop_data(
@@ -66,18 +189,45 @@ fn validate_loop_data(
     }
 }
 
-fn generate_variable_data(
-    variable_key: &str,
+/// Strings render as they are, anything else renders as JSON without quotes.
+pub fn value_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        _ => serde_json::to_string(value).unwrap().replace('\"', ""),
+    }
+}
+
+/// Renders what is between `${` and `}`, which is evaluated as an expression whether
+/// it is a path such as `user.name` or something like `price * 2`.
+fn render_variable(
+    node_value_cleaned: &str,
     params: &HashMap<String, serde_json::Value>,
-) -> Result<String, String> {
-    let variable = params.get(variable_key);
-    //println!("Var: {:?}", variable);
-    match variable {
-        Some(e) => Ok(serde_json::to_string(e).unwrap().replace('\"', "")),
-        None => Err(format!(
-            "\n${{{}}} is missing from parameter data mapping.\n",
-            variable_key
-        )),
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let expression = expression::parse_expression(node_value_cleaned.trim())?;
+    let value = expression::evaluate(&expression, params, parent_params, ctx)?;
+    Ok(value_to_string(&value))
+}
+
+/// Handles a variable that is missing from the parameters according to the
+/// environment's `UndefinedBehavior`.
+pub fn undefined_variable(
+    node: &ASTNode,
+    name: &str,
+    message: String,
+    ctx: &RenderContext,
+) -> TemplateResult {
+    match ctx.environment.undefined_behavior {
+        UndefinedBehavior::Strict => match node.tokens.first() {
+            Some(token) => Err(Error::UndefinedVariable(format!(
+                "{}at line: {}:{}\n",
+                message, token.line_start, token.pos_start
+            ))),
+            None => Err(Error::UndefinedVariable(message)),
+        },
+        UndefinedBehavior::Lenient => Ok("".to_owned()),
+        UndefinedBehavior::Debug => Ok(format!("[[undefined: {}]]", name)),
     }
 }
 
@@ -86,145 +236,37 @@ pub fn generate_template(
     params: HashMap<String, serde_json::Value>,
     parent_params: HashMap<String, serde_json::Value>,
     loop_stack: Vec<String>,
+    ctx: &mut RenderContext,
 ) -> TemplateResult {
     let mut html = String::new();
-    let mut open_loop_stack: Vec<String> = loop_stack;
+    let open_loop_stack: Vec<String> = loop_stack;
 
     for node in ast.nodes {
         if node.identifier == ASTNodeIdentifier::NewLine {
             html.push_str(&node.value)
         } else if node.identifier == ASTNodeIdentifier::Loop {
-            let variable_iterator_name = node.value.split_whitespace().nth(1).unwrap().to_string();
-            open_loop_stack.push(variable_iterator_name.clone());
-            let the_node_str = node
-                .value
-                .replace("#}", "")
-                .split_whitespace()
-                .nth(3)
-                .unwrap()
-                .to_string();
-            let list_iterator_name = if the_node_str.contains('.') {
-                let the_split = the_node_str.split('.');
-
-                the_split.last().unwrap()
-            } else {
-                &the_node_str
-            };
-
-            let data_retrieval = if params.get(list_iterator_name).is_some() {
-                params.get(list_iterator_name)
-            } else {
-                parent_params.get(&the_node_str)
-            };
-
-            let list_data = match validate_loop_data(&node, data_retrieval) {
-                Ok(data) => data,
-                Err(e) => return Err(Error::InvalidSyntax(e)),
-            };
+            let loop_data =
+                iteration::evaluate_loop(&node, &params, &parent_params, &open_loop_stack, ctx)?;
 
-            let list_data_with_key_name = &list_data[&list_iterator_name].as_array();
-
-            // Depending on the root mapping, this needs to be handled
-            let loop_over = if list_data_with_key_name.is_some() {
-                list_data_with_key_name.unwrap()
-            } else {
-                list_data.as_array().unwrap()
-            };
-
-            for (i, item) in loop_over.iter().enumerate() {
-                let item_to_mapping = match item.as_object() {
-                    Some(data) => data,
-                    None => {
-                        return Err(Error::InvalidSyntax(format!(
-                            "Data was not contained inside of an object for list: {}",
-                            list_iterator_name
-                        )))
-                    }
-                };
-                let mut new_params = HashMap::new();
-
-                for (k, v) in item_to_mapping.iter() {
-                    new_params.insert(k.clone(), v.clone());
-                }
-
-                let key_data_from_params = params.get(list_iterator_name);
-                let mut clone_of_parent_params = parent_params.clone();
-                if let Some(key_data_from_params) = key_data_from_params {
-                    let x = key_data_from_params.as_object();
-                    let y = key_data_from_params.as_array();
-                    if let Some(x) = x {
-                        let item = x.get(list_iterator_name).unwrap();
-                        let the_item = item.get(i).unwrap();
-                        if !parent_params.contains_key(&variable_iterator_name) {
-                            clone_of_parent_params.remove(&variable_iterator_name);
-                        }
-                        clone_of_parent_params
-                            .insert(variable_iterator_name.clone(), the_item.clone());
-                    } else if let Some(y) = y {
-                        let the_item = y.get(i).unwrap();
-                        if !parent_params.contains_key(&variable_iterator_name) {
-                            clone_of_parent_params.remove(&variable_iterator_name);
-                        }
-                        clone_of_parent_params
-                            .insert(variable_iterator_name.clone(), the_item.clone());
-                    }
-                } else if parent_params.get(&variable_iterator_name).is_some() {
-                    clone_of_parent_params.remove(&variable_iterator_name);
-                    clone_of_parent_params.insert(variable_iterator_name.clone(), item.clone());
-                }
-
-                match generate_template(
-                    node.children.clone().unwrap(),
-                    new_params,
-                    clone_of_parent_params,
-                    open_loop_stack.clone(),
-                ) {
-                    Ok(data) => html.push_str(data.as_str()),
-                    Err(e) => return Err(e),
-                }
+            html.push_str(loop_data.as_str());
+        } else if node.identifier == ASTNodeIdentifier::Raw {
+            for child in node.children.unwrap().nodes {
+                html.push_str(&child.value);
             }
-            open_loop_stack.pop();
+        } else if node.identifier == ASTNodeIdentifier::Break {
+            ctx.loop_control = LoopControl::Break;
+            return Ok(html);
+        } else if node.identifier == ASTNodeIdentifier::Continue {
+            ctx.loop_control = LoopControl::Continue;
+            return Ok(html);
         } else if node.identifier == ASTNodeIdentifier::Variable {
-            //println!("{:#?}", node);
-            let node_value_cleaned = node.value.replace("${", "").replace('}', "");
-            if node.value.contains('.') {
-                let node_iterator_name = node_value_cleaned.split('.').next().unwrap().to_string();
-                let node_property_name = node_value_cleaned.split('.').last().unwrap();
-
-                match validate_iterator(&node, &node_iterator_name, &open_loop_stack) {
-                    Ok(()) => (),
-                    Err(e) => return Err(Error::InvalidSyntax(e)),
-                }
-
-                match validate_property(
-                    &node,
-                    &node_iterator_name,
-                    node_property_name,
-                    &params,
-                    &parent_params,
-                ) {
-                    Ok(()) => (),
-                    Err(e) => return Err(Error::InvalidSyntax(e)),
-                }
-
-                if parent_params.contains_key(&node_iterator_name) {
-                    let the_data = parent_params.get(&node_iterator_name).unwrap().as_object();
-                    if let Some(the_data) = the_data {
-                        let mut new_map = HashMap::new();
-
-                        for (k, v) in the_data.iter() {
-                            new_map.insert(k.clone(), v.clone());
-                        }
-                        if let Ok(data) = generate_variable_data(node_property_name, &new_map) {
-                            html.push_str(&data)
-                        }
-                    }
+            let node_value_cleaned = variable_contents(&node).to_owned();
+            match render_variable(&node_value_cleaned, &params, &parent_params, ctx) {
+                Ok(data) => html.push_str(&data),
+                Err(Error::UndefinedVariable(e)) => {
+                    html.push_str(&undefined_variable(&node, &node_value_cleaned, e, ctx)?)
                 }
-            } else {
-                match generate_variable_data(&node_value_cleaned, &params) {
-                    Ok(data) => html.push_str(&data),
-                    Err(e) => return Err(Error::InvalidSyntax(e)),
-                };
+                Err(e) => return Err(e),
             }
         } else if node.identifier == ASTNodeIdentifier::If {
             let condition_data = conditional::evaluate_condition(
@@ -232,10 +274,92 @@ pub fn generate_template(
                 params.clone(),
                 parent_params.clone(),
                 &open_loop_stack,
+                ctx,
             )?;
 
             html.push_str(condition_data.as_str());
-        } else if node.identifier == ASTNodeIdentifier::IfEnd {
+
+            // A break or continue inside of the condition ends this block early too
+            if ctx.loop_control != LoopControl::None {
+                return Ok(html);
+            }
+        } else if node.identifier == ASTNodeIdentifier::Switch {
+            let switch_data =
+                switch::evaluate_switch(&node, &params, &parent_params, &open_loop_stack, ctx)?;
+
+            html.push_str(switch_data.as_str());
+
+            // A break or continue inside of a case ends this block early too
+            if ctx.loop_control != LoopControl::None {
+                return Ok(html);
+            }
+        } else if node.identifier == ASTNodeIdentifier::Plural {
+            let plural_data =
+                plurals::evaluate_plural(&node, &params, &parent_params, &open_loop_stack, ctx)?;
+
+            html.push_str(plural_data.as_str());
+
+            // A break or continue inside of a form ends this block early too
+            if ctx.loop_control != LoopControl::None {
+                return Ok(html);
+            }
+        } else if node.identifier == ASTNodeIdentifier::Section {
+            let section_data =
+                sections::evaluate_section(&node, &params, &parent_params, &open_loop_stack, ctx)?;
+
+            html.push_str(section_data.as_str());
+
+            // A break or continue inside of the section ends this block early too
+            if ctx.loop_control != LoopControl::None {
+                return Ok(html);
+            }
+        } else if node.identifier == ASTNodeIdentifier::Trans {
+            html.push_str(&translation::evaluate_trans(
+                &node,
+                &params,
+                &parent_params,
+                ctx,
+            )?);
+        } else if node.identifier == ASTNodeIdentifier::Set {
+            html.push_str(&assignment::evaluate_set(
+                &node,
+                &params,
+                &parent_params,
+                ctx,
+            )?);
+        } else if node.identifier == ASTNodeIdentifier::Capture {
+            assignment::evaluate_capture(&node, &params, &parent_params, &open_loop_stack, ctx)?;
+
+            // A break or continue inside of the capture ends this block early too
+            if ctx.loop_control != LoopControl::None {
+                return Ok(html);
+            }
+        } else if node.identifier == ASTNodeIdentifier::Macro {
+            macros::define_macro(&node, ctx)?;
+        } else if node.identifier == ASTNodeIdentifier::Import
+            || node.identifier == ASTNodeIdentifier::FromImport
+        {
+            imports::evaluate_import(&node, ctx)?;
+        } else if node.identifier == ASTNodeIdentifier::Call {
+            let call_data =
+                macros::evaluate_call_block(&node, &params, &parent_params, &open_loop_stack, ctx)?;
+
+            html.push_str(call_data.as_str());
+
+            // A break or continue inside of the call body ends this block early too
+            if ctx.loop_control != LoopControl::None {
+                return Ok(html);
+            }
+        } else if node.identifier == ASTNodeIdentifier::IfEnd
+            || node.identifier == ASTNodeIdentifier::RawEnd
+            || node.identifier == ASTNodeIdentifier::CaptureEnd
+            || node.identifier == ASTNodeIdentifier::MacroEnd
+            || node.identifier == ASTNodeIdentifier::CallEnd
+            || node.identifier == ASTNodeIdentifier::SwitchEnd
+            || node.identifier == ASTNodeIdentifier::TransEnd
+            || node.identifier == ASTNodeIdentifier::PluralEnd
+            || node.identifier == ASTNodeIdentifier::SectionEnd
+        {
             //
         } else if node.identifier != ASTNodeIdentifier::LoopEnd {
             html.push_str(&node.value);
@@ -245,14 +369,57 @@ pub fn generate_template(
     Ok(html)
 }
 
+/// Compiles a template, or reuses it if the environment has compiled it before, and
+/// gets its render ready with the macros and imports defined.
+fn start_render<'a>(
+    template_html: String,
+    parameters: &HashMap<String, serde_json::Value>,
+    environment: &'a Environment,
+) -> Result<(Ast, RenderContext<'a>), Error> {
+    let template = environment.compile(template_html)?;
+    let mut ctx = RenderContext::new(environment, parameters.clone());
+    ctx.regexes = template.patterns.clone();
+
+    // Macros and imports at the top level can be used from anywhere in the template
+    imports::define_module(&template.ast, &mut ctx)?;
+
+    Ok((template.ast.clone(), ctx))
+}
+
 pub fn render_template(
     template_html: String,
     parameters: HashMap<String, serde_json::Value>,
+    environment: &Environment,
 ) -> TemplateResult {
-    let tokens = generate_tokens(template_html);
-    let parsed_tokens = parse_tokens(tokens);
-    let ast = construct_ast(parsed_tokens)?;
+    let (ast, mut ctx) = start_render(template_html, &parameters, environment)?;
     let loop_stack: Vec<String> = vec![];
 
-    generate_template(ast, parameters.clone(), parameters, loop_stack)
+    generate_template(ast, parameters.clone(), parameters, loop_stack, &mut ctx)
+}
+
+pub fn render_sections(
+    template_html: String,
+    parameters: HashMap<String, serde_json::Value>,
+    environment: &Environment,
+) -> Result<HashMap<String, String>, Error> {
+    let (ast, mut ctx) = start_render(template_html, &parameters, environment)?;
+    let loop_stack: Vec<String> = vec![];
+    ctx.sections = Some(HashMap::new());
+
+    let default = generate_template(ast, parameters.clone(), parameters, loop_stack, &mut ctx)?;
+    let mut sections = ctx.sections.take().unwrap_or_default();
+    sections.insert(sections::DEFAULT_SECTION.to_owned(), default);
+
+    Ok(sections)
+}
+
+pub fn extract_messages(name: &str, template_html: &str) -> Result<Vec<ExtractedMessage>, Error> {
+    let tokens = generate_tokens(template_html.to_owned());
+    let parsed_tokens = parse_tokens(tokens);
+    let ast = construct_ast(parsed_tokens, &Environment::default())?;
+    let mut messages = vec![];
+
+    translation::extract_messages(&ast, name, &mut messages)?;
+
+    Ok(messages)
 }
@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::ASTNode,
+    error::{Error, TemplateResult},
+    template::{
+        expression::{evaluate, parse_expression},
+        generate_template, is_valid_name, undefined_variable, RenderContext,
+    },
+};
+
+/// The contents of a tag without its opener and closer, e.g. `x = 1` for `{#set x = 1#}`.
+fn tag_contents<'a>(node: &'a ASTNode, opener: &str) -> &'a str {
+    node.value[opener.len()..node.value.len() - 2].trim()
+}
+
+fn assignment_error(node: &ASTNode, message: &str) -> Error {
+    let construct_token = node.tokens.first().unwrap();
+    Error::InvalidSyntax(format!(
+        "\n{}\n{} at line {}:{}\n",
+        message, node.value, construct_token.line_start, construct_token.pos_start
+    ))
+}
+
+/// Splits off an optional leading `global`, returning whether it was present.
+fn take_global(contents: &str) -> (bool, &str) {
+    match contents.strip_prefix("global ") {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, contents),
+    }
+}
+
+/// Assigns to the innermost scope, or to the outermost one when `global`.
+fn assign(ctx: &mut RenderContext, name: String, value: serde_json::Value, global: bool) {
+    let scope = match global {
+        true => ctx.scopes.first_mut(),
+        false => ctx.scopes.last_mut(),
+    };
+
+    if let Some(scope) = scope {
+        scope.insert(name, value);
+    }
+}
+
+/// `{#set [global] name = expression#}`, which renders nothing unless the expression is
+/// undefined in a lenient or debug render, in which case nothing is assigned.
+pub fn evaluate_set(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let (global, contents) = take_global(tag_contents(node, "{#set"));
+
+    let (name, expression) = match contents.split_once('=') {
+        Some((name, expression)) => (name.trim(), expression.trim()),
+        None => return Err(assignment_error(node, "Expected name = value in set")),
+    };
+
+    if !is_valid_name(name) {
+        return Err(assignment_error(
+            node,
+            &format!("'{}' is not a valid variable name", name),
+        ));
+    }
+
+    let value = match evaluate(&parse_expression(expression)?, params, parent_params, ctx) {
+        Err(Error::UndefinedVariable(e)) => return undefined_variable(node, expression, e, ctx),
+        value => value?,
+    };
+    assign(ctx, name.to_owned(), value, global);
+
+    Ok("".to_owned())
+}
+
+/// `{#capture [global] name#}…{#endcapture#}` renders its contents into a variable.
+pub fn evaluate_capture(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> Result<(), Error> {
+    let (global, name) = take_global(tag_contents(node, "{#capture"));
+
+    if !is_valid_name(name) {
+        return Err(assignment_error(
+            node,
+            &format!("'{}' is not a valid variable name", name),
+        ));
+    }
+
+    let captured = generate_template(
+        node.children.clone().unwrap(),
+        params.clone(),
+        parent_params.clone(),
+        open_loop_stack.to_owned(),
+        ctx,
+    )?;
+    assign(
+        ctx,
+        name.to_owned(),
+        serde_json::Value::String(captured),
+        global,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+mod collections;
+mod dates;
+mod numbers;
+mod strings;
+
+use crate::{
+    error::Error,
+    template::{
+        conditional::values_equal, expression::Arguments, patterns::regex,
+        plurals::apply_plural_builtin, value_to_string, RenderContext,
+    },
+};
+
+pub fn argument_error(name: &str, expected: &str) -> Error {
+    Error::InvalidApi(format!("?{} expects {}", name, expected))
+}
+
+/// Matches the arguments of a builtin to its parameters by position or by name. The
+/// first `required` parameters must be given, the rest are `None` when left out.
+pub fn bind_arguments<'a>(
+    name: &str,
+    arguments: &'a Arguments,
+    parameters: &[&str],
+    required: usize,
+) -> Result<Vec<Option<&'a serde_json::Value>>, Error> {
+    let (positional, keyword) = arguments;
+    if positional.len() > parameters.len() {
+        return Err(argument_error(
+            name,
+            &format!("at most {} arguments", parameters.len()),
+        ));
+    }
+
+    let mut bound: Vec<Option<&serde_json::Value>> = vec![None; parameters.len()];
+    for (i, value) in positional.iter().enumerate() {
+        bound[i] = Some(value);
+    }
+    for (keyword, value) in keyword {
+        match parameters.iter().position(|p| p == keyword) {
+            Some(i) if bound[i].is_none() => bound[i] = Some(value),
+            Some(_) => {
+                return Err(Error::InvalidApi(format!(
+                    "?{} was given '{}' more than once",
+                    name, keyword
+                )))
+            }
+            None => {
+                return Err(Error::InvalidApi(format!(
+                    "?{} has no parameter '{}'",
+                    name, keyword
+                )))
+            }
+        }
+    }
+
+    if let Some(i) = bound[..required].iter().position(|b| b.is_none()) {
+        return Err(Error::InvalidApi(format!(
+            "?{} is missing the argument '{}'",
+            name, parameters[i]
+        )));
+    }
+
+    Ok(bound)
+}
+
+pub fn string_argument<'a>(
+    name: &str,
+    parameter: &str,
+    value: &'a serde_json::Value,
+) -> Result<&'a str, Error> {
+    value
+        .as_str()
+        .ok_or_else(|| argument_error(name, &format!("{} to be a string", parameter)))
+}
+
+/// A count such as a width or length, which can't be negative.
+pub fn count_argument(
+    name: &str,
+    parameter: &str,
+    value: &serde_json::Value,
+) -> Result<usize, Error> {
+    value.as_u64().map(|count| count as usize).ok_or_else(|| {
+        argument_error(
+            name,
+            &format!("{} to be a whole number of 0 or more", parameter),
+        )
+    })
+}
+
+pub fn bool_argument(
+    name: &str,
+    parameter: &str,
+    value: &serde_json::Value,
+) -> Result<bool, Error> {
+    value
+        .as_bool()
+        .ok_or_else(|| argument_error(name, &format!("{} to be true or false", parameter)))
+}
+
+/// The value a builtin that transforms values is applied to, which has to be there.
+pub fn required_target(
+    name: &str,
+    target: Option<serde_json::Value>,
+) -> Result<serde_json::Value, Error> {
+    target.ok_or_else(|| {
+        Error::UndefinedVariable(format!("\n?{} was applied to a missing value\n", name))
+    })
+}
+
+/// The single positional argument of a builtin such as `?contains('x')`.
+fn single_argument<'a>(
+    name: &str,
+    arguments: &'a Arguments,
+) -> Result<&'a serde_json::Value, Error> {
+    match (arguments.0.as_slice(), arguments.1.is_empty()) {
+        ([argument], true) => Ok(argument),
+        _ => Err(argument_error(name, "one argument")),
+    }
+}
+
+/// Whether a string contains a substring, a list contains an element equal to the
+/// needle, or an object has the needle as a key.
+pub fn contains(
+    name: &str,
+    haystack: &serde_json::Value,
+    needle: &serde_json::Value,
+) -> Result<bool, Error> {
+    match (haystack, needle) {
+        (serde_json::Value::String(haystack), serde_json::Value::String(needle)) => {
+            Ok(haystack.contains(needle.as_str()))
+        }
+        (serde_json::Value::Array(items), _) => Ok(items.iter().any(|i| values_equal(i, needle))),
+        (serde_json::Value::Object(map), serde_json::Value::String(key)) => {
+            Ok(map.contains_key(key))
+        }
+        (serde_json::Value::String(_), _) | (serde_json::Value::Object(_), _) => {
+            Err(Error::TypeError(format!(
+                "\n{} needs a string to look for in {}, not {}\n",
+                name, haystack, needle
+            )))
+        }
+        _ => Err(Error::TypeError(format!(
+            "\n{} can only be used on a string, list or object, not {}\n",
+            name, haystack
+        ))),
+    }
+}
+
+fn has_key(name: &str, target: &serde_json::Value, key: &serde_json::Value) -> Result<bool, Error> {
+    match target {
+        serde_json::Value::Object(_) => contains(name, target, key),
+        _ => Err(Error::TypeError(format!(
+            "\n{} can only be used on an object, not {}\n",
+            name, target
+        ))),
+    }
+}
+
+/// Whether a string, list or object has nothing in it.
+fn is_empty(name: &str, target: &serde_json::Value) -> Result<bool, Error> {
+    match target {
+        serde_json::Value::String(s) => Ok(s.is_empty()),
+        serde_json::Value::Array(a) => Ok(a.is_empty()),
+        serde_json::Value::Object(o) => Ok(o.is_empty()),
+        _ => Err(Error::TypeError(format!(
+            "\n?{} can only be used on a string, list or object, not {}\n",
+            name, target
+        ))),
+    }
+}
+
+/// The pattern and `ignore_case=true` keyword of `?matches`, `?starts_with` and `?ends_with`.
+fn pattern_arguments<'a>(name: &str, arguments: &'a Arguments) -> Result<(&'a str, bool), Error> {
+    let ignore_case = match arguments.1.as_slice() {
+        [] => false,
+        [(keyword, serde_json::Value::Bool(ignore_case))] if keyword == "ignore_case" => {
+            *ignore_case
+        }
+        _ => {
+            return Err(argument_error(
+                name,
+                "a string and optionally ignore_case=true",
+            ))
+        }
+    };
+
+    match arguments.0.as_slice() {
+        [serde_json::Value::String(pattern)] => Ok((pattern, ignore_case)),
+        _ => Err(argument_error(
+            name,
+            "a string and optionally ignore_case=true",
+        )),
+    }
+}
+
+/// The text that patterns are matched against. Numbers match as they render, and
+/// missing or null values never match.
+fn pattern_target(name: &str, target: &Option<serde_json::Value>) -> Result<Option<String>, Error> {
+    match target {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(text)) => Ok(Some(text.clone())),
+        Some(number @ serde_json::Value::Number(_)) => Ok(Some(value_to_string(number))),
+        Some(value) => Err(Error::TypeError(format!(
+            "\n?{} can only be used on a string or number, not {}\n",
+            name, value
+        ))),
+    }
+}
+
+/// Applies a builtin such as `?exists` to a value, which is `None` when it is missing
+/// from the parameters.
+pub fn apply_builtin(
+    name: &str,
+    target: Option<serde_json::Value>,
+    arguments: Arguments,
+    ctx: &mut RenderContext,
+) -> Result<serde_json::Value, Error> {
+    let result = match name {
+        "exists" => target.is_some(),
+        "not_empty" | "has_content" => match &target {
+            None | Some(serde_json::Value::Null) => false,
+            Some(serde_json::Value::Bool(_) | serde_json::Value::Number(_)) => true,
+            Some(t) => !is_empty(name, t)?,
+        },
+        "is_empty" => match &target {
+            None | Some(serde_json::Value::Null) => true,
+            Some(t) => is_empty(name, t)?,
+        },
+        "is_string" => target.is_some_and(|t| t.is_string()),
+        "is_number" => target.is_some_and(|t| t.is_number()),
+        "is_bool" => target.is_some_and(|t| t.is_boolean()),
+        "is_null" => target.is_some_and(|t| t.is_null()),
+        "is_array" => target.is_some_and(|t| t.is_array()),
+        "is_object" => target.is_some_and(|t| t.is_object()),
+        "contains" => {
+            let needle = single_argument(name, &arguments)?;
+            match &target {
+                Some(target) => contains("?contains", target, needle)?,
+                None => false,
+            }
+        }
+        "has_key" => {
+            let key = single_argument(name, &arguments)?;
+            match &target {
+                Some(target) => has_key("?has_key", target, key)?,
+                None => false,
+            }
+        }
+        "matches" => {
+            let (pattern, ignore_case) = pattern_arguments(name, &arguments)?;
+            match pattern_target(name, &target)? {
+                Some(text) => regex(pattern, ignore_case, &mut ctx.regexes)?.is_match(&text),
+                None => false,
+            }
+        }
+        "starts_with" | "ends_with" => {
+            let (affix, ignore_case) = pattern_arguments(name, &arguments)?;
+            let (text, affix) = match (pattern_target(name, &target)?, ignore_case) {
+                (Some(text), true) => (text.to_lowercase(), affix.to_lowercase()),
+                (Some(text), false) => (text, affix.to_owned()),
+                (None, _) => return Ok(serde_json::Value::Bool(false)),
+            };
+            match name {
+                "starts_with" => text.starts_with(&affix),
+                _ => text.ends_with(&affix),
+            }
+        }
+        "size" | "keys" | "values" | "join" | "sum" | "min" | "max" | "map" | "filter" | "sort"
+        | "unique" | "first" | "last" | "reverse" => {
+            return collections::apply_collection_builtin(name, target, &arguments)
+        }
+        // ?length counts the characters of a string and the items of anything else
+        "length" if !matches!(target, Some(serde_json::Value::String(_))) => {
+            return collections::apply_collection_builtin(name, target, &arguments)
+        }
+        "string" => return numbers::format_number(name, target, &arguments, ctx),
+        "plural" => return apply_plural_builtin(name, target, &arguments, ctx),
+        "date_format" | "timezone" | "date_add" | "date_diff" | "before" | "after" | "epoch"
+        | "relative" => return dates::apply_date_builtin(name, target, &arguments, ctx),
+        _ => {
+            return match strings::apply_string_builtin(name, target, &arguments)? {
+                Some(value) => Ok(value),
+                None => Err(Error::InvalidApi(format!("Not valid api: {}", name))),
+            }
+        }
+    };
+
+    Ok(serde_json::Value::Bool(result))
+}
@@ -0,0 +1,239 @@
+use crate::{
+    error::Error,
+    template::{
+        builtins::{bind_arguments, bool_argument, required_target, string_argument},
+        conditional::values_equal,
+        expression::{is_truthy, Arguments},
+        iteration::{attribute_path, compare_values, sort_by_key, unique_by_key},
+        value_to_string,
+    },
+};
+
+fn list_target(
+    name: &str,
+    target: Option<serde_json::Value>,
+) -> Result<Vec<serde_json::Value>, Error> {
+    match required_target(name, target)? {
+        serde_json::Value::Array(items) => Ok(items),
+        value => Err(Error::TypeError(format!(
+            "\n?{} can only be used on a list, not {}\n",
+            name, value
+        ))),
+    }
+}
+
+fn path_argument<'a>(
+    name: &str,
+    value: Option<&'a serde_json::Value>,
+) -> Result<Option<&'a str>, Error> {
+    value
+        .map(|path| string_argument(name, "attribute", path))
+        .transpose()
+}
+
+/// The item itself, or what its attribute path leads to, which has to be there.
+fn required_attribute<'a>(
+    name: &str,
+    item: &'a serde_json::Value,
+    path: Option<&str>,
+) -> Result<&'a serde_json::Value, Error> {
+    let path = match path {
+        Some(path) => path,
+        None => return Ok(item),
+    };
+
+    attribute_path(item, path).ok_or_else(|| {
+        Error::UndefinedVariable(format!("\n?{} found no '{}' in {}\n", name, path, item))
+    })
+}
+
+/// An empty list has no first, last, smallest or largest item, which is undefined like
+/// a missing variable so that defaults such as `?min!'none'` apply.
+fn empty_list_error(name: &str) -> Error {
+    Error::UndefinedVariable(format!("\n?{} of an empty list has no value\n", name))
+}
+
+/// The item itself, or what its attribute path leads to, which is null when missing.
+fn optional_attribute(item: &serde_json::Value, path: Option<&str>) -> serde_json::Value {
+    match path {
+        Some(path) => attribute_path(item, path).cloned().unwrap_or_default(),
+        None => item.clone(),
+    }
+}
+
+/// Adds integers exactly and only falls back to floating point when a value has
+/// decimals.
+fn sum(name: &str, values: &[&serde_json::Value]) -> Result<serde_json::Value, Error> {
+    let mut integer: i128 = 0;
+    let mut float: Option<f64> = None;
+    for value in values {
+        let number = match value {
+            serde_json::Value::Number(number) => number,
+            value => {
+                return Err(Error::TypeError(format!(
+                    "\n?{} can only add numbers, not {}\n",
+                    name, value
+                )))
+            }
+        };
+
+        match (number.as_i64(), number.as_u64()) {
+            (Some(n), _) => integer += i128::from(n),
+            (_, Some(n)) => integer += i128::from(n),
+            _ => *float.get_or_insert(0.0) += number.as_f64().unwrap_or_default(),
+        }
+    }
+
+    if let Some(float) = float {
+        return Ok((integer as f64 + float).into());
+    }
+    match (i64::try_from(integer), u64::try_from(integer)) {
+        (Ok(n), _) => Ok(n.into()),
+        (_, Ok(n)) => Ok(n.into()),
+        _ => Err(Error::Overflow(format!(
+            "\n?{} of {} is too large to represent\n",
+            name, integer
+        ))),
+    }
+}
+
+/// Applies a builtin for lists and objects, e.g. `${users?map("name")?join(", ")}`.
+/// `?size`, `?keys` and `?values` also work on objects, the rest only on lists.
+pub fn apply_collection_builtin(
+    name: &str,
+    target: Option<serde_json::Value>,
+    arguments: &Arguments,
+) -> Result<serde_json::Value, Error> {
+    let value = match name {
+        "size" | "length" => {
+            bind_arguments(name, arguments, &[], 0)?;
+            match required_target(name, target)? {
+                serde_json::Value::Array(items) => items.len().into(),
+                serde_json::Value::Object(map) => map.len().into(),
+                serde_json::Value::String(text) => text.chars().count().into(),
+                value => {
+                    return Err(Error::TypeError(format!(
+                        "\n?{} can only be used on a list, object or string, not {}\n",
+                        name, value
+                    )))
+                }
+            }
+        }
+        "keys" | "values" => {
+            bind_arguments(name, arguments, &[], 0)?;
+            match required_target(name, target)? {
+                serde_json::Value::Object(map) if name == "keys" => {
+                    map.keys().cloned().map(serde_json::Value::String).collect()
+                }
+                serde_json::Value::Object(map) => map.values().cloned().collect(),
+                value => {
+                    return Err(Error::TypeError(format!(
+                        "\n?{} can only be used on an object, not {}\n",
+                        name, value
+                    )))
+                }
+            }
+        }
+        "join" => {
+            let items = list_target(name, target)?;
+            let bound = bind_arguments(name, arguments, &["separator", "attribute"], 0)?;
+            let separator = match bound[0] {
+                Some(separator) => string_argument(name, "separator", separator)?,
+                None => "",
+            };
+            let path = path_argument(name, bound[1])?;
+
+            let mut parts = vec![];
+            for item in &items {
+                match required_attribute(name, item, path)? {
+                    serde_json::Value::Null => parts.push(String::new()),
+                    value @ (serde_json::Value::Array(_) | serde_json::Value::Object(_)) => {
+                        return Err(Error::TypeError(format!(
+                            "\n?{} can only join strings, numbers and booleans, not {}\n",
+                            name, value
+                        )))
+                    }
+                    value => parts.push(value_to_string(value)),
+                }
+            }
+            parts.join(separator).into()
+        }
+        "sum" | "min" | "max" | "map" => {
+            let items = list_target(name, target)?;
+            let required = if name == "map" { 1 } else { 0 };
+            let bound = bind_arguments(name, arguments, &["attribute"], required)?;
+            let path = path_argument(name, bound[0])?;
+            let values = items
+                .iter()
+                .map(|item| required_attribute(name, item, path))
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            match name {
+                "sum" => sum(name, &values)?,
+                "map" => values.into_iter().cloned().collect(),
+                "min" => values
+                    .into_iter()
+                    .min_by(|a, b| compare_values(a, b))
+                    .cloned()
+                    .ok_or_else(|| empty_list_error(name))?,
+                _ => values
+                    .into_iter()
+                    .max_by(|a, b| compare_values(a, b))
+                    .cloned()
+                    .ok_or_else(|| empty_list_error(name))?,
+            }
+        }
+        "filter" => {
+            let items = list_target(name, target)?;
+            let bound = bind_arguments(name, arguments, &["attribute", "value"], 0)?;
+            let path = path_argument(name, bound[0])?;
+            items
+                .into_iter()
+                .filter(|item| {
+                    let value = optional_attribute(item, path);
+                    match bound[1] {
+                        Some(expected) => values_equal(&value, expected),
+                        None => is_truthy(&value),
+                    }
+                })
+                .collect()
+        }
+        "sort" | "unique" => {
+            let mut items = list_target(name, target)?;
+            let parameters: &[&str] = match name {
+                "sort" => &["attribute", "reverse"],
+                _ => &["attribute"],
+            };
+            let bound = bind_arguments(name, arguments, parameters, 0)?;
+            let path = path_argument(name, bound[0])?;
+
+            if name == "sort" {
+                let reverse = match bound.get(1).copied().flatten() {
+                    Some(reverse) => bool_argument(name, "reverse", reverse)?,
+                    None => false,
+                };
+                sort_by_key(items, |item| optional_attribute(item, path), reverse).into()
+            } else {
+                unique_by_key(&mut items, |item| optional_attribute(item, path));
+                items.into()
+            }
+        }
+        _ => {
+            bind_arguments(name, arguments, &[], 0)?;
+            let mut items = list_target(name, target)?;
+            match name {
+                "first" => items
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| empty_list_error(name))?,
+                "last" => items.pop().ok_or_else(|| empty_list_error(name))?,
+                _ => {
+                    items.reverse();
+                    items.into()
+                }
+            }
+        }
+    };
+
+    Ok(value)
+}
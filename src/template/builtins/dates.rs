@@ -0,0 +1,444 @@
+use std::fmt::Display;
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Datelike, FixedOffset, Months, NaiveDate, NaiveDateTime, Offset, SecondsFormat,
+    TimeDelta, TimeZone, Timelike,
+};
+use chrono_tz::Tz;
+
+use crate::{
+    error::Error,
+    template::{
+        builtins::{argument_error, bind_arguments, required_target, string_argument},
+        expression::Arguments,
+        locale::{find_locale, Locale},
+        RenderContext,
+    },
+};
+
+/// Reads an ISO-8601 date such as `2024-05-01`, `2024-05-01T08:30:00` or
+/// `2024-05-01T08:30:00+02:00`, or a number of seconds since 1970. Dates without an
+/// offset are in UTC.
+fn parse_date(name: &str, value: &serde_json::Value) -> Result<DateTime<FixedOffset>, Error> {
+    let utc = match value {
+        serde_json::Value::Number(number) => match (number.as_i64(), number.as_f64()) {
+            (Some(seconds), _) => DateTime::from_timestamp(seconds, 0),
+            (None, Some(seconds)) => {
+                let whole = seconds.floor();
+                let nanoseconds = ((seconds - whole) * 1e9).min(999_999_999.0) as u32;
+                DateTime::from_timestamp(whole as i64, nanoseconds)
+            }
+            _ => None,
+        },
+        serde_json::Value::String(text) => {
+            if let Ok(date) = DateTime::parse_from_rfc3339(text) {
+                return Ok(date);
+            }
+            [
+                "%Y-%m-%dT%H:%M:%S%.f",
+                "%Y-%m-%d %H:%M:%S%.f",
+                "%Y-%m-%dT%H:%M",
+                "%Y-%m-%d %H:%M",
+            ]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+            .or_else(|| {
+                NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+            .map(|date| date.and_utc())
+        }
+        _ => None,
+    };
+
+    utc.map(|date| date.fixed_offset()).ok_or_else(|| {
+        Error::TypeError(format!(
+            "\n?{} needs an ISO-8601 date or seconds since 1970, not {}\n",
+            name, value
+        ))
+    })
+}
+
+/// The date a builtin is applied to, or that it compares with, which is now if left out.
+fn other_date(
+    name: &str,
+    value: Option<&serde_json::Value>,
+    ctx: &RenderContext,
+) -> Result<DateTime<FixedOffset>, Error> {
+    match value {
+        Some(value) => parse_date(name, value),
+        None => Ok(ctx.now.fixed_offset()),
+    }
+}
+
+fn parse_timezone(name: &str, timezone: &str) -> Result<Tz, Error> {
+    timezone.parse::<Tz>().map_err(|_| {
+        Error::InvalidApi(format!(
+            "?{} was given the unknown timezone '{}'",
+            name, timezone
+        ))
+    })
+}
+
+/// A date as ISO-8601 text, so that it can be formatted or compared again.
+fn date_value<Z: TimeZone>(date: DateTime<Z>) -> serde_json::Value
+where
+    Z::Offset: Display,
+{
+    date.to_rfc3339_opts(SecondsFormat::AutoSi, true).into()
+}
+
+fn format_offset(seconds: i32, colon: bool, zulu: bool) -> String {
+    if zulu && seconds == 0 {
+        return "Z".to_owned();
+    }
+
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let (hours, minutes) = (seconds.abs() / 3600, seconds.abs() % 3600 / 60);
+    let colon = if colon { ":" } else { "" };
+    format!("{}{:02}{}{:02}", sign, hours, colon, minutes)
+}
+
+/// A field of a CLDR pattern such as `yyyy` or `MMM`, given by its letter and length.
+fn format_field<Z: TimeZone>(
+    name: &str,
+    date: &DateTime<Z>,
+    letter: char,
+    count: usize,
+    locale: &Locale,
+) -> Result<String, Error>
+where
+    Z::Offset: Display,
+{
+    let number = |value: u32| format!("{:0width$}", value, width = count);
+    let names = locale.dates;
+    let month = date.month0() as usize;
+    let weekday = date.weekday().num_days_from_monday() as usize;
+    let offset = date.offset().fix().local_minus_utc();
+
+    let field = match (letter, count) {
+        ('y', 2) => format!("{:02}", date.year().rem_euclid(100)),
+        ('y', _) => format!("{:0width$}", date.year(), width = count),
+        ('M', 1 | 2) => number(date.month()),
+        ('M', 3) => names.months_short[month].to_owned(),
+        ('M', _) => names.months[month].to_owned(),
+        ('d', _) => number(date.day()),
+        ('E', 1..=3) => names.weekdays_short[weekday].to_owned(),
+        ('E', _) => names.weekdays[weekday].to_owned(),
+        ('H', _) => number(date.hour()),
+        ('h', _) => number(date.hour12().1),
+        ('m', _) => number(date.minute()),
+        ('s', _) => number(date.second()),
+        ('S', _) => format!("{:09}", date.nanosecond() % 1_000_000_000)[..count.min(9)].to_owned(),
+        ('a', _) => names.day_periods[(date.hour() >= 12) as usize].to_owned(),
+        ('z', _) => date.offset().to_string(),
+        ('Z', 1..=3) => format_offset(offset, false, false),
+        ('Z', _) => format_offset(offset, true, true),
+        ('X', _) => format_offset(offset, count >= 3, true),
+        ('x', _) => format_offset(offset, count >= 3, false),
+        _ => {
+            return Err(Error::InvalidApi(format!(
+                "?{} has no date field '{}', text in a pattern is quoted like 'at'",
+                name, letter
+            )))
+        }
+    };
+
+    Ok(field)
+}
+
+/// Formats with a CLDR pattern such as `d MMMM yyyy, HH:mm`, where text is quoted like
+/// `'at'` and `''` is a single quote.
+fn format_cldr<Z: TimeZone>(
+    name: &str,
+    date: &DateTime<Z>,
+    pattern: &str,
+    locale: &Locale,
+) -> Result<String, Error>
+where
+    Z::Offset: Display,
+{
+    let mut formatted = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\'' {
+            if chars.next_if_eq(&'\'').is_some() {
+                formatted.push('\'');
+                continue;
+            }
+            while let Some(c) = chars.next() {
+                match c {
+                    '\'' if chars.next_if_eq(&'\'').is_some() => formatted.push('\''),
+                    '\'' => break,
+                    c => formatted.push(c),
+                }
+            }
+        } else if c.is_ascii_alphabetic() {
+            let mut count = 1;
+            while chars.next_if_eq(&c).is_some() {
+                count += 1;
+            }
+            formatted.push_str(&format_field(name, date, c, count, locale)?);
+        } else {
+            formatted.push(c);
+        }
+    }
+
+    Ok(formatted)
+}
+
+/// Formats with a strftime pattern such as `%d %B %Y`, with the names of months,
+/// weekdays and `%p` taken from the locale.
+fn format_strftime<Z: TimeZone>(
+    name: &str,
+    date: &DateTime<Z>,
+    pattern: &str,
+    locale: &Locale,
+) -> Result<String, Error>
+where
+    Z::Offset: Display,
+{
+    let names = locale.dates;
+    let month = date.month0() as usize;
+    let weekday = date.weekday().num_days_from_monday() as usize;
+
+    let mut localized = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            localized.push(c);
+            continue;
+        }
+        let text = match chars.next() {
+            Some('B') => names.months[month],
+            Some('b' | 'h') => names.months_short[month],
+            Some('A') => names.weekdays[weekday],
+            Some('a') => names.weekdays_short[weekday],
+            Some('p') => names.day_periods[(date.hour() >= 12) as usize],
+            next => {
+                localized.push('%');
+                localized.extend(next);
+                continue;
+            }
+        };
+        localized.push_str(&text.replace('%', "%%"));
+    }
+
+    let items: Vec<Item> = StrftimeItems::new(&localized).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(argument_error(
+            name,
+            "a valid strftime pattern such as '%Y-%m-%d'",
+        ));
+    }
+    Ok(date.format_with_items(items.into_iter()).to_string())
+}
+
+fn format_date<Z: TimeZone>(
+    name: &str,
+    date: &DateTime<Z>,
+    pattern: &str,
+    locale: &Locale,
+) -> Result<String, Error>
+where
+    Z::Offset: Display,
+{
+    match pattern.contains('%') {
+        true => format_strftime(name, date, pattern, locale),
+        false => format_cldr(name, date, pattern, locale),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unit {
+    Seconds,
+    Minutes,
+    Hours,
+    Days,
+    Weeks,
+    Months,
+    Years,
+}
+
+impl Unit {
+    fn parse(name: &str, unit: &str) -> Result<Unit, Error> {
+        match unit.strip_suffix('s').unwrap_or(unit) {
+            "second" => Ok(Unit::Seconds),
+            "minute" => Ok(Unit::Minutes),
+            "hour" => Ok(Unit::Hours),
+            "day" => Ok(Unit::Days),
+            "week" => Ok(Unit::Weeks),
+            "month" => Ok(Unit::Months),
+            "year" => Ok(Unit::Years),
+            _ => Err(argument_error(
+                name,
+                "a unit of seconds, minutes, hours, days, weeks, months or years",
+            )),
+        }
+    }
+
+    /// The length of the unit, for units that always have the same length.
+    fn seconds(self) -> Option<i64> {
+        match self {
+            Unit::Seconds => Some(1),
+            Unit::Minutes => Some(60),
+            Unit::Hours => Some(3600),
+            Unit::Days => Some(86_400),
+            Unit::Weeks => Some(604_800),
+            Unit::Months | Unit::Years => None,
+        }
+    }
+}
+
+fn add_months(date: DateTime<FixedOffset>, months: i64) -> Option<DateTime<FixedOffset>> {
+    let count = Months::new(u32::try_from(months.unsigned_abs()).ok()?);
+    match months < 0 {
+        true => date.checked_sub_months(count),
+        false => date.checked_add_months(count),
+    }
+}
+
+/// Adds a whole number of units, keeping the offset of the date. Adding a month to
+/// 31 January gives the last day of February.
+fn add(
+    name: &str,
+    date: DateTime<FixedOffset>,
+    amount: i64,
+    unit: Unit,
+) -> Result<DateTime<FixedOffset>, Error> {
+    let added = match unit {
+        Unit::Months => add_months(date, amount),
+        Unit::Years => amount.checked_mul(12).and_then(|m| add_months(date, m)),
+        unit => amount
+            .checked_mul(unit.seconds().unwrap())
+            .and_then(TimeDelta::try_seconds)
+            .and_then(|delta| date.checked_add_signed(delta)),
+    };
+
+    added.ok_or_else(|| argument_error(name, "an amount that keeps the date in range"))
+}
+
+/// The whole number of units from `from` to `to`, negative if `to` is earlier.
+fn difference(from: DateTime<FixedOffset>, to: DateTime<FixedOffset>, unit: Unit) -> i64 {
+    if let Some(seconds) = unit.seconds() {
+        return (to - from).num_seconds() / seconds;
+    }
+
+    let from = from.with_timezone(to.offset());
+    let mut months =
+        i64::from(to.year() - from.year()) * 12 + to.month() as i64 - from.month() as i64;
+    match add_months(from, months) {
+        Some(date) if months > 0 && date > to => months -= 1,
+        Some(date) if months < 0 && date < to => months += 1,
+        _ => {}
+    }
+
+    match unit {
+        Unit::Years => months / 12,
+        _ => months,
+    }
+}
+
+/// How long ago or from now a date is in English, rounded to the largest sensible
+/// unit, e.g. `3 days ago` or `in 2 hours`.
+fn relative(date: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> String {
+    let seconds = (date - now).num_seconds();
+    let elapsed = seconds.unsigned_abs();
+    let rounded = |unit: u64| ((elapsed + unit / 2) / unit).max(1);
+
+    let (count, unit) = match elapsed {
+        0..45 => return "just now".to_owned(),
+        45..2_700 => (rounded(60), "minute"),
+        2_700..79_200 => (rounded(3_600), "hour"),
+        79_200..2_246_400 => (rounded(86_400), "day"),
+        2_246_400..27_648_000 => (rounded(2_592_000), "month"),
+        _ => (rounded(31_536_000), "year"),
+    };
+    let plural = if count == 1 { "" } else { "s" };
+
+    match seconds < 0 {
+        true => format!("{} {}{} ago", count, unit, plural),
+        false => format!("in {} {}{}", count, unit, plural),
+    }
+}
+
+/// Applies a builtin for dates, e.g. `${created?date_format('d MMM yyyy')}` or
+/// `{#if due?before#}`.
+pub fn apply_date_builtin(
+    name: &str,
+    target: Option<serde_json::Value>,
+    arguments: &Arguments,
+    ctx: &RenderContext,
+) -> Result<serde_json::Value, Error> {
+    let date = parse_date(name, &required_target(name, target)?)?;
+
+    let value = match name {
+        "date_format" => {
+            let bound = bind_arguments(name, arguments, &["format", "timezone", "locale"], 0)?;
+            let pattern = match bound[0] {
+                Some(pattern) => string_argument(name, "format", pattern)?,
+                None => "yyyy-MM-dd",
+            };
+            let locale = match bound[2] {
+                Some(locale) => find_locale(string_argument(name, "locale", locale)?),
+                None => find_locale(&ctx.environment.locale),
+            };
+            let formatted = match bound[1] {
+                Some(timezone) => {
+                    let timezone =
+                        parse_timezone(name, string_argument(name, "timezone", timezone)?)?;
+                    format_date(name, &date.with_timezone(&timezone), pattern, locale)?
+                }
+                None => format_date(name, &date, pattern, locale)?,
+            };
+            formatted.into()
+        }
+        "timezone" => {
+            let bound = bind_arguments(name, arguments, &["timezone"], 1)?;
+            let timezone = string_argument(name, "timezone", bound[0].unwrap())?;
+            date_value(date.with_timezone(&parse_timezone(name, timezone)?))
+        }
+        "date_add" => {
+            let bound = bind_arguments(name, arguments, &["amount", "unit"], 2)?;
+            let amount = bound[0]
+                .unwrap()
+                .as_i64()
+                .ok_or_else(|| argument_error(name, "amount to be a whole number"))?;
+            let unit = Unit::parse(name, string_argument(name, "unit", bound[1].unwrap())?)?;
+            date_value(add(name, date, amount, unit)?)
+        }
+        "date_diff" => {
+            let bound = bind_arguments(name, arguments, &["from", "unit"], 0)?;
+            let from = other_date(name, bound[0], ctx)?;
+            let unit = match bound[1] {
+                Some(unit) => Unit::parse(name, string_argument(name, "unit", unit)?)?,
+                None => Unit::Days,
+            };
+            difference(from, date, unit).into()
+        }
+        "before" | "after" => {
+            let bound = bind_arguments(name, arguments, &["other"], 0)?;
+            let other = other_date(name, bound[0], ctx)?;
+            match name {
+                "before" => date < other,
+                _ => date > other,
+            }
+            .into()
+        }
+        "epoch" => {
+            bind_arguments(name, arguments, &[], 0)?;
+            match date.timestamp_subsec_nanos() {
+                0 => date.timestamp().into(),
+                nanoseconds => (date.timestamp() as f64 + f64::from(nanoseconds) / 1e9).into(),
+            }
+        }
+        _ => {
+            bind_arguments(name, arguments, &[], 0)?;
+            relative(date, ctx.now.fixed_offset()).into()
+        }
+    };
+
+    Ok(value)
+}
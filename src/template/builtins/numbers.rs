@@ -0,0 +1,345 @@
+use crate::{
+    error::Error,
+    template::{
+        builtins::{argument_error, bind_arguments, required_target, string_argument},
+        expression::Arguments,
+        locale::{find_locale, Locale},
+        RenderContext,
+    },
+};
+
+/// How the last kept digit is rounded when a number has more decimals than shown.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Rounding {
+    HalfEven,
+    HalfUp,
+    HalfDown,
+    Up,
+    Down,
+    Ceiling,
+    Floor,
+}
+
+fn parse_rounding(name: &str, rounding: &str) -> Result<Rounding, Error> {
+    match rounding {
+        "half_even" => Ok(Rounding::HalfEven),
+        "half_up" => Ok(Rounding::HalfUp),
+        "half_down" => Ok(Rounding::HalfDown),
+        "up" => Ok(Rounding::Up),
+        "down" => Ok(Rounding::Down),
+        "ceiling" => Ok(Rounding::Ceiling),
+        "floor" => Ok(Rounding::Floor),
+        _ => Err(argument_error(
+            name,
+            "rounding to be one of half_even, half_up, half_down, up, down, ceiling or floor",
+        )),
+    }
+}
+
+/// A number as its decimal digits, so that rounding is exact, e.g. 1.005 is
+/// `integer: [1], fraction: [0, 0, 5]`.
+#[derive(Debug, Clone, PartialEq)]
+struct Decimal {
+    negative: bool,
+    integer: Vec<u8>,
+    fraction: Vec<u8>,
+}
+
+impl Decimal {
+    /// Reads the shortest form that serde_json prints, e.g. `-1234.5` or `1e21`.
+    fn from_number(number: &serde_json::Number) -> Decimal {
+        let text = number.to_string();
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text.as_str()),
+        };
+        let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().unwrap_or(0)),
+            None => (text, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+        let digits = |s: &str| s.bytes().map(|b| b - b'0').collect::<Vec<u8>>();
+        let mut decimal = Decimal {
+            negative,
+            integer: digits(integer),
+            fraction: digits(fraction),
+        };
+        decimal.shift(exponent);
+        decimal
+    }
+
+    /// Multiplies by 10 to the power of `places`, which may be negative.
+    fn shift(&mut self, places: i64) {
+        for _ in 0..places.max(0) {
+            let digit = match self.fraction.is_empty() {
+                true => 0,
+                false => self.fraction.remove(0),
+            };
+            self.integer.push(digit);
+        }
+        for _ in 0..(-places).max(0) {
+            let digit = self.integer.pop().unwrap_or(0);
+            self.fraction.insert(0, digit);
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.integer.iter().chain(&self.fraction).all(|d| *d == 0)
+    }
+
+    /// The number of digits before the decimal, ignoring leading zeros.
+    fn integer_digits(&self) -> usize {
+        self.integer.iter().skip_while(|d| **d == 0).count()
+    }
+
+    /// Rounds to at most `places` decimals.
+    fn round(&mut self, places: usize, rounding: Rounding) {
+        if self.fraction.len() <= places {
+            return;
+        }
+
+        let rest = self.fraction.split_off(places);
+        if rest.iter().all(|d| *d == 0) {
+            return;
+        }
+
+        let first = rest[0];
+        let beyond_half = rest[1..].iter().any(|d| *d != 0);
+        let last_kept = self.fraction.last().or(self.integer.last()).copied();
+        let round_away = match rounding {
+            Rounding::Up => true,
+            Rounding::Down => false,
+            Rounding::Ceiling => !self.negative,
+            Rounding::Floor => self.negative,
+            Rounding::HalfUp => first >= 5,
+            Rounding::HalfDown => first > 5 || (first == 5 && beyond_half),
+            Rounding::HalfEven => {
+                first > 5 || (first == 5 && (beyond_half || last_kept.unwrap_or(0) % 2 == 1))
+            }
+        };
+
+        if round_away {
+            self.increment();
+        }
+    }
+
+    /// Adds one to the last kept digit.
+    fn increment(&mut self) {
+        for digit in self
+            .fraction
+            .iter_mut()
+            .rev()
+            .chain(self.integer.iter_mut().rev())
+        {
+            if *digit < 9 {
+                *digit += 1;
+                return;
+            }
+            *digit = 0;
+        }
+        self.integer.insert(0, 1);
+    }
+}
+
+/// A pattern such as `#,##0.00`, `0.#%` or `€ #,##0.00`.
+#[derive(Debug)]
+struct NumberPattern {
+    prefix: String,
+    suffix: String,
+    min_integer: usize,
+    min_fraction: usize,
+    max_fraction: usize,
+    /// The first and following group sizes, if digits are grouped at all.
+    grouping: Option<(usize, usize)>,
+    percent: bool,
+}
+
+fn parse_pattern(name: &str, pattern: &str) -> Result<NumberPattern, Error> {
+    // The digits start at the first `#` or `0`, so `Rs. #,##0` has `Rs. ` before them
+    let is_numeric = |c: char| matches!(c, '#' | '0' | ',' | '.');
+    let start = match pattern.find(['#', '0']) {
+        Some(start) => start,
+        None => return Err(argument_error(name, "a pattern such as '#,##0.00'")),
+    };
+    let end = pattern[start..]
+        .find(|c| !is_numeric(c))
+        .map_or(pattern.len(), |end| start + end);
+
+    let numeric = &pattern[start..end];
+    let (integer, fraction) = numeric.split_once('.').unwrap_or((numeric, ""));
+    if fraction.contains([',', '.']) {
+        return Err(argument_error(name, "a pattern such as '#,##0.00'"));
+    }
+
+    let groups: Vec<&str> = integer.split(',').collect();
+    let grouping = match groups.as_slice() {
+        [_] => None,
+        [.., secondary, primary] if groups.len() > 2 && !secondary.is_empty() => {
+            Some((primary.len(), secondary.len()))
+        }
+        [.., primary] => Some((primary.len(), primary.len())),
+        [] => None,
+    };
+
+    let (prefix, suffix) = (&pattern[..start], &pattern[end..]);
+    Ok(NumberPattern {
+        prefix: prefix.to_owned(),
+        suffix: suffix.to_owned(),
+        min_integer: integer.matches('0').count(),
+        min_fraction: fraction.matches('0').count(),
+        max_fraction: fraction.len(),
+        grouping: grouping.filter(|(primary, _)| *primary > 0),
+        percent: prefix.contains('%') || suffix.contains('%'),
+    })
+}
+
+fn group_digits(digits: &str, grouping: (usize, usize), separator: &str) -> String {
+    let (primary, secondary) = grouping;
+    if digits.len() <= primary {
+        return digits.to_owned();
+    }
+
+    let (rest, last) = digits.split_at(digits.len() - primary);
+    let mut groups = vec![last.to_owned()];
+    let mut rest = rest;
+    while !rest.is_empty() {
+        let (head, group) = rest.split_at(rest.len().saturating_sub(secondary.max(1)));
+        groups.push(group.to_owned());
+        rest = head;
+    }
+    groups.reverse();
+    groups.join(separator)
+}
+
+fn format_decimal(
+    mut decimal: Decimal,
+    pattern: &NumberPattern,
+    locale: &Locale,
+    rounding: Rounding,
+) -> String {
+    if pattern.percent {
+        decimal.shift(2);
+    }
+    decimal.round(pattern.max_fraction, rounding);
+
+    while decimal.fraction.len() > pattern.min_fraction && decimal.fraction.last() == Some(&0) {
+        decimal.fraction.pop();
+    }
+    decimal
+        .fraction
+        .resize(decimal.fraction.len().max(pattern.min_fraction), 0);
+
+    let significant = decimal.integer_digits();
+    let integer_length = significant.max(pattern.min_integer);
+    let integer: String = std::iter::repeat_n(0, integer_length.saturating_sub(significant))
+        .chain(
+            decimal.integer[decimal.integer.len() - significant..]
+                .iter()
+                .copied(),
+        )
+        .map(|d| char::from(b'0' + d))
+        .collect();
+    let integer = match pattern.grouping {
+        Some(grouping) => group_digits(&integer, grouping, locale.group),
+        None => integer,
+    };
+    let fraction: String = decimal
+        .fraction
+        .iter()
+        .map(|d| char::from(b'0' + d))
+        .collect();
+
+    let mut number = integer;
+    if !fraction.is_empty() {
+        number.push_str(locale.decimal);
+        number.push_str(&fraction);
+    }
+    if number.is_empty() {
+        number.push('0');
+    }
+
+    // -0.001 rounded to 0.00 shows as 0.00
+    let sign = match decimal.negative && !decimal.is_zero() {
+        true => "-",
+        false => "",
+    };
+    format!("{}{}{}{}", sign, pattern.prefix, number, pattern.suffix)
+}
+
+/// Thousands, millions, billions and trillions with at most one decimal, e.g. `1.2K`.
+fn format_compact(decimal: Decimal, locale: &Locale, rounding: Rounding) -> String {
+    let pattern = parse_pattern("string", "0.#").unwrap();
+
+    let mut unit = 0;
+    let mut scaled = decimal.clone();
+    while unit < locale.compact.len() && scaled.integer_digits() > 3 {
+        scaled.shift(-3);
+        unit += 1;
+    }
+
+    // 999,950 rounds up to 1000K, which is 1M
+    let mut rounded = scaled.clone();
+    rounded.round(1, rounding);
+    if rounded.integer_digits() > 3 && unit < locale.compact.len() {
+        scaled.shift(-3);
+        unit += 1;
+    }
+
+    let number = format_decimal(scaled, &pattern, locale, rounding);
+    match unit {
+        0 => number,
+        unit => number + locale.compact[unit - 1],
+    }
+}
+
+/// `?string` on a number, e.g. `${amount?string("#,##0.00")}`, `?string("percent")`
+/// or `?string("compact")`, in the locale of the environment unless one is given.
+pub fn format_number(
+    name: &str,
+    target: Option<serde_json::Value>,
+    arguments: &Arguments,
+    ctx: &RenderContext,
+) -> Result<serde_json::Value, Error> {
+    let number = match required_target(name, target)? {
+        serde_json::Value::Number(number) => number,
+        value => {
+            return Err(Error::TypeError(format!(
+                "\n?{} can only be used on a number, not {}\n",
+                name, value
+            )))
+        }
+    };
+
+    let bound = bind_arguments(name, arguments, &["format", "locale", "rounding"], 0)?;
+    let format = match bound[0] {
+        Some(format) => string_argument(name, "format", format)?,
+        None => "number",
+    };
+    let locale = match bound[1] {
+        Some(locale) => find_locale(string_argument(name, "locale", locale)?),
+        None => find_locale(&ctx.environment.locale),
+    };
+    let rounding = match bound[2] {
+        Some(rounding) => parse_rounding(name, string_argument(name, "rounding", rounding)?)?,
+        None => Rounding::HalfEven,
+    };
+
+    let decimal = Decimal::from_number(&number);
+    let grouping = locale.grouping;
+    let pattern = match format {
+        "compact" => return Ok(format_compact(decimal, locale, rounding).into()),
+        "number" => "#,##0.###",
+        "integer" => "#,##0",
+        "percent" => "#,##0%",
+        pattern => pattern,
+    };
+
+    let mut pattern = parse_pattern(name, pattern)?;
+    // The named formats group digits the way the locale does
+    if matches!(format, "number" | "integer" | "percent") {
+        pattern.grouping = Some(grouping);
+    }
+
+    Ok(format_decimal(decimal, &pattern, locale, rounding).into())
+}
@@ -0,0 +1,246 @@
+use crate::{
+    error::Error,
+    template::{
+        builtins::{
+            argument_error, bind_arguments, bool_argument, count_argument, required_target,
+            string_argument,
+        },
+        expression::Arguments,
+    },
+};
+
+fn string_target(name: &str, target: Option<serde_json::Value>) -> Result<String, Error> {
+    match required_target(name, target)? {
+        serde_json::Value::String(text) => Ok(text),
+        value => Err(Error::TypeError(format!(
+            "\n?{} can only be used on a string, not {}\n",
+            name, value
+        ))),
+    }
+}
+
+/// A single character to pad with, e.g. `'0'` in `?pad_left(5, '0')`.
+fn fill_argument(name: &str, value: Option<&serde_json::Value>) -> Result<char, Error> {
+    let fill = match value {
+        Some(value) => string_argument(name, "fill", value)?,
+        None => return Ok(' '),
+    };
+
+    let mut chars = fill.chars();
+    match (chars.next(), chars.next()) {
+        (Some(fill), None) => Ok(fill),
+        _ => Err(argument_error(name, "fill to be a single character")),
+    }
+}
+
+/// Upper cases the first letter of each word and lower cases the rest.
+fn title(text: &str) -> String {
+    let mut title = String::new();
+    let mut word_start = true;
+    for c in text.chars() {
+        match word_start {
+            true => title.extend(c.to_uppercase()),
+            false => title.extend(c.to_lowercase()),
+        }
+        word_start = c.is_whitespace() || matches!(c, '-' | '(' | '[' | '{' | '<');
+    }
+    title
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+fn pad(text: String, width: usize, fill: char, left: bool) -> String {
+    let padding: String =
+        std::iter::repeat_n(fill, width.saturating_sub(text.chars().count())).collect();
+    match left {
+        true => padding + &text,
+        false => text + &padding,
+    }
+}
+
+/// Shortens text to at most `length` characters including the ellipsis. With `words`
+/// it only cuts between words, unless the first word alone is too long. If the
+/// ellipsis alone is too long the text is cut without one.
+fn truncate(text: String, length: usize, ellipsis: &str, words: bool) -> String {
+    if text.chars().count() <= length {
+        return text;
+    }
+    if ellipsis.chars().count() >= length {
+        return text.chars().take(length).collect();
+    }
+
+    let keep = length - ellipsis.chars().count();
+    let mut cut: String = text.chars().take(keep).collect();
+    if words {
+        let inside_word = text.chars().nth(keep).is_some_and(|c| !c.is_whitespace());
+        if let Some(i) = cut.rfind(char::is_whitespace).filter(|_| inside_word) {
+            cut.truncate(i);
+        }
+        cut.truncate(cut.trim_end().len());
+    }
+
+    cut + ellipsis
+}
+
+/// Breaks lines between words so that they are at most `width` characters, where
+/// possible. Existing line breaks are kept.
+fn word_wrap(text: &str, width: usize) -> String {
+    let wrap = |line: &str| {
+        let mut lines = vec![];
+        let mut current = String::new();
+        for word in line.split_whitespace() {
+            if !current.is_empty() && current.chars().count() + 1 + word.chars().count() > width {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        lines.push(current);
+        lines.join("\n")
+    };
+
+    text.split('\n').map(wrap).collect::<Vec<_>>().join("\n")
+}
+
+/// Indents every line but the first by `width` spaces, as the first usually follows
+/// indentation in the template already. Blank lines are left alone.
+fn indent(text: &str, width: usize, first: bool) -> String {
+    let indentation = " ".repeat(width);
+    text.split('\n')
+        .enumerate()
+        .map(
+            |(i, line)| match (i > 0 || first) && !line.trim().is_empty() {
+                true => indentation.clone() + line,
+                false => line.to_owned(),
+            },
+        )
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lower case letters and digits, with anything else between them becoming a `-`.
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// Characters from `start` up to but not including `end`, counted in Unicode scalar values.
+fn substring(text: &str, start: usize, end: Option<usize>) -> String {
+    let end = end.unwrap_or(usize::MAX);
+    text.chars()
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .collect()
+}
+
+/// Applies a builtin that transforms a string, e.g. `${name?upper}`, or returns `None`
+/// if there is no such builtin.
+pub fn apply_string_builtin(
+    name: &str,
+    target: Option<serde_json::Value>,
+    arguments: &Arguments,
+) -> Result<Option<serde_json::Value>, Error> {
+    let text = match name {
+        "upper" | "lower" | "title" | "capitalize" | "trim" | "trim_start" | "trim_end"
+        | "pad_left" | "pad_right" | "truncate" | "word_wrap" | "indent" | "slugify"
+        | "replace" | "split" | "substring" | "length" => string_target(name, target)?,
+        _ => return Ok(None),
+    };
+
+    let value = match name {
+        "upper" | "lower" | "title" | "capitalize" | "trim" | "trim_start" | "trim_end"
+        | "slugify" | "length" => {
+            bind_arguments(name, arguments, &[], 0)?;
+            match name {
+                "upper" => text.to_uppercase().into(),
+                "lower" => text.to_lowercase().into(),
+                "title" => title(&text).into(),
+                "capitalize" => capitalize(&text).into(),
+                "trim" => text.trim().into(),
+                "trim_start" => text.trim_start().into(),
+                "trim_end" => text.trim_end().into(),
+                "slugify" => slugify(&text).into(),
+                _ => text.chars().count().into(),
+            }
+        }
+        "pad_left" | "pad_right" => {
+            let bound = bind_arguments(name, arguments, &["width", "fill"], 1)?;
+            let width = count_argument(name, "width", bound[0].unwrap())?;
+            let fill = fill_argument(name, bound[1])?;
+            pad(text, width, fill, name == "pad_left").into()
+        }
+        "truncate" => {
+            let bound = bind_arguments(name, arguments, &["length", "ellipsis", "words"], 1)?;
+            let length = count_argument(name, "length", bound[0].unwrap())?;
+            let ellipsis = match bound[1] {
+                Some(ellipsis) => string_argument(name, "ellipsis", ellipsis)?,
+                None => "...",
+            };
+            let words = match bound[2] {
+                Some(words) => bool_argument(name, "words", words)?,
+                None => false,
+            };
+            truncate(text, length, ellipsis, words).into()
+        }
+        "word_wrap" => {
+            let bound = bind_arguments(name, arguments, &["width"], 1)?;
+            let width = count_argument(name, "width", bound[0].unwrap())?;
+            word_wrap(&text, width).into()
+        }
+        "indent" => {
+            let bound = bind_arguments(name, arguments, &["width", "first"], 1)?;
+            let width = count_argument(name, "width", bound[0].unwrap())?;
+            let first = match bound[1] {
+                Some(first) => bool_argument(name, "first", first)?,
+                None => false,
+            };
+            indent(&text, width, first).into()
+        }
+        "replace" => {
+            let bound = bind_arguments(name, arguments, &["from", "to"], 2)?;
+            let from = string_argument(name, "from", bound[0].unwrap())?;
+            let to = string_argument(name, "to", bound[1].unwrap())?;
+            if from.is_empty() {
+                return Err(argument_error(name, "from to not be empty"));
+            }
+            text.replace(from, to).into()
+        }
+        "split" => {
+            let bound = bind_arguments(name, arguments, &["separator"], 1)?;
+            let separator = string_argument(name, "separator", bound[0].unwrap())?;
+            match separator.is_empty() {
+                true => text.chars().map(|c| c.to_string()).collect(),
+                false => text.split(separator).collect(),
+            }
+        }
+        _ => {
+            let bound = bind_arguments(name, arguments, &["start", "end"], 1)?;
+            let start = count_argument(name, "start", bound[0].unwrap())?;
+            let end = match bound[1] {
+                Some(end) => Some(count_argument(name, "end", end)?),
+                None => None,
+            };
+            substring(&text, start, end).into()
+        }
+    };
+
+    Ok(Some(value))
+}
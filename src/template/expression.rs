@@ -0,0 +1,756 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{
+    error::Error,
+    template::{
+        builtins::{apply_builtin, argument_error, contains},
+        conditional::{evaluate_test, values_equal},
+        iteration::compare_values,
+        lookup_parameter,
+        macros::call_macro,
+        translation::{translate_call, translate_plural_call},
+        value_to_string, RenderContext,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum ExpressionToken {
+    Number(serde_json::Number),
+    String(String),
+    Path(String),
+    /// A builtin applied to what comes before it, e.g. `?exists`.
+    Builtin(String),
+    Symbol(&'static str),
+    OpenParen,
+    CloseParen,
+    Comma,
+    Equals,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    In,
+    NotIn,
+    And,
+    Or,
+}
+
+/// An argument of a call, either positional or given by name e.g. `type="text"`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Argument {
+    pub name: Option<String>,
+    pub value: Expression,
+}
+
+/// Evaluated positional and keyword arguments.
+pub type Arguments = (Vec<serde_json::Value>, Vec<(String, serde_json::Value)>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    Literal(serde_json::Value),
+    Path(String),
+    Negate(Box<Expression>),
+    Not(Box<Expression>),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+    Call(String, Vec<Argument>),
+    Builtin(Box<Expression>, String, Vec<Argument>),
+    /// `value!default` or `value ?? default`, the default being used when the value is
+    /// missing or null.
+    Default(Box<Expression>, Box<Expression>),
+    /// `condition ? then : otherwise`, only evaluating the chosen branch.
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+}
+
+fn expression_error(source: &str, message: &str) -> Error {
+    Error::InvalidSyntax(format!("\nInvalid expression: {}\n{}\n", source, message))
+}
+
+const SYMBOLS: [&str; 20] = [
+    "==", "!=", "<=", ">=", "&&", "||", "??", "+", "-", "*", "/", "%", "~", "<", ">", "!", "?",
+    ":", "(", ")",
+];
+
+fn tokenise(source: &str) -> Result<Vec<ExpressionToken>, Error> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_';
+
+    while i < chars.len() {
+        let char = chars[i];
+        match char {
+            _ if char.is_whitespace() => i += 1,
+            '0'..='9' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // An exponent such as `1e3` or `2.5E-4`
+                let exponent_digits = match chars.get(i + 1) {
+                    Some('+' | '-') => i + 2,
+                    _ => i + 1,
+                };
+                if matches!(chars.get(i), Some('e' | 'E'))
+                    && chars.get(exponent_digits).is_some_and(char::is_ascii_digit)
+                {
+                    i = exponent_digits;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+                let number: String = chars[start..i].iter().collect();
+                match serde_json::from_str::<serde_json::Number>(&number) {
+                    Ok(number) => tokens.push(ExpressionToken::Number(number)),
+                    Err(_) => {
+                        return Err(expression_error(
+                            source,
+                            &format!("'{}' is not a number", number),
+                        ))
+                    }
+                }
+            }
+            '\'' | '"' => {
+                let mut string = String::new();
+                i += 1;
+                while i < chars.len() && chars[i] != char {
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        i += 1;
+                        string.push(match chars[i] {
+                            'n' => '\n',
+                            't' => '\t',
+                            c => c,
+                        });
+                    } else {
+                        string.push(chars[i]);
+                    }
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(expression_error(source, "String has no closing quote"));
+                }
+                i += 1;
+                tokens.push(ExpressionToken::String(string));
+            }
+            _ if char.is_alphabetic() || char == '_' => {
+                let start = i;
+                while i < chars.len() && (is_name_char(chars[i]) || chars[i] == '.') {
+                    i += 1;
+                }
+                tokens.push(ExpressionToken::Path(chars[start..i].iter().collect()));
+            }
+            '?' if chars.get(i + 1).is_some_and(|c| c.is_alphabetic()) => {
+                let start = i + 1;
+                i += 1;
+                while i < chars.len() && is_name_char(chars[i]) {
+                    i += 1;
+                }
+                tokens.push(ExpressionToken::Builtin(chars[start..i].iter().collect()));
+            }
+            ',' => {
+                tokens.push(ExpressionToken::Comma);
+                i += 1;
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                match SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+                    Some(&"(") => tokens.push(ExpressionToken::OpenParen),
+                    Some(&")") => tokens.push(ExpressionToken::CloseParen),
+                    Some(symbol) => tokens.push(ExpressionToken::Symbol(symbol)),
+                    None if char == '=' => tokens.push(ExpressionToken::Equals),
+                    None => {
+                        return Err(expression_error(source, &format!("Unexpected '{}'", char)))
+                    }
+                }
+                i += match tokens.last() {
+                    Some(ExpressionToken::Symbol(symbol)) => symbol.len(),
+                    _ => 1,
+                };
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<ExpressionToken>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&ExpressionToken> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<ExpressionToken> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Consumes the next token if it is one of the given symbols or keywords,
+    /// returning the operator it stands for.
+    fn take_operator(&mut self, operators: &[(&str, Operator)]) -> Option<Operator> {
+        let text = match self.peek() {
+            Some(ExpressionToken::Symbol(symbol)) => *symbol,
+            Some(ExpressionToken::Path(keyword)) => keyword.as_str(),
+            _ => return None,
+        };
+
+        let operator = operators.iter().find(|(s, _)| *s == text)?.1;
+        self.position += 1;
+        Some(operator)
+    }
+
+    fn parse_binary(
+        &mut self,
+        operators: &[(&str, Operator)],
+        operand: fn(&mut Self) -> Result<Expression, Error>,
+    ) -> Result<Expression, Error> {
+        let mut left = operand(self)?;
+        while let Some(operator) = self.take_operator(operators) {
+            let right = operand(self)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    /// `a ? b : c`, where the `?` is followed by a space or anything but a name so that
+    /// it is not read as a builtin. Nested conditionals group to the right.
+    fn parse_ternary(&mut self) -> Result<Expression, Error> {
+        let condition = self.parse_or()?;
+        if self.peek() != Some(&ExpressionToken::Symbol("?")) {
+            return Ok(condition);
+        }
+        self.position += 1;
+
+        let then = self.parse_ternary()?;
+        if self.next() != Some(ExpressionToken::Symbol(":")) {
+            return Err(expression_error(self.source, "Expected : in conditional"));
+        }
+        let otherwise = self.parse_ternary()?;
+
+        Ok(Expression::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        ))
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, Error> {
+        self.parse_binary(
+            &[("||", Operator::Or), ("or", Operator::Or)],
+            Self::parse_and,
+        )
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, Error> {
+        self.parse_binary(
+            &[("&&", Operator::And), ("and", Operator::And)],
+            Self::parse_not,
+        )
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, Error> {
+        if self.peek() == Some(&ExpressionToken::Path("not".to_owned())) {
+            self.position += 1;
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, Error> {
+        let left = self.parse_coalesce()?;
+        let operator = self.take_operator(&[
+            ("==", Operator::Equal),
+            ("!=", Operator::NotEqual),
+            ("<=", Operator::LessEqual),
+            (">=", Operator::GreaterEqual),
+            ("<", Operator::Less),
+            (">", Operator::Greater),
+            ("in", Operator::In),
+        ]);
+        let operator = match operator {
+            None if self.peek() == Some(&ExpressionToken::Path("not".to_owned()))
+                && self.tokens.get(self.position + 1)
+                    == Some(&ExpressionToken::Path("in".to_owned())) =>
+            {
+                self.position += 2;
+                Some(Operator::NotIn)
+            }
+            operator => operator,
+        };
+
+        match operator {
+            Some(operator) => {
+                let right = self.parse_coalesce()?;
+                Ok(Expression::Binary(
+                    operator,
+                    Box::new(left),
+                    Box::new(right),
+                ))
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn parse_coalesce(&mut self) -> Result<Expression, Error> {
+        let mut left = self.parse_concat()?;
+        while self.peek() == Some(&ExpressionToken::Symbol("??")) {
+            self.position += 1;
+            let right = self.parse_concat()?;
+            left = Expression::Default(Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_concat(&mut self) -> Result<Expression, Error> {
+        self.parse_binary(&[("~", Operator::Concat)], Self::parse_additive)
+    }
+
+    fn parse_additive(&mut self) -> Result<Expression, Error> {
+        self.parse_binary(
+            &[("+", Operator::Add), ("-", Operator::Subtract)],
+            Self::parse_multiplicative,
+        )
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expression, Error> {
+        self.parse_binary(
+            &[
+                ("*", Operator::Multiply),
+                ("/", Operator::Divide),
+                ("%", Operator::Modulo),
+            ],
+            Self::parse_unary,
+        )
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, Error> {
+        if self.peek() == Some(&ExpressionToken::Symbol("-")) {
+            self.position += 1;
+            return Ok(Expression::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_postfix()
+    }
+
+    /// Builtins such as `?exists` and `?contains('x')`, and defaults such as
+    /// `!"friend"`, after a value.
+    fn parse_postfix(&mut self) -> Result<Expression, Error> {
+        let mut expression = self.parse_primary()?;
+        loop {
+            let name = match self.peek() {
+                Some(ExpressionToken::Builtin(name)) => name.clone(),
+                Some(ExpressionToken::Symbol("!")) => {
+                    self.position += 1;
+                    // Without a default, a missing value becomes empty text
+                    let default = match self.peek() {
+                        Some(
+                            ExpressionToken::Number(_)
+                            | ExpressionToken::String(_)
+                            | ExpressionToken::Path(_)
+                            | ExpressionToken::OpenParen,
+                        ) => self.parse_postfix()?,
+                        _ => Expression::Literal(serde_json::Value::String(String::new())),
+                    };
+                    expression = Expression::Default(Box::new(expression), Box::new(default));
+                    continue;
+                }
+                _ => return Ok(expression),
+            };
+            self.position += 1;
+
+            let arguments = match self.peek() {
+                Some(ExpressionToken::OpenParen) => {
+                    self.position += 1;
+                    self.parse_arguments()?
+                }
+                _ => vec![],
+            };
+            expression = Expression::Builtin(Box::new(expression), name, arguments);
+        }
+    }
+
+    /// Arguments after the opening `(` of a call, up to and including the closing `)`.
+    fn parse_arguments(&mut self) -> Result<Vec<Argument>, Error> {
+        let mut arguments = vec![];
+        if self.peek() == Some(&ExpressionToken::CloseParen) {
+            self.position += 1;
+            return Ok(arguments);
+        }
+
+        loop {
+            let name = match (self.peek(), self.tokens.get(self.position + 1)) {
+                (Some(ExpressionToken::Path(name)), Some(ExpressionToken::Equals)) => {
+                    let name = name.clone();
+                    self.position += 2;
+                    Some(name)
+                }
+                _ => None,
+            };
+            if name.is_none() && arguments.iter().any(|a: &Argument| a.name.is_some()) {
+                return Err(expression_error(
+                    self.source,
+                    "Positional arguments cannot follow keyword arguments",
+                ));
+            }
+            let value = self.parse_ternary()?;
+            arguments.push(Argument { name, value });
+
+            match self.next() {
+                Some(ExpressionToken::Comma) => {}
+                Some(ExpressionToken::CloseParen) => return Ok(arguments),
+                _ => return Err(expression_error(self.source, "Missing closing ) of call")),
+            }
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, Error> {
+        match self.next() {
+            Some(ExpressionToken::Number(number)) => {
+                Ok(Expression::Literal(serde_json::Value::Number(number)))
+            }
+            Some(ExpressionToken::String(string)) => {
+                Ok(Expression::Literal(serde_json::Value::String(string)))
+            }
+            Some(ExpressionToken::Path(path)) => Ok(match path.as_str() {
+                "true" => Expression::Literal(serde_json::Value::Bool(true)),
+                "false" => Expression::Literal(serde_json::Value::Bool(false)),
+                "null" => Expression::Literal(serde_json::Value::Null),
+                _ if self.peek() == Some(&ExpressionToken::OpenParen) => {
+                    self.position += 1;
+                    Expression::Call(path, self.parse_arguments()?)
+                }
+                _ => Expression::Path(path),
+            }),
+            Some(ExpressionToken::OpenParen) => {
+                let expression = self.parse_ternary()?;
+                match self.next() {
+                    Some(ExpressionToken::CloseParen) => Ok(expression),
+                    _ => Err(expression_error(self.source, "Missing closing )")),
+                }
+            }
+            Some(token) => Err(expression_error(
+                self.source,
+                &format!("Unexpected {:?}", token),
+            )),
+            None => Err(expression_error(
+                self.source,
+                "Unexpected end of expression",
+            )),
+        }
+    }
+}
+
+pub fn parse_expression(source: &str) -> Result<Expression, Error> {
+    let mut parser = Parser {
+        source,
+        tokens: tokenise(source)?,
+        position: 0,
+    };
+
+    let expression = parser.parse_ternary()?;
+    match parser.peek() {
+        None => Ok(expression),
+        Some(token) => Err(expression_error(source, &format!("Unexpected {:?}", token))),
+    }
+}
+
+/// Parses comma separated expressions, e.g. the values of `{#case "refunded", "void"#}`.
+pub fn parse_expression_list(source: &str) -> Result<Vec<Expression>, Error> {
+    let mut parser = Parser {
+        source,
+        tokens: tokenise(source)?,
+        position: 0,
+    };
+
+    let mut expressions = vec![parser.parse_ternary()?];
+    loop {
+        match parser.next() {
+            None => return Ok(expressions),
+            Some(ExpressionToken::Comma) => expressions.push(parser.parse_ternary()?),
+            Some(token) => {
+                return Err(expression_error(source, &format!("Unexpected {:?}", token)))
+            }
+        }
+    }
+}
+
+/// Whether a value counts as true in a condition. `null`, `false`, `0`, `""`, `[]` and
+/// `{}` are false, everything else is true.
+pub fn is_truthy(value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::Null => false,
+        serde_json::Value::Bool(b) => *b,
+        serde_json::Value::Number(n) => n.as_f64() != Some(0.0),
+        serde_json::Value::String(s) => !s.is_empty(),
+        serde_json::Value::Array(a) => !a.is_empty(),
+        serde_json::Value::Object(o) => !o.is_empty(),
+    }
+}
+
+fn operator_symbol(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Add => "+",
+        Operator::Subtract => "-",
+        Operator::Multiply => "*",
+        Operator::Divide => "/",
+        Operator::Modulo => "%",
+        Operator::Concat => "~",
+        Operator::Equal => "==",
+        Operator::NotEqual => "!=",
+        Operator::Less => "<",
+        Operator::LessEqual => "<=",
+        Operator::Greater => ">",
+        Operator::GreaterEqual => ">=",
+        Operator::In => "in",
+        Operator::NotIn => "not in",
+        Operator::And => "&&",
+        Operator::Or => "||",
+    }
+}
+
+fn type_error(operator: Operator, left: &serde_json::Value, right: &serde_json::Value) -> Error {
+    Error::TypeError(format!(
+        "\nCannot apply {} to {} and {}\n",
+        operator_symbol(operator),
+        left,
+        right
+    ))
+}
+
+/// Arithmetic with JSON number semantics. Integers stay integers where the result
+/// is exact, anything else becomes a float.
+fn arithmetic(
+    operator: Operator,
+    left: &serde_json::Value,
+    right: &serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    let (l, r) = match (left.as_number(), right.as_number()) {
+        (Some(l), Some(r)) => (l, r),
+        _ => return Err(type_error(operator, left, right)),
+    };
+
+    if r.as_f64() == Some(0.0) && matches!(operator, Operator::Divide | Operator::Modulo) {
+        return Err(Error::DivisionByZero(format!(
+            "\n{} {} {} divides by zero\n",
+            left,
+            operator_symbol(operator),
+            right
+        )));
+    }
+
+    let overflow = || {
+        Error::Overflow(format!(
+            "\n{} {} {} is too large to represent\n",
+            left,
+            operator_symbol(operator),
+            right
+        ))
+    };
+
+    // Integers up to u64::MAX are exact in i128 and narrowed back afterwards
+    let as_i128 = |n: &serde_json::Number| match (n.as_i64(), n.as_u64()) {
+        (Some(n), _) => Some(i128::from(n)),
+        (_, Some(n)) => Some(i128::from(n)),
+        _ => None,
+    };
+    if let (Some(l), Some(r)) = (as_i128(l), as_i128(r)) {
+        let result = match operator {
+            Operator::Add => l.checked_add(r),
+            Operator::Subtract => l.checked_sub(r),
+            Operator::Multiply => l.checked_mul(r),
+            Operator::Modulo => l.checked_rem(r),
+            _ => l.checked_div(r),
+        };
+        match result {
+            // Inexact integer division falls through to a float
+            Some(result) if operator != Operator::Divide || l % r == 0 => {
+                return match (i64::try_from(result), u64::try_from(result)) {
+                    (Ok(result), _) => Ok(result.into()),
+                    (_, Ok(result)) => Ok(result.into()),
+                    _ => Err(overflow()),
+                };
+            }
+            Some(_) => {}
+            None => return Err(overflow()),
+        }
+    }
+
+    let (l, r) = (l.as_f64().unwrap(), r.as_f64().unwrap());
+    let result = match operator {
+        Operator::Add => l + r,
+        Operator::Subtract => l - r,
+        Operator::Multiply => l * r,
+        Operator::Divide => l / r,
+        _ => l % r,
+    };
+
+    match serde_json::Number::from_f64(result) {
+        Some(result) => Ok(serde_json::Value::Number(result)),
+        None => Err(overflow()),
+    }
+}
+
+fn compare(
+    operator: Operator,
+    left: &serde_json::Value,
+    right: &serde_json::Value,
+) -> Result<serde_json::Value, Error> {
+    let ordering = match (left, right) {
+        (serde_json::Value::Number(_), serde_json::Value::Number(_))
+        | (serde_json::Value::String(_), serde_json::Value::String(_)) => {
+            compare_values(left, right)
+        }
+        _ => return Err(type_error(operator, left, right)),
+    };
+
+    Ok(serde_json::Value::Bool(match operator {
+        Operator::Less => ordering == Ordering::Less,
+        Operator::LessEqual => ordering != Ordering::Greater,
+        Operator::Greater => ordering == Ordering::Greater,
+        _ => ordering != Ordering::Less,
+    }))
+}
+
+/// Evaluates the arguments of a call into positional and keyword values.
+pub fn evaluate_arguments(
+    arguments: &[Argument],
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &mut RenderContext,
+) -> Result<Arguments, Error> {
+    let mut positional = vec![];
+    let mut keyword = vec![];
+    for argument in arguments {
+        let value = evaluate(&argument.value, params, parent_params, ctx)?;
+        match &argument.name {
+            Some(name) => keyword.push((name.clone(), value)),
+            None => positional.push(value),
+        }
+    }
+
+    Ok((positional, keyword))
+}
+
+/// Evaluates an expression, or `None` if a variable in it is missing. Builtins such
+/// as `?exists` and defaults need to know whether the value is there at all.
+pub fn probe(
+    expression: &Expression,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &mut RenderContext,
+) -> Result<Option<serde_json::Value>, Error> {
+    match evaluate(expression, params, parent_params, ctx) {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::UndefinedVariable(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Evaluates an expression, a missing variable being an `Error::UndefinedVariable`.
+pub fn evaluate(
+    expression: &Expression,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &mut RenderContext,
+) -> Result<serde_json::Value, Error> {
+    let evaluate = |expression: &Expression, ctx: &mut RenderContext| {
+        self::evaluate(expression, params, parent_params, ctx)
+    };
+
+    match expression {
+        Expression::Literal(value) => Ok(value.clone()),
+        Expression::Path(path) => match lookup_parameter(path, params, parent_params, ctx) {
+            Some(value) => Ok(value.clone()),
+            None => Err(Error::UndefinedVariable(format!(
+                "\n{} is missing from parameter data mapping.\n",
+                path
+            ))),
+        },
+        Expression::Negate(expression) => {
+            let value = evaluate(expression, ctx)?;
+            arithmetic(Operator::Subtract, &serde_json::Value::from(0), &value)
+        }
+        Expression::Not(expression) => Ok(serde_json::Value::Bool(!is_truthy(&evaluate(
+            expression, ctx,
+        )?))),
+        Expression::Binary(Operator::And, left, right) => Ok(serde_json::Value::Bool(
+            is_truthy(&evaluate(left, ctx)?) && is_truthy(&evaluate(right, ctx)?),
+        )),
+        Expression::Binary(Operator::Or, left, right) => Ok(serde_json::Value::Bool(
+            is_truthy(&evaluate(left, ctx)?) || is_truthy(&evaluate(right, ctx)?),
+        )),
+        Expression::Binary(operator, left, right) => {
+            let left = evaluate(left, ctx)?;
+            let right = evaluate(right, ctx)?;
+            match operator {
+                Operator::Equal => Ok(serde_json::Value::Bool(values_equal(&left, &right))),
+                Operator::NotEqual => Ok(serde_json::Value::Bool(!values_equal(&left, &right))),
+                Operator::In => Ok(serde_json::Value::Bool(contains("in", &right, &left)?)),
+                Operator::NotIn => Ok(serde_json::Value::Bool(!contains("not in", &right, &left)?)),
+                Operator::Concat => Ok(serde_json::Value::String(
+                    value_to_string(&left) + &value_to_string(&right),
+                )),
+                Operator::Less
+                | Operator::LessEqual
+                | Operator::Greater
+                | Operator::GreaterEqual => compare(*operator, &left, &right),
+                _ => arithmetic(*operator, &left, &right),
+            }
+        }
+        Expression::Call(name, arguments) if name == "_" || name == "_n" => {
+            let arguments = evaluate_arguments(arguments, params, parent_params, ctx)?;
+            match name.as_str() {
+                "_" => translate_call(arguments, params, parent_params, ctx),
+                _ => translate_plural_call(arguments, params, parent_params, ctx),
+            }
+        }
+        Expression::Call(name, arguments) => {
+            let (positional, keyword) = evaluate_arguments(arguments, params, parent_params, ctx)?;
+            call_macro(name, positional, keyword, None, ctx).map(serde_json::Value::String)
+        }
+        Expression::Default(value, default) => match probe(value, params, parent_params, ctx)? {
+            Some(value) if !value.is_null() => Ok(value),
+            _ => evaluate(default, ctx),
+        },
+        Expression::Conditional(condition, then, otherwise) => {
+            match evaluate_test(condition, params, parent_params, ctx)? {
+                true => evaluate(then, ctx),
+                false => evaluate(otherwise, ctx),
+            }
+        }
+        // Only the chosen argument of `?then` is evaluated
+        Expression::Builtin(target, name, arguments) if name == "then" => {
+            let (then, otherwise) = match arguments.as_slice() {
+                [then, otherwise] if then.name.is_none() && otherwise.name.is_none() => {
+                    (&then.value, &otherwise.value)
+                }
+                _ => return Err(argument_error(name, "two arguments")),
+            };
+            match evaluate_test(target, params, parent_params, ctx)? {
+                true => evaluate(then, ctx),
+                false => evaluate(otherwise, ctx),
+            }
+        }
+        Expression::Builtin(target, name, arguments) => {
+            let target = probe(target, params, parent_params, ctx)?;
+            let arguments = evaluate_arguments(arguments, params, parent_params, ctx)?;
+            apply_builtin(name, target, arguments, ctx)
+        }
+    }
+}
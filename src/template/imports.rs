@@ -0,0 +1,159 @@
+use crate::{
+    ast::{ASTNode, ASTNodeIdentifier, Ast},
+    error::Error,
+    template::{is_valid_name, macros::define_macro, RenderContext},
+};
+
+fn import_error(node: &ASTNode, message: &str) -> Error {
+    let construct_token = node.tokens.first().unwrap();
+    Error::InvalidSyntax(format!(
+        "\n{}\n{} at line {}:{}\n",
+        message, node.value, construct_token.line_start, construct_token.pos_start
+    ))
+}
+
+/// Splits `"forms.html" as forms` into the quoted template name and the rest.
+fn take_template_name(contents: &str) -> Option<(&str, &str)> {
+    let quote = contents
+        .chars()
+        .next()
+        .filter(|c| *c == '"' || *c == '\'')?;
+    let (name, rest) = contents[1..].split_once(quote)?;
+    Some((name, rest.trim()))
+}
+
+/// Defines the macros and imports at the top level of a template into the current
+/// module. Everything else at the top level is ignored.
+pub fn define_module(ast: &Ast, ctx: &mut RenderContext) -> Result<(), Error> {
+    for node in &ast.nodes {
+        match node.identifier {
+            ASTNodeIdentifier::Macro => define_macro(node, ctx)?,
+            ASTNodeIdentifier::Import | ASTNodeIdentifier::FromImport => {
+                evaluate_import(node, ctx)?
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Loads a template through the environment as a module of macros, once per render.
+fn load_module(node: &ASTNode, name: &str, ctx: &mut RenderContext) -> Result<usize, Error> {
+    if ctx.import_chain.iter().any(|n| n == name) {
+        let mut chain = ctx.import_chain.clone();
+        chain.push(name.to_owned());
+        return Err(import_error(
+            node,
+            &format!("Import cycle detected: {}", chain.join(" -> ")),
+        ));
+    }
+
+    if let Some(module) = ctx.imported.get(name) {
+        return Ok(*module);
+    }
+
+    let source = match ctx.environment.load_template(name) {
+        Some(source) => source,
+        None => {
+            return Err(import_error(
+                node,
+                &format!("Template '{}' could not be found", name),
+            ))
+        }
+    };
+    let template = ctx.environment.compile(source)?;
+    for (key, regex) in &template.patterns {
+        ctx.regexes.insert(key.clone(), regex.clone());
+    }
+
+    let module = ctx.modules.len();
+    ctx.modules.push(Default::default());
+    ctx.import_chain.push(name.to_owned());
+    let importing_module = std::mem::replace(&mut ctx.module, module);
+
+    let defined = define_module(&template.ast, ctx);
+
+    ctx.module = importing_module;
+    ctx.import_chain.pop();
+    defined?;
+
+    ctx.imported.insert(name.to_owned(), module);
+    Ok(module)
+}
+
+/// `{#import "forms.html" as forms#}` makes the macros of a template callable as
+/// `forms.field(...)`, and `{#from "ui.html" import button, badge as b#}` makes the
+/// named ones callable directly.
+pub fn evaluate_import(node: &ASTNode, ctx: &mut RenderContext) -> Result<(), Error> {
+    let is_from = node.identifier == ASTNodeIdentifier::FromImport;
+    let opener = if is_from { "{#from" } else { "{#import" };
+    let contents = node.value[opener.len()..node.value.len() - 2].trim();
+
+    let (name, rest) = match take_template_name(contents) {
+        Some(parts) => parts,
+        None => return Err(import_error(node, "Expected a quoted template name")),
+    };
+
+    if !is_from {
+        let namespace = match rest.strip_prefix("as ").map(str::trim) {
+            Some(namespace) if is_valid_name(namespace) => namespace,
+            _ => {
+                return Err(import_error(
+                    node,
+                    "Expected as <namespace> after the template name",
+                ))
+            }
+        };
+
+        let module = load_module(node, name, ctx)?;
+        let macros: Vec<_> = ctx.modules[module]
+            .iter()
+            .filter(|(macro_name, _)| !macro_name.contains('.'))
+            .map(|(macro_name, definition)| {
+                (format!("{}.{}", namespace, macro_name), definition.clone())
+            })
+            .collect();
+        ctx.modules[ctx.module].extend(macros);
+
+        return Ok(());
+    }
+
+    let names = match rest.strip_prefix("import ") {
+        Some(names) => names,
+        None => {
+            return Err(import_error(
+                node,
+                "Expected import <names> after the template name",
+            ))
+        }
+    };
+
+    let module = load_module(node, name, ctx)?;
+    for imported in names.split(',') {
+        let (macro_name, alias) = match imported.split_once(" as ") {
+            Some((macro_name, alias)) => (macro_name.trim(), alias.trim()),
+            None => (imported.trim(), imported.trim()),
+        };
+
+        if !is_valid_name(alias) {
+            return Err(import_error(
+                node,
+                &format!("'{}' is not a valid name", alias),
+            ));
+        }
+
+        let definition = match ctx.modules[module].get(macro_name) {
+            Some(definition) => definition.clone(),
+            None => {
+                return Err(import_error(
+                    node,
+                    &format!("'{}' does not define the macro '{}'", name, macro_name),
+                ))
+            }
+        };
+        ctx.modules[ctx.module].insert(alias.to_owned(), definition);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,464 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{
+    ast::ASTNode,
+    environment::UndefinedBehavior,
+    error::{Error, TemplateResult},
+    template::{
+        conditional::evaluate_condition_expression, generate_template, lookup_parameter,
+        undefined_variable, validate_loop_data, LoopControl, RenderContext,
+    },
+};
+
+#[derive(Debug, Default)]
+struct LoopHeader {
+    iterator: String,
+    source: String,
+    filter: Option<String>,
+    sorted_by: Option<(String, bool)>,
+    unique_by: Option<String>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    grouped_by: Option<String>,
+    chunk: Option<(usize, Option<serde_json::Value>)>,
+}
+
+fn header_error(node: &ASTNode, message: &str) -> Error {
+    let construct_token = node.tokens.first().unwrap();
+    Error::InvalidSyntax(format!(
+        "\n{} in loop: {} at line {}:{}\n",
+        message, node.value, construct_token.line_start, construct_token.pos_start
+    ))
+}
+
+/// Modifiers are only treated as such when followed by what they expect, so a
+/// filter such as `if fruit.sorted` is left alone.
+fn is_modifier(contents: &[&str], i: usize) -> bool {
+    match contents[i] {
+        "sorted" | "unique" | "grouped" => contents.get(i + 1) == Some(&"by"),
+        "limit" | "offset" => contents
+            .get(i + 1)
+            .is_some_and(|n| n.parse::<usize>().is_ok()),
+        _ => false,
+    }
+}
+
+fn parse_loop_header(node: &ASTNode) -> Result<LoopHeader, Error> {
+    let header = node.value.replace("#}", "");
+    let contents: Vec<&str> = header.split_whitespace().collect();
+
+    if contents.len() < 4 {
+        return Err(header_error(node, "Incomplete header"));
+    }
+
+    // Builtin arguments on the source may contain spaces e.g. `?chunk(3, null)`
+    let mut i = 4;
+    if contents[3].contains('(') && !contents[3].contains(')') {
+        while i < contents.len() && !contents[i - 1].contains(')') {
+            i += 1;
+        }
+    }
+
+    let mut loop_header = LoopHeader {
+        iterator: contents[1].to_string(),
+        source: contents[3..i].join(" "),
+        ..Default::default()
+    };
+
+    if let Some((source, builtin)) = loop_header.source.clone().split_once('?') {
+        loop_header.source = source.to_string();
+        loop_header.chunk = Some(parse_chunk(node, builtin)?);
+    }
+
+    while i < contents.len() {
+        let path = contents.get(i + 2).map(|p| p.to_string());
+
+        match contents[i] {
+            "if" if loop_header.filter.is_none() => {
+                let mut end = i + 1;
+                while end < contents.len() && !is_modifier(&contents, end) {
+                    end += 1;
+                }
+                if end == i + 1 {
+                    return Err(header_error(node, "Missing condition after 'if'"));
+                }
+                loop_header.filter = Some(contents[i + 1..end].join(" "));
+                i = end;
+            }
+            "sorted" if is_modifier(&contents, i) && path.is_some() => {
+                let (descending, consumed) = match contents.get(i + 3) {
+                    Some(&"desc") => (true, 4),
+                    Some(&"asc") => (false, 4),
+                    _ => (false, 3),
+                };
+                loop_header.sorted_by = Some((path.unwrap(), descending));
+                i += consumed;
+            }
+            "unique" if is_modifier(&contents, i) && path.is_some() => {
+                loop_header.unique_by = path;
+                i += 3;
+            }
+            "grouped" if is_modifier(&contents, i) && path.is_some() => {
+                loop_header.grouped_by = path;
+                i += 3;
+            }
+            "limit" if is_modifier(&contents, i) => {
+                loop_header.limit = contents[i + 1].parse().ok();
+                i += 2;
+            }
+            "offset" if is_modifier(&contents, i) => {
+                loop_header.offset = contents[i + 1].parse().ok();
+                i += 2;
+            }
+            _ => {
+                return Err(header_error(
+                    node,
+                    &format!("Unexpected '{}'", contents[i..].join(" ")),
+                ))
+            }
+        }
+    }
+
+    Ok(loop_header)
+}
+
+/// The filter condition of a loop, e.g. `fruit.ripe` in `{#for fruit of fruits if fruit.ripe#}`.
+pub fn loop_filter(node: &ASTNode) -> Option<String> {
+    parse_loop_header(node).ok()?.filter
+}
+
+/// Parses a literal argument such as `'-'`, `0`, `null` or `{"name": ""}`.
+fn parse_literal(literal: &str) -> Option<serde_json::Value> {
+    let literal = literal.trim();
+    let quoted =
+        |quote| literal.len() > 1 && literal.starts_with(quote) && literal.ends_with(quote);
+
+    match quoted('\'') || quoted('"') {
+        true => Some(serde_json::Value::String(
+            literal[1..literal.len() - 1].to_string(),
+        )),
+        false => serde_json::from_str(literal).ok(),
+    }
+}
+
+fn parse_chunk(node: &ASTNode, builtin: &str) -> Result<(usize, Option<serde_json::Value>), Error> {
+    let arguments = match builtin
+        .strip_prefix("chunk(")
+        .and_then(|b| b.strip_suffix(')'))
+    {
+        Some(arguments) => arguments,
+        None => return Err(header_error(node, &format!("Not valid api: {}", builtin))),
+    };
+
+    let (size, fill) = match arguments.split_once(',') {
+        Some((size, fill)) => match parse_literal(fill) {
+            Some(fill) => (size, Some(fill)),
+            None => return Err(header_error(node, "Invalid fill value for chunk")),
+        },
+        None => (arguments, None),
+    };
+
+    match size.trim().parse::<usize>() {
+        Ok(size) if size > 0 => Ok((size, fill)),
+        _ => Err(header_error(node, "Chunk size must be a positive number")),
+    }
+}
+
+/// Splits items into arrays of `size`, padding the last one with `fill` if given.
+fn chunk_items(
+    items: &[serde_json::Value],
+    size: usize,
+    fill: &Option<serde_json::Value>,
+) -> Vec<serde_json::Value> {
+    items
+        .chunks(size)
+        .map(|chunk| {
+            let mut chunk = chunk.to_vec();
+            if let Some(fill) = fill {
+                chunk.resize(size, fill.clone());
+            }
+            serde_json::Value::Array(chunk)
+        })
+        .collect()
+}
+
+/// Resolves a modifier path such as `price` or `fruit.price` against a single item.
+fn item_attribute<'a>(
+    item: &'a serde_json::Value,
+    path: &str,
+    iterator: &str,
+) -> Option<&'a serde_json::Value> {
+    let path = match path.strip_prefix(iterator) {
+        Some(rest) if rest.starts_with('.') => &rest[1..],
+        _ => path,
+    };
+
+    attribute_path(item, path)
+}
+
+/// Follows a path such as `address.city` or `tags.0` into a value.
+pub fn attribute_path<'a>(
+    value: &'a serde_json::Value,
+    path: &str,
+) -> Option<&'a serde_json::Value> {
+    path.split('.')
+        .try_fold(value, |value, segment| match segment.parse::<usize>() {
+            Ok(index) if value.is_array() => value.get(index),
+            _ => value.get(segment),
+        })
+}
+
+fn type_rank(value: &serde_json::Value) -> u8 {
+    match value {
+        serde_json::Value::Null => 0,
+        serde_json::Value::Bool(_) => 1,
+        serde_json::Value::Number(_) => 2,
+        serde_json::Value::String(_) => 3,
+        serde_json::Value::Array(_) => 4,
+        serde_json::Value::Object(_) => 5,
+    }
+}
+
+/// Total ordering across JSON values. Values of different types are ordered
+/// null < bool < number < string < array < object, and a missing value sorts as null.
+pub fn compare_values(a: &serde_json::Value, b: &serde_json::Value) -> Ordering {
+    use serde_json::Value;
+
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => match (a.as_i64(), b.as_i64()) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => match (a.as_u64(), b.as_u64()) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                _ => a
+                    .as_f64()
+                    .unwrap_or(f64::NAN)
+                    .total_cmp(&b.as_f64().unwrap_or(f64::NAN)),
+            },
+        },
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => {
+            for (a, b) in a.iter().zip(b.iter()) {
+                let ordering = compare_values(a, b);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            a.len().cmp(&b.len())
+        }
+        (Value::Object(_), Value::Object(_)) => a.to_string().cmp(&b.to_string()),
+        _ => type_rank(a).cmp(&type_rank(b)),
+    }
+}
+
+/// Sorts items by a key, where items with equal keys keep the order they came in with.
+/// Shared by `sorted by` and `?sort`.
+pub fn sort_by_key(
+    items: Vec<serde_json::Value>,
+    key: impl Fn(&serde_json::Value) -> serde_json::Value,
+    descending: bool,
+) -> Vec<serde_json::Value> {
+    let mut keyed: Vec<(serde_json::Value, serde_json::Value)> =
+        items.into_iter().map(|item| (key(&item), item)).collect();
+    keyed.sort_by(|(a, _), (b, _)| match descending {
+        true => compare_values(a, b).reverse(),
+        false => compare_values(a, b),
+    });
+    keyed.into_iter().map(|(_, item)| item).collect()
+}
+
+/// Keeps the first item with each key. Shared by `unique by` and `?unique`.
+pub fn unique_by_key(
+    items: &mut Vec<serde_json::Value>,
+    key: impl Fn(&serde_json::Value) -> serde_json::Value,
+) {
+    let mut seen: Vec<serde_json::Value> = vec![];
+    items.retain(|item| {
+        let key = key(item);
+        match seen
+            .iter()
+            .any(|s| compare_values(s, &key) == Ordering::Equal)
+        {
+            true => false,
+            false => {
+                seen.push(key);
+                true
+            }
+        }
+    });
+}
+
+fn apply_modifiers(
+    header: &LoopHeader,
+    mut items: Vec<serde_json::Value>,
+) -> Vec<serde_json::Value> {
+    let attribute = |item: &serde_json::Value, path: &str| {
+        item_attribute(item, path, &header.iterator)
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    if let Some(path) = &header.unique_by {
+        unique_by_key(&mut items, |item| attribute(item, path));
+    }
+
+    if let Some((path, descending)) = &header.sorted_by {
+        items = sort_by_key(items, |item| attribute(item, path), *descending);
+    }
+
+    let offset = header.offset.unwrap_or(0);
+    let limit = header.limit.unwrap_or(usize::MAX);
+    let mut items: Vec<serde_json::Value> = items.into_iter().skip(offset).take(limit).collect();
+
+    // Groups keep the order in which their key was first seen
+    if let Some(path) = &header.grouped_by {
+        let mut groups: Vec<(serde_json::Value, Vec<serde_json::Value>)> = vec![];
+        for item in items {
+            let key = attribute(&item, path);
+            match groups
+                .iter_mut()
+                .find(|(k, _)| compare_values(k, &key) == Ordering::Equal)
+            {
+                Some((_, group)) => group.push(item),
+                None => groups.push((key, vec![item])),
+            }
+        }
+
+        items = groups
+            .into_iter()
+            .map(|(key, items)| serde_json::json!({ "key": key, "items": items }))
+            .collect();
+    }
+
+    items
+}
+
+/// Properties of an object item are also reachable without the iterator name.
+/// Any other item, such as a chunk, is only reachable through the iterator.
+fn item_params(item: &serde_json::Value) -> HashMap<String, serde_json::Value> {
+    let mut new_params = HashMap::new();
+
+    if let Some(item_to_mapping) = item.as_object() {
+        for (k, v) in item_to_mapping.iter() {
+            new_params.insert(k.clone(), v.clone());
+        }
+    }
+
+    new_params
+}
+
+fn loop_metadata(index: usize, length: usize) -> serde_json::Value {
+    serde_json::json!({
+        "index": index + 1,
+        "index0": index,
+        "first": index == 0,
+        "last": index + 1 == length,
+        "length": length,
+    })
+}
+
+pub fn evaluate_loop(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let header = parse_loop_header(node)?;
+    let list_iterator_name = match header.source.contains('.') {
+        true => header.source.split('.').next_back().unwrap(),
+        false => &header.source,
+    };
+
+    // Found like any other variable, so `user.orders` can be a path into a root object
+    let data_retrieval = lookup_parameter(&header.source, params, parent_params, ctx);
+
+    // The error already points at the loop source, so a strict render needs no span
+    let list_data = match validate_loop_data(node, data_retrieval) {
+        Ok(data) => data,
+        Err(e) => match ctx.environment.undefined_behavior {
+            UndefinedBehavior::Strict => return Err(Error::UndefinedVariable(e)),
+            UndefinedBehavior::Lenient => return Ok("".to_owned()),
+            UndefinedBehavior::Debug => return Ok(format!("[[undefined: {}]]", header.source)),
+        },
+    };
+
+    // Depending on the root mapping, this needs to be handled
+    let mut loop_over = match (
+        list_data[list_iterator_name].as_array(),
+        list_data.as_array(),
+    ) {
+        (Some(data), _) | (None, Some(data)) => data.to_owned(),
+        (None, None) => {
+            return Err(Error::TypeError(format!(
+                "\n'{}' is not a list and cannot be looped over\n",
+                header.source
+            )))
+        }
+    };
+
+    if let Some((size, fill)) = &header.chunk {
+        loop_over = chunk_items(&loop_over, *size, fill);
+    }
+
+    let mut loop_stack = open_loop_stack.to_owned();
+    loop_stack.push(header.iterator.clone());
+    loop_stack.push("loop".to_owned());
+
+    // Filter up front so that the loop metadata only counts the items that pass
+    let mut items = vec![];
+    for item in &loop_over {
+        let new_params = item_params(item);
+
+        if let Some(filter) = &header.filter {
+            let mut clone_of_parent_params = parent_params.clone();
+            clone_of_parent_params.insert(header.iterator.clone(), item.clone());
+
+            match evaluate_condition_expression(filter, &new_params, &clone_of_parent_params, ctx) {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(Error::UndefinedVariable(e)) => {
+                    return undefined_variable(node, filter, e, ctx);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        items.push(item.clone());
+    }
+
+    let items = apply_modifiers(&header, items);
+
+    let mut html = String::new();
+    let length = items.len();
+
+    for (i, item) in items.into_iter().enumerate() {
+        let new_params = item_params(&item);
+        let mut clone_of_parent_params = parent_params.clone();
+        clone_of_parent_params.insert(header.iterator.clone(), item);
+        clone_of_parent_params.insert("loop".to_owned(), loop_metadata(i, length));
+
+        // Each iteration gets its own scope for assignments
+        ctx.scopes.push(HashMap::new());
+        let data = generate_template(
+            node.children.clone().unwrap(),
+            new_params,
+            clone_of_parent_params,
+            loop_stack.clone(),
+            ctx,
+        );
+        ctx.scopes.pop();
+        html.push_str(data?.as_str());
+
+        match ctx.loop_control {
+            LoopControl::Break => {
+                ctx.loop_control = LoopControl::None;
+                break;
+            }
+            LoopControl::Continue => ctx.loop_control = LoopControl::None,
+            LoopControl::None => {}
+        }
+    }
+
+    Ok(html)
+}
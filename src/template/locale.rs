@@ -0,0 +1,512 @@
+/// Formatting data for a locale, bundled for the locales below.
+#[derive(Debug)]
+pub struct Locale {
+    pub tag: &'static str,
+    pub decimal: &'static str,
+    pub group: &'static str,
+    /// Sizes of the first group of digits before the decimal and of the groups after
+    /// it, e.g. 3 and 2 for `12,34,567`.
+    pub grouping: (usize, usize),
+    /// Suffixes of compact numbers for thousands, millions, billions and trillions.
+    pub compact: [&'static str; 4],
+    pub dates: &'static DateNames,
+}
+
+/// Names used when formatting dates, with weeks starting on Monday.
+#[derive(Debug)]
+pub struct DateNames {
+    pub months: [&'static str; 12],
+    pub months_short: [&'static str; 12],
+    pub weekdays: [&'static str; 7],
+    pub weekdays_short: [&'static str; 7],
+    /// Before and after noon, e.g. `AM` and `PM`.
+    pub day_periods: [&'static str; 2],
+}
+
+const NBSP: &str = "\u{a0}";
+const NARROW_NBSP: &str = "\u{202f}";
+
+static EN_DATES: DateNames = DateNames {
+    months: [
+        "January",
+        "February",
+        "March",
+        "April",
+        "May",
+        "June",
+        "July",
+        "August",
+        "September",
+        "October",
+        "November",
+        "December",
+    ],
+    months_short: [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ],
+    weekdays: [
+        "Monday",
+        "Tuesday",
+        "Wednesday",
+        "Thursday",
+        "Friday",
+        "Saturday",
+        "Sunday",
+    ],
+    weekdays_short: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"],
+    day_periods: ["AM", "PM"],
+};
+static DE_DATES: DateNames = DateNames {
+    months: [
+        "Januar",
+        "Februar",
+        "März",
+        "April",
+        "Mai",
+        "Juni",
+        "Juli",
+        "August",
+        "September",
+        "Oktober",
+        "November",
+        "Dezember",
+    ],
+    months_short: [
+        "Jan.", "Feb.", "März", "Apr.", "Mai", "Juni", "Juli", "Aug.", "Sept.", "Okt.", "Nov.",
+        "Dez.",
+    ],
+    weekdays: [
+        "Montag",
+        "Dienstag",
+        "Mittwoch",
+        "Donnerstag",
+        "Freitag",
+        "Samstag",
+        "Sonntag",
+    ],
+    weekdays_short: ["Mo.", "Di.", "Mi.", "Do.", "Fr.", "Sa.", "So."],
+    day_periods: ["AM", "PM"],
+};
+static FR_DATES: DateNames = DateNames {
+    months: [
+        "janvier",
+        "février",
+        "mars",
+        "avril",
+        "mai",
+        "juin",
+        "juillet",
+        "août",
+        "septembre",
+        "octobre",
+        "novembre",
+        "décembre",
+    ],
+    months_short: [
+        "janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.",
+        "déc.",
+    ],
+    weekdays: [
+        "lundi", "mardi", "mercredi", "jeudi", "vendredi", "samedi", "dimanche",
+    ],
+    weekdays_short: ["lun.", "mar.", "mer.", "jeu.", "ven.", "sam.", "dim."],
+    day_periods: ["AM", "PM"],
+};
+static ES_DATES: DateNames = DateNames {
+    months: [
+        "enero",
+        "febrero",
+        "marzo",
+        "abril",
+        "mayo",
+        "junio",
+        "julio",
+        "agosto",
+        "septiembre",
+        "octubre",
+        "noviembre",
+        "diciembre",
+    ],
+    months_short: [
+        "ene", "feb", "mar", "abr", "may", "jun", "jul", "ago", "sept", "oct", "nov", "dic",
+    ],
+    weekdays: [
+        "lunes",
+        "martes",
+        "miércoles",
+        "jueves",
+        "viernes",
+        "sábado",
+        "domingo",
+    ],
+    weekdays_short: ["lun", "mar", "mié", "jue", "vie", "sáb", "dom"],
+    day_periods: ["a.\u{a0}m.", "p.\u{a0}m."],
+};
+static IT_DATES: DateNames = DateNames {
+    months: [
+        "gennaio",
+        "febbraio",
+        "marzo",
+        "aprile",
+        "maggio",
+        "giugno",
+        "luglio",
+        "agosto",
+        "settembre",
+        "ottobre",
+        "novembre",
+        "dicembre",
+    ],
+    months_short: [
+        "gen", "feb", "mar", "apr", "mag", "giu", "lug", "ago", "set", "ott", "nov", "dic",
+    ],
+    weekdays: [
+        "lunedì",
+        "martedì",
+        "mercoledì",
+        "giovedì",
+        "venerdì",
+        "sabato",
+        "domenica",
+    ],
+    weekdays_short: ["lun", "mar", "mer", "gio", "ven", "sab", "dom"],
+    day_periods: ["AM", "PM"],
+};
+static NL_DATES: DateNames = DateNames {
+    months: [
+        "januari",
+        "februari",
+        "maart",
+        "april",
+        "mei",
+        "juni",
+        "juli",
+        "augustus",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    months_short: [
+        "jan", "feb", "mrt", "apr", "mei", "jun", "jul", "aug", "sep", "okt", "nov", "dec",
+    ],
+    weekdays: [
+        "maandag",
+        "dinsdag",
+        "woensdag",
+        "donderdag",
+        "vrijdag",
+        "zaterdag",
+        "zondag",
+    ],
+    weekdays_short: ["ma", "di", "wo", "do", "vr", "za", "zo"],
+    day_periods: ["a.m.", "p.m."],
+};
+static PT_DATES: DateNames = DateNames {
+    months: [
+        "janeiro",
+        "fevereiro",
+        "março",
+        "abril",
+        "maio",
+        "junho",
+        "julho",
+        "agosto",
+        "setembro",
+        "outubro",
+        "novembro",
+        "dezembro",
+    ],
+    months_short: [
+        "jan.", "fev.", "mar.", "abr.", "mai.", "jun.", "jul.", "ago.", "set.", "out.", "nov.",
+        "dez.",
+    ],
+    weekdays: [
+        "segunda-feira",
+        "terça-feira",
+        "quarta-feira",
+        "quinta-feira",
+        "sexta-feira",
+        "sábado",
+        "domingo",
+    ],
+    weekdays_short: ["seg.", "ter.", "qua.", "qui.", "sex.", "sáb.", "dom."],
+    day_periods: ["AM", "PM"],
+};
+static PL_DATES: DateNames = DateNames {
+    months: [
+        "stycznia",
+        "lutego",
+        "marca",
+        "kwietnia",
+        "maja",
+        "czerwca",
+        "lipca",
+        "sierpnia",
+        "września",
+        "października",
+        "listopada",
+        "grudnia",
+    ],
+    months_short: [
+        "sty", "lut", "mar", "kwi", "maj", "cze", "lip", "sie", "wrz", "paź", "lis", "gru",
+    ],
+    weekdays: [
+        "poniedziałek",
+        "wtorek",
+        "środa",
+        "czwartek",
+        "piątek",
+        "sobota",
+        "niedziela",
+    ],
+    weekdays_short: ["pon.", "wt.", "śr.", "czw.", "pt.", "sob.", "niedz."],
+    day_periods: ["AM", "PM"],
+};
+static RU_DATES: DateNames = DateNames {
+    months: [
+        "января",
+        "февраля",
+        "марта",
+        "апреля",
+        "мая",
+        "июня",
+        "июля",
+        "августа",
+        "сентября",
+        "октября",
+        "ноября",
+        "декабря",
+    ],
+    months_short: [
+        "янв.",
+        "февр.",
+        "мар.",
+        "апр.",
+        "мая",
+        "июн.",
+        "июл.",
+        "авг.",
+        "сент.",
+        "окт.",
+        "нояб.",
+        "дек.",
+    ],
+    weekdays: [
+        "понедельник",
+        "вторник",
+        "среда",
+        "четверг",
+        "пятница",
+        "суббота",
+        "воскресенье",
+    ],
+    weekdays_short: ["пн", "вт", "ср", "чт", "пт", "сб", "вс"],
+    day_periods: ["AM", "PM"],
+};
+static SV_DATES: DateNames = DateNames {
+    months: [
+        "januari",
+        "februari",
+        "mars",
+        "april",
+        "maj",
+        "juni",
+        "juli",
+        "augusti",
+        "september",
+        "oktober",
+        "november",
+        "december",
+    ],
+    months_short: [
+        "jan.", "feb.", "mars", "apr.", "maj", "juni", "juli", "aug.", "sep.", "okt.", "nov.",
+        "dec.",
+    ],
+    weekdays: [
+        "måndag", "tisdag", "onsdag", "torsdag", "fredag", "lördag", "söndag",
+    ],
+    weekdays_short: ["mån", "tis", "ons", "tors", "fre", "lör", "sön"],
+    day_periods: ["fm", "em"],
+};
+static JA_DATES: DateNames = DateNames {
+    months: [
+        "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+    ],
+    months_short: [
+        "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+    ],
+    weekdays: [
+        "月曜日",
+        "火曜日",
+        "水曜日",
+        "木曜日",
+        "金曜日",
+        "土曜日",
+        "日曜日",
+    ],
+    weekdays_short: ["月", "火", "水", "木", "金", "土", "日"],
+    day_periods: ["午前", "午後"],
+};
+static ZH_DATES: DateNames = DateNames {
+    months: [
+        "一月",
+        "二月",
+        "三月",
+        "四月",
+        "五月",
+        "六月",
+        "七月",
+        "八月",
+        "九月",
+        "十月",
+        "十一月",
+        "十二月",
+    ],
+    months_short: [
+        "1月", "2月", "3月", "4月", "5月", "6月", "7月", "8月", "9月", "10月", "11月", "12月",
+    ],
+    weekdays: [
+        "星期一",
+        "星期二",
+        "星期三",
+        "星期四",
+        "星期五",
+        "星期六",
+        "星期日",
+    ],
+    weekdays_short: ["周一", "周二", "周三", "周四", "周五", "周六", "周日"],
+    day_periods: ["上午", "下午"],
+};
+
+static LOCALES: &[Locale] = &[
+    Locale {
+        tag: "en",
+        decimal: ".",
+        group: ",",
+        grouping: (3, 3),
+        compact: ["K", "M", "B", "T"],
+        dates: &EN_DATES,
+    },
+    Locale {
+        tag: "en-IN",
+        decimal: ".",
+        group: ",",
+        grouping: (3, 2),
+        compact: ["K", "L", "Cr", "T"],
+        dates: &EN_DATES,
+    },
+    Locale {
+        tag: "de",
+        decimal: ",",
+        group: ".",
+        grouping: (3, 3),
+        compact: ["\u{a0}Tsd.", "\u{a0}Mio.", "\u{a0}Mrd.", "\u{a0}Bio."],
+        dates: &DE_DATES,
+    },
+    Locale {
+        tag: "de-CH",
+        decimal: ".",
+        group: "’",
+        grouping: (3, 3),
+        compact: ["\u{a0}Tsd.", "\u{a0}Mio.", "\u{a0}Mrd.", "\u{a0}Bio."],
+        dates: &DE_DATES,
+    },
+    Locale {
+        tag: "fr",
+        decimal: ",",
+        group: NARROW_NBSP,
+        grouping: (3, 3),
+        compact: ["\u{a0}k", "\u{a0}M", "\u{a0}Md", "\u{a0}Bn"],
+        dates: &FR_DATES,
+    },
+    Locale {
+        tag: "es",
+        decimal: ",",
+        group: ".",
+        grouping: (3, 3),
+        compact: ["\u{a0}mil", "\u{a0}M", "\u{a0}mil\u{a0}M", "\u{a0}B"],
+        dates: &ES_DATES,
+    },
+    Locale {
+        tag: "it",
+        decimal: ",",
+        group: ".",
+        grouping: (3, 3),
+        compact: ["K", "\u{a0}Mln", "\u{a0}Mrd", "\u{a0}Bln"],
+        dates: &IT_DATES,
+    },
+    Locale {
+        tag: "nl",
+        decimal: ",",
+        group: ".",
+        grouping: (3, 3),
+        compact: ["K", "\u{a0}mln.", "\u{a0}mld.", "\u{a0}bln."],
+        dates: &NL_DATES,
+    },
+    Locale {
+        tag: "pt",
+        decimal: ",",
+        group: ".",
+        grouping: (3, 3),
+        compact: ["\u{a0}mil", "\u{a0}mi", "\u{a0}bi", "\u{a0}tri"],
+        dates: &PT_DATES,
+    },
+    Locale {
+        tag: "pl",
+        decimal: ",",
+        group: NBSP,
+        grouping: (3, 3),
+        compact: ["\u{a0}tys.", "\u{a0}mln", "\u{a0}mld", "\u{a0}bln"],
+        dates: &PL_DATES,
+    },
+    Locale {
+        tag: "ru",
+        decimal: ",",
+        group: NBSP,
+        grouping: (3, 3),
+        compact: ["\u{a0}тыс.", "\u{a0}млн", "\u{a0}млрд", "\u{a0}трлн"],
+        dates: &RU_DATES,
+    },
+    Locale {
+        tag: "sv",
+        decimal: ",",
+        group: NBSP,
+        grouping: (3, 3),
+        compact: ["\u{a0}tn", "\u{a0}mn", "\u{a0}md", "\u{a0}bn"],
+        dates: &SV_DATES,
+    },
+    Locale {
+        tag: "ja",
+        decimal: ".",
+        group: ",",
+        grouping: (3, 3),
+        compact: ["K", "M", "B", "T"],
+        dates: &JA_DATES,
+    },
+    Locale {
+        tag: "zh",
+        decimal: ".",
+        group: ",",
+        grouping: (3, 3),
+        compact: ["K", "M", "B", "T"],
+        dates: &ZH_DATES,
+    },
+];
+
+/// Finds a bundled locale by tag such as `de-DE` or `pt_BR`, falling back to its
+/// language and then to English.
+pub fn find_locale(tag: &str) -> &'static Locale {
+    let tag = tag.replace('_', "-");
+    let language = tag.split('-').next().unwrap_or_default();
+
+    LOCALES
+        .iter()
+        .find(|l| l.tag.eq_ignore_ascii_case(&tag))
+        .or_else(|| {
+            LOCALES
+                .iter()
+                .find(|l| l.tag.eq_ignore_ascii_case(language))
+        })
+        .unwrap_or(&LOCALES[0])
+}
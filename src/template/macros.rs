@@ -0,0 +1,281 @@
+use std::{collections::HashMap, rc::Rc};
+
+use crate::{
+    ast::{ASTNode, Ast},
+    error::{Error, TemplateResult},
+    template::{
+        expression::{evaluate, evaluate_arguments, parse_expression, Expression},
+        generate_template, LoopControl, RenderContext,
+    },
+};
+
+/// A `{#macro name(a, b="default")#}...{#endmacro#}` definition.
+#[derive(Debug)]
+pub struct Macro {
+    name: String,
+    /// The template the macro is defined in, whose macros it can call.
+    module: usize,
+    parameters: Vec<(String, Option<Expression>)>,
+    body: Ast,
+}
+
+/// The body of a `{#call#}` block, rendered where its macro uses `${caller()}`.
+#[derive(Debug)]
+pub struct Caller {
+    body: Ast,
+    params: HashMap<String, serde_json::Value>,
+    parent_params: HashMap<String, serde_json::Value>,
+    open_loop_stack: Vec<String>,
+    module: usize,
+    /// Assignments local to where the call block is, set aside while the macro renders.
+    scopes: Vec<HashMap<String, serde_json::Value>>,
+    /// A break or continue from the body, held back until the macro has rendered.
+    loop_control: LoopControl,
+}
+
+fn macro_error(node: &ASTNode, message: &str) -> Error {
+    let construct_token = node.tokens.first().unwrap();
+    Error::InvalidSyntax(format!(
+        "\n{}\n{} at line {}:{}\n",
+        message, node.value, construct_token.line_start, construct_token.pos_start
+    ))
+}
+
+pub fn define_macro(node: &ASTNode, ctx: &mut RenderContext) -> Result<(), Error> {
+    let contents = node.value["{#macro".len()..node.value.len() - 2].trim();
+    let (name, arguments) = match parse_expression(contents)? {
+        Expression::Call(name, arguments) if !name.contains('.') => (name, arguments),
+        _ => {
+            return Err(macro_error(
+                node,
+                "Expected a macro name and parameters e.g. field(name, label)",
+            ))
+        }
+    };
+
+    let mut parameters: Vec<(String, Option<Expression>)> = vec![];
+    for argument in arguments {
+        let parameter = match (argument.name, argument.value) {
+            (None, Expression::Path(parameter)) if !parameter.contains('.') => (parameter, None),
+            (Some(parameter), default) => (parameter, Some(default)),
+            (None, _) => return Err(macro_error(node, "Macro parameters must be names")),
+        };
+
+        if parameter.1.is_none() && parameters.iter().any(|(_, d)| d.is_some()) {
+            return Err(macro_error(
+                node,
+                &format!(
+                    "'{}' without a default follows one with a default",
+                    parameter.0
+                ),
+            ));
+        }
+        parameters.push(parameter);
+    }
+
+    ctx.modules[ctx.module].insert(
+        name.clone(),
+        Rc::new(Macro {
+            name,
+            module: ctx.module,
+            parameters,
+            body: node.children.clone().unwrap(),
+        }),
+    );
+
+    Ok(())
+}
+
+/// Matches positional and keyword arguments to the parameters of a macro, falling
+/// back to defaults.
+fn bind_arguments(
+    definition: &Macro,
+    positional: Vec<serde_json::Value>,
+    keyword: Vec<(String, serde_json::Value)>,
+    ctx: &mut RenderContext,
+) -> Result<HashMap<String, serde_json::Value>, Error> {
+    if positional.len() > definition.parameters.len() {
+        return Err(Error::InvalidSyntax(format!(
+            "\nMacro '{}' takes {} arguments but {} were given\n",
+            definition.name,
+            definition.parameters.len(),
+            positional.len()
+        )));
+    }
+
+    let mut arguments: HashMap<String, serde_json::Value> = definition
+        .parameters
+        .iter()
+        .map(|(name, _)| name.clone())
+        .zip(positional)
+        .collect();
+
+    for (name, value) in keyword {
+        if !definition.parameters.iter().any(|(p, _)| *p == name) {
+            return Err(Error::InvalidSyntax(format!(
+                "\nMacro '{}' has no parameter '{}'\n",
+                definition.name, name
+            )));
+        }
+        if arguments.insert(name.clone(), value).is_some() {
+            return Err(Error::InvalidSyntax(format!(
+                "\nMacro '{}' was given '{}' more than once\n",
+                definition.name, name
+            )));
+        }
+    }
+
+    for (name, default) in &definition.parameters {
+        if arguments.contains_key(name) {
+            continue;
+        }
+        match default {
+            Some(default) => {
+                let globals = ctx.globals.clone();
+                let value = evaluate(default, &globals, &globals, ctx)?;
+                arguments.insert(name.clone(), value);
+            }
+            None => {
+                return Err(Error::InvalidSyntax(format!(
+                    "\nMacro '{}' is missing the argument '{}'\n",
+                    definition.name, name
+                )))
+            }
+        }
+    }
+
+    Ok(arguments)
+}
+
+/// Renders the body of the innermost `{#call#}` block where its caller is.
+fn render_caller(ctx: &mut RenderContext) -> TemplateResult {
+    let mut caller = match ctx.callers.pop() {
+        Some(caller) => caller,
+        None => {
+            return Err(Error::InvalidSyntax(
+                "\ncaller() can only be used in a macro invoked by a {#call#} block\n".to_owned(),
+            ))
+        }
+    };
+
+    let macro_scopes = ctx.scopes.split_off(1);
+    ctx.scopes.append(&mut caller.scopes);
+    let macro_module = std::mem::replace(&mut ctx.module, caller.module);
+    let rendered = generate_template(
+        caller.body.clone(),
+        caller.params.clone(),
+        caller.parent_params.clone(),
+        caller.open_loop_stack.clone(),
+        ctx,
+    );
+    ctx.module = macro_module;
+    caller.scopes = ctx.scopes.split_off(1);
+    ctx.scopes.extend(macro_scopes);
+    if ctx.loop_control != LoopControl::None {
+        caller.loop_control = std::mem::take(&mut ctx.loop_control);
+    }
+    ctx.callers.push(caller);
+
+    rendered
+}
+
+/// Renders a macro with the given arguments. Macros only see their arguments, the
+/// global scope and the render parameters, not the variables where they are called.
+pub fn call_macro(
+    name: &str,
+    positional: Vec<serde_json::Value>,
+    keyword: Vec<(String, serde_json::Value)>,
+    caller: Option<Caller>,
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let definition = match ctx.modules[ctx.module].get(name) {
+        Some(definition) => definition.clone(),
+        None if name == "caller" => return render_caller(ctx),
+        None => {
+            return Err(Error::InvalidSyntax(format!(
+                "\nMacro '{}' is not defined\n",
+                name
+            )))
+        }
+    };
+
+    if ctx.depth >= ctx.environment.max_recursion_depth {
+        return Err(Error::InvalidSyntax(format!(
+            "\nMacro '{}' exceeded the maximum recursion depth of {}\n",
+            name, ctx.environment.max_recursion_depth
+        )));
+    }
+
+    // Defaults see what the macro body sees, not the variables where it is called
+    let mut local_scopes = ctx.scopes.split_off(1);
+    let arguments = match bind_arguments(&definition, positional, keyword, ctx) {
+        Ok(arguments) => arguments,
+        Err(e) => {
+            ctx.scopes.extend(local_scopes);
+            return Err(e);
+        }
+    };
+    let has_caller = caller.is_some();
+    if let Some(mut caller) = caller {
+        caller.scopes = local_scopes;
+        local_scopes = vec![];
+        ctx.callers.push(caller);
+    }
+    ctx.scopes.push(arguments);
+    ctx.depth += 1;
+    let calling_module = std::mem::replace(&mut ctx.module, definition.module);
+
+    let rendered = generate_template(
+        definition.body.clone(),
+        ctx.globals.clone(),
+        ctx.globals.clone(),
+        vec![],
+        ctx,
+    );
+
+    ctx.module = calling_module;
+    ctx.depth -= 1;
+    ctx.scopes.truncate(1);
+    if has_caller {
+        let caller = ctx.callers.pop().unwrap();
+        local_scopes = caller.scopes;
+        ctx.loop_control = caller.loop_control;
+    }
+    ctx.scopes.extend(local_scopes);
+
+    rendered
+}
+
+/// `{#call name(arguments)#}...{#endcall#}` calls a macro, which can render the
+/// contents of the block with `${caller()}`.
+pub fn evaluate_call_block(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let contents = node.value["{#call".len()..node.value.len() - 2].trim();
+    let (name, arguments) = match parse_expression(contents)? {
+        Expression::Call(name, arguments) => (name, arguments),
+        _ => {
+            return Err(macro_error(
+                node,
+                "Expected a macro call e.g. field(\"email\")",
+            ))
+        }
+    };
+
+    let (positional, keyword) = evaluate_arguments(&arguments, params, parent_params, ctx)?;
+    let caller = Caller {
+        body: node.children.clone().unwrap(),
+        params: params.clone(),
+        parent_params: parent_params.clone(),
+        open_loop_stack: open_loop_stack.to_owned(),
+        module: ctx.module,
+        scopes: vec![],
+        loop_control: LoopControl::None,
+    };
+
+    call_macro(&name, positional, keyword, Some(caller), ctx)
+}
@@ -0,0 +1,151 @@
+use std::rc::Rc;
+
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    ast::{ASTNode, ASTNodeIdentifier, Ast},
+    error::Error,
+    template::{
+        expression::{parse_expression, parse_expression_list, Argument, Expression},
+        is_plain_path,
+        iteration::loop_filter,
+        variable_contents, Patterns,
+    },
+};
+
+/// Compiles a pattern for `?matches`, reusing it if it was compiled before.
+pub fn regex(
+    pattern: &str,
+    ignore_case: bool,
+    patterns: &mut Patterns,
+) -> Result<Rc<Regex>, Error> {
+    let key = (pattern.to_owned(), ignore_case);
+    if let Some(regex) = patterns.get(&key) {
+        return Ok(regex.clone());
+    }
+
+    let regex = match RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+    {
+        Ok(regex) => Rc::new(regex),
+        Err(e) => {
+            return Err(Error::InvalidSyntax(format!(
+                "\nInvalid pattern '{}' in ?matches:\n{}\n",
+                pattern, e
+            )))
+        }
+    };
+    patterns.insert(key, regex.clone());
+    Ok(regex)
+}
+
+/// The expressions in a tag, where they can be found without rendering anything.
+pub fn tag_expressions(node: &ASTNode) -> Result<Vec<Expression>, Error> {
+    let contents = |opener: &str| node.value[opener.len()..node.value.len() - 2].trim();
+
+    let source = match node.identifier {
+        ASTNodeIdentifier::Variable => {
+            let variable = variable_contents(node);
+            match is_plain_path(variable) {
+                true => return Ok(vec![]),
+                false => variable.to_owned(),
+            }
+        }
+        ASTNodeIdentifier::If => node.value[4..node.value.len() - 2].to_owned(),
+        ASTNodeIdentifier::Switch => contents("{#switch").to_owned(),
+        ASTNodeIdentifier::Plural => contents("{#plural").to_owned(),
+        ASTNodeIdentifier::Case => return parse_expression_list(contents("{#case")),
+        ASTNodeIdentifier::Set => match contents("{#set").split_once('=') {
+            Some((_, expression)) => expression.to_owned(),
+            None => return Ok(vec![]),
+        },
+        ASTNodeIdentifier::Loop => match loop_filter(node) {
+            Some(filter) => filter,
+            None => return Ok(vec![]),
+        },
+        // The arguments of a call and the defaults of a macro, which parse as calls
+        ASTNodeIdentifier::Call => contents("{#call").to_owned(),
+        ASTNodeIdentifier::Macro => contents("{#macro").to_owned(),
+        ASTNodeIdentifier::Section => contents("{#section").to_owned(),
+        _ => return Ok(vec![]),
+    };
+
+    Ok(vec![parse_expression(source.trim())?])
+}
+
+/// Compiles the literal patterns of `?matches` in an expression.
+fn compile_expression(expression: &Expression, patterns: &mut Patterns) -> Result<(), Error> {
+    let compile_arguments = |arguments: &[Argument], patterns: &mut Patterns| {
+        arguments
+            .iter()
+            .try_for_each(|a| compile_expression(&a.value, patterns))
+    };
+
+    match expression {
+        Expression::Literal(_) | Expression::Path(_) => Ok(()),
+        Expression::Negate(expression) | Expression::Not(expression) => {
+            compile_expression(expression, patterns)
+        }
+        Expression::Binary(_, left, right) | Expression::Default(left, right) => {
+            compile_expression(left, patterns)?;
+            compile_expression(right, patterns)
+        }
+        Expression::Conditional(condition, then, otherwise) => {
+            compile_expression(condition, patterns)?;
+            compile_expression(then, patterns)?;
+            compile_expression(otherwise, patterns)
+        }
+        Expression::Call(_, arguments) => compile_arguments(arguments, patterns),
+        Expression::Builtin(target, name, arguments) => {
+            compile_expression(target, patterns)?;
+            compile_arguments(arguments, patterns)?;
+
+            let ignore_case = arguments.iter().find_map(|a| match (&a.name, &a.value) {
+                (Some(name), Expression::Literal(value)) if name == "ignore_case" => {
+                    value.as_bool()
+                }
+                _ => None,
+            });
+            match (
+                name.as_str(),
+                arguments.first().map(|a| (&a.name, &a.value)),
+            ) {
+                ("matches", Some((None, Expression::Literal(serde_json::Value::String(p))))) => {
+                    regex(p, ignore_case.unwrap_or_default(), patterns).map(|_| ())
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+}
+
+/// Compiles the patterns of every `?matches` with a literal pattern when the template
+/// is compiled, so that an invalid one is reported with its line even if it would never
+/// be used, and none of them are compiled again when it renders.
+pub fn compile_patterns(ast: &Ast, patterns: &mut Patterns) -> Result<(), Error> {
+    for node in &ast.nodes {
+        if node.identifier == ASTNodeIdentifier::Raw {
+            continue;
+        }
+
+        // Anything else wrong with the tag is reported when it renders
+        if let Ok(expressions) = tag_expressions(node) {
+            for expression in expressions {
+                if let Err(Error::InvalidSyntax(e)) = compile_expression(&expression, patterns) {
+                    let construct_token = node.tokens.first().unwrap();
+                    return Err(Error::InvalidSyntax(format!(
+                        "{}at line: {}:{}\n",
+                        e, construct_token.line_start, construct_token.pos_start
+                    )));
+                }
+            }
+        }
+
+        if let Some(children) = &node.children {
+            compile_patterns(children, patterns)?;
+        }
+    }
+
+    Ok(())
+}
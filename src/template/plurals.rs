@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{ASTNode, ASTNodeIdentifier, Ast},
+    error::{Error, TemplateResult},
+    template::{
+        builtins::{argument_error, bind_arguments, required_target},
+        expression::{evaluate, parse_expression, Arguments},
+        generate_template, undefined_variable, RenderContext,
+    },
+};
+
+/// The plural categories of CLDR, which languages use some of for their forms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PluralCategory {
+    Zero,
+    One,
+    Two,
+    Few,
+    Many,
+    Other,
+}
+
+use PluralCategory::{Few, Many, One, Other, Two, Zero};
+
+const CATEGORIES: [PluralCategory; 6] = [Zero, One, Two, Few, Many, Other];
+
+impl PluralCategory {
+    pub fn name(self) -> &'static str {
+        match self {
+            Zero => "zero",
+            One => "one",
+            Two => "two",
+            Few => "few",
+            Many => "many",
+            Other => "other",
+        }
+    }
+}
+
+/// `many` for round millions in the Romance languages, e.g. "1 million de livres".
+const MILLIONS: &str = "e = 0 and i != 0 and i % 1000000 = 0 and v = 0 or e != 0..5";
+
+/// The condition of each category of a language, in the order they are tested.
+/// Numbers that match none of them are `other`.
+type Rules = &'static [(PluralCategory, &'static str)];
+
+/// CLDR plural rules by language.
+static RULES: &[(&[&str], Rules)] = &[
+    (&["en", "de", "nl", "sv", "fi", "et", "ca", "gl"], &[(One, "i = 1 and v = 0")]),
+    (&["ja", "zh", "ko", "th", "vi", "id", "ms"], &[]),
+    (&["nb", "no", "el", "hu", "tr", "bg"], &[(One, "n = 1")]),
+    (&["da"], &[(One, "n = 1 or t != 0 and i = 0,1")]),
+    (&["hi"], &[(One, "i = 0 or n = 1")]),
+    (&["it"], &[(One, "i = 1 and v = 0"), (Many, MILLIONS)]),
+    (&["es"], &[(One, "n = 1"), (Many, MILLIONS)]),
+    (&["fr"], &[(One, "i = 0,1"), (Many, MILLIONS)]),
+    (&["pt"], &[(One, "i = 0..1"), (Many, MILLIONS)]),
+    (&["pt-PT"], &[(One, "i = 1 and v = 0"), (Many, MILLIONS)]),
+    (
+        &["pl"],
+        &[
+            (One, "i = 1 and v = 0"),
+            (Few, "v = 0 and i % 10 = 2..4 and i % 100 != 12..14"),
+            (
+                Many,
+                "v = 0 and i != 1 and i % 10 = 0..1 or v = 0 and i % 10 = 5..9 or v = 0 and i % 100 = 12..14",
+            ),
+        ],
+    ),
+    (
+        &["ru", "uk"],
+        &[
+            (One, "v = 0 and i % 10 = 1 and i % 100 != 11"),
+            (Few, "v = 0 and i % 10 = 2..4 and i % 100 != 12..14"),
+            (
+                Many,
+                "v = 0 and i % 10 = 0 or v = 0 and i % 10 = 5..9 or v = 0 and i % 100 = 11..14",
+            ),
+        ],
+    ),
+    (
+        &["hr", "sr", "bs"],
+        &[
+            (
+                One,
+                "v = 0 and i % 10 = 1 and i % 100 != 11 or f % 10 = 1 and f % 100 != 11",
+            ),
+            (
+                Few,
+                "v = 0 and i % 10 = 2..4 and i % 100 != 12..14 or f % 10 = 2..4 and f % 100 != 12..14",
+            ),
+        ],
+    ),
+    (
+        &["cs", "sk"],
+        &[
+            (One, "i = 1 and v = 0"),
+            (Few, "i = 2..4 and v = 0"),
+            (Many, "v != 0"),
+        ],
+    ),
+    (
+        &["sl"],
+        &[
+            (One, "v = 0 and i % 100 = 1"),
+            (Two, "v = 0 and i % 100 = 2"),
+            (Few, "v = 0 and i % 100 = 3..4 or v != 0"),
+        ],
+    ),
+    (
+        &["ro"],
+        &[
+            (One, "i = 1 and v = 0"),
+            (Few, "v != 0 or n = 0 or n != 1 and n % 100 = 1..19"),
+        ],
+    ),
+    (
+        &["lt"],
+        &[
+            (One, "n % 10 = 1 and n % 100 != 11..19"),
+            (Few, "n % 10 = 2..9 and n % 100 != 11..19"),
+            (Many, "f != 0"),
+        ],
+    ),
+    (
+        &["lv"],
+        &[
+            (Zero, "n % 10 = 0 or n % 100 = 11..19 or v = 2 and f % 100 = 11..19"),
+            (
+                One,
+                "n % 10 = 1 and n % 100 != 11 or v = 2 and f % 10 = 1 and f % 100 != 11 or v != 2 and f % 10 = 1",
+            ),
+        ],
+    ),
+    (
+        &["he"],
+        &[
+            (One, "i = 1 and v = 0 or i = 0 and v != 0"),
+            (Two, "i = 2 and v = 0"),
+        ],
+    ),
+    (
+        &["ar"],
+        &[
+            (Zero, "n = 0"),
+            (One, "n = 1"),
+            (Two, "n = 2"),
+            (Few, "n % 100 = 3..10"),
+            (Many, "n % 100 = 11..99"),
+        ],
+    ),
+    (
+        &["cy"],
+        &[
+            (Zero, "n = 0"),
+            (One, "n = 1"),
+            (Two, "n = 2"),
+            (Few, "n = 3"),
+            (Many, "n = 6"),
+        ],
+    ),
+    (
+        &["ga"],
+        &[
+            (One, "n = 1"),
+            (Two, "n = 2"),
+            (Few, "n = 3..6"),
+            (Many, "n = 7..10"),
+        ],
+    ),
+];
+
+/// The rules for a locale such as `pt-BR` or `ru`, falling back to its language and
+/// then to English.
+fn find_rules(tag: &str) -> Rules {
+    let tag = tag.replace('_', "-");
+    let language = tag.split('-').next().unwrap_or_default();
+    let find = |tag: &str| {
+        RULES
+            .iter()
+            .find(|(tags, _)| tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    };
+
+    find(&tag).or_else(|| find(language)).unwrap_or(&RULES[0]).1
+}
+
+/// The operands that plural rules test: the absolute value `n`, its integer digits `i`,
+/// the number `v` of visible fraction digits, the fraction digits `f` and the same
+/// without trailing zeros `t`. The exponent `e` is always 0.
+struct Operands {
+    n: f64,
+    i: u64,
+    v: u64,
+    f: u64,
+    t: u64,
+}
+
+/// Digits as a number, keeping the last 18 when there are more, which the remainders
+/// that rules use are still right for.
+fn digits_value(digits: &str) -> u64 {
+    digits[digits.len().saturating_sub(18)..]
+        .parse()
+        .unwrap_or_default()
+}
+
+/// Reads the operands of a number as it is written, so that `1.0` has a visible
+/// fraction digit and is not `one` in English.
+fn operands(value: &serde_json::Value) -> Option<Operands> {
+    let written = match value {
+        serde_json::Value::Number(number) => number.to_string(),
+        serde_json::Value::String(text) => text.trim().to_owned(),
+        _ => return None,
+    };
+    let n: f64 = written.parse().ok().filter(|n: &f64| n.is_finite())?;
+    let written = match written.contains(['e', 'E']) {
+        true => n.to_string(),
+        false => written,
+    };
+
+    let absolute = written.trim_start_matches(['-', '+']);
+    let (integer, fraction) = absolute.split_once('.').unwrap_or((absolute, ""));
+    if !(integer.chars().chain(fraction.chars())).all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    Some(Operands {
+        n: n.abs(),
+        i: digits_value(integer),
+        v: fraction.len() as u64,
+        f: digits_value(fraction),
+        t: digits_value(fraction.trim_end_matches('0')),
+    })
+}
+
+/// Whether a relation of a rule such as `i % 10 = 2..4` or `n != 1` holds.
+fn relation_holds(relation: &str, operands: &Operands) -> bool {
+    let (expression, negated, ranges) = match relation.split_once("!=") {
+        Some((expression, ranges)) => (expression, true, ranges),
+        None => {
+            let (expression, ranges) = relation.split_once('=').unwrap_or_default();
+            (expression, false, ranges)
+        }
+    };
+
+    let (operand, modulus) = match expression.split_once('%') {
+        Some((operand, modulus)) => (operand.trim(), modulus.trim().parse::<u64>().ok()),
+        None => (expression.trim(), None),
+    };
+    let integer = |value: u64| modulus.map_or(value, |m| value % m) as f64;
+    let value = match operand {
+        "n" => modulus.map_or(operands.n, |m| operands.n % m as f64),
+        "i" => integer(operands.i),
+        "v" => integer(operands.v),
+        "f" => integer(operands.f),
+        "t" => integer(operands.t),
+        _ => 0.0,
+    };
+
+    let matches = ranges.split(',').any(|range| {
+        let bound = |b: &str| b.trim().parse::<f64>().unwrap_or(f64::NAN);
+        match range.split_once("..") {
+            Some((low, high)) => {
+                value.fract() == 0.0 && bound(low) <= value && value <= bound(high)
+            }
+            None => value == bound(range),
+        }
+    });
+    matches != negated
+}
+
+/// Which category a count is in for a locale, or `None` when it is not a number.
+pub fn plural_category(locale: &str, count: &serde_json::Value) -> Option<PluralCategory> {
+    let operands = operands(count)?;
+    let category = find_rules(locale)
+        .iter()
+        .find(|(_, condition)| {
+            condition.split(" or ").any(|all| {
+                all.split(" and ")
+                    .all(|relation| relation_holds(relation, &operands))
+            })
+        })
+        .map_or(Other, |(category, _)| *category);
+    Some(category)
+}
+
+/// Which form of a gettext plural message a count uses in a locale. gettext numbers
+/// the forms in the order of the CLDR categories of the language, e.g. `one`, `few`,
+/// `many` for Polish, whose `other` is only for fractions.
+pub fn plural_form_index(locale: &str, count: &serde_json::Value) -> Option<usize> {
+    let category = plural_category(locale, count)?;
+    let rules = find_rules(locale);
+    CATEGORIES
+        .iter()
+        .filter(|c| **c == Other || rules.iter().any(|(rule, _)| rule == *c))
+        .position(|c| *c == category)
+}
+
+fn count_category(
+    name: &str,
+    locale: &str,
+    count: &serde_json::Value,
+) -> Result<PluralCategory, Error> {
+    plural_category(locale, count).ok_or_else(|| {
+        Error::TypeError(format!(
+            "\n{} needs a number to count, not {}\n",
+            name, count
+        ))
+    })
+}
+
+/// `{#plural count#}{#one#}...{#few#}...{#other#}...{#endplural#}` renders the form for
+/// the plural category of the count in the locale of the environment, or `other`.
+pub fn evaluate_plural(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let construct_token = node.tokens.first().unwrap();
+    let operand = node.value["{#plural".len()..node.value.len() - 2].trim();
+    let count = match evaluate(&parse_expression(operand)?, params, parent_params, ctx) {
+        Err(Error::UndefinedVariable(e)) => return undefined_variable(node, operand, e, ctx),
+        count => count?,
+    };
+    let category = count_category("{#plural#}", &ctx.environment.locale, &count)?;
+
+    // Anything before the first form is only there for layout and never renders
+    let mut forms: Vec<(&ASTNode, Ast)> = vec![];
+    for child in &node.children.as_ref().unwrap().nodes {
+        match child.identifier {
+            ASTNodeIdentifier::PluralForm => forms.push((child, Ast { nodes: vec![] })),
+            _ => {
+                if let Some((_, form)) = forms.last_mut() {
+                    form.nodes.push(child.clone());
+                }
+            }
+        }
+    }
+
+    for (i, (form, _)) in forms.iter().enumerate() {
+        if forms[..i]
+            .iter()
+            .any(|(earlier, _)| earlier.value == form.value)
+        {
+            let form_token = form.tokens.first().unwrap();
+            return Err(Error::InvalidSyntax(format!(
+                "\nA plural can only have one {}\nat line: {}:{}\n",
+                form.value, form_token.line_start, form_token.pos_start
+            )));
+        }
+    }
+    if !forms.iter().any(|(form, _)| form.value == "{#other#}") {
+        return Err(Error::InvalidSyntax(format!(
+            "\nA plural needs an {{#other#}} form\nat line: {}:{}\n",
+            construct_token.line_start, construct_token.pos_start
+        )));
+    }
+
+    let form_for = |category: PluralCategory| {
+        forms
+            .iter()
+            .find(|(form, _)| form.value == format!("{{#{}#}}", category.name()))
+    };
+    let (_, form) = form_for(category).or_else(|| form_for(Other)).unwrap();
+
+    generate_template(
+        form.clone(),
+        params.clone(),
+        parent_params.clone(),
+        open_loop_stack.to_owned(),
+        ctx,
+    )
+}
+
+/// `${count?plural(one="item", other="items")}` picks the value for the plural category
+/// of the count in the locale of the environment, or `other`.
+pub fn apply_plural_builtin(
+    name: &str,
+    target: Option<serde_json::Value>,
+    arguments: &Arguments,
+    ctx: &RenderContext,
+) -> Result<serde_json::Value, Error> {
+    if !arguments.0.is_empty() {
+        return Err(argument_error(
+            name,
+            "its forms by category, e.g. one=\"item\", other=\"items\"",
+        ));
+    }
+    let parameters = CATEGORIES.map(PluralCategory::name);
+    let mut bound = bind_arguments(name, arguments, &parameters, 0)?;
+    if bound[5].is_none() {
+        return Err(Error::InvalidApi(format!(
+            "?{} is missing the argument 'other'",
+            name
+        )));
+    }
+
+    let count = required_target(name, target)?;
+    let category = count_category(&format!("?{}", name), &ctx.environment.locale, &count)?;
+    let index = CATEGORIES.iter().position(|c| *c == category).unwrap();
+    let form = bound[index].take().or(bound[5].take()).unwrap();
+    Ok(form.clone())
+}
@@ -0,0 +1,51 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::ASTNode,
+    error::{Error, TemplateResult},
+    template::{
+        expression::{parse_expression, Expression},
+        generate_template, RenderContext,
+    },
+};
+
+/// The section that everything outside of `{#section#}` blocks goes to.
+pub const DEFAULT_SECTION: &str = "default";
+
+/// `{#section "subject"#}...{#endsection#}` adds its contents to the named section when
+/// rendering with `render_sections`, and otherwise renders in place. A section that
+/// renders more than once, e.g. in a loop, adds to what it has so far.
+pub fn evaluate_section(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let name = match parse_expression(node.value["{#section".len()..node.value.len() - 2].trim()) {
+        Ok(Expression::Literal(serde_json::Value::String(name))) => name,
+        _ => {
+            let construct_token = node.tokens.first().unwrap();
+            return Err(Error::InvalidSyntax(format!(
+                "\n{{#section#}} needs a quoted name, e.g. {{#section \"subject\"#}}\nat line: {}:{}\n",
+                construct_token.line_start, construct_token.pos_start
+            )));
+        }
+    };
+
+    let contents = generate_template(
+        node.children.clone().unwrap(),
+        params.clone(),
+        parent_params.clone(),
+        open_loop_stack.to_owned(),
+        ctx,
+    )?;
+
+    match &mut ctx.sections {
+        Some(sections) if name != DEFAULT_SECTION => {
+            sections.entry(name).or_default().push_str(&contents);
+            Ok("".to_owned())
+        }
+        _ => Ok(contents),
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{ASTNode, ASTNodeIdentifier, Ast},
+    error::{Error, TemplateResult},
+    template::{
+        conditional::values_equal,
+        expression::{evaluate, parse_expression, parse_expression_list},
+        generate_template, undefined_variable, RenderContext,
+    },
+};
+
+/// `{#switch value#}{#case "a", "b"#}...{#default#}...{#endswitch#}` renders the first
+/// case with a value equal to the switch value, or the default if none are.
+pub fn evaluate_switch(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    let operand = node.value["{#switch".len()..node.value.len() - 2].trim();
+    let value = match evaluate(&parse_expression(operand)?, params, parent_params, ctx) {
+        Err(Error::UndefinedVariable(e)) => return undefined_variable(node, operand, e, ctx),
+        value => value?,
+    };
+
+    // Anything before the first case is only there for layout and never renders
+    let mut branches: Vec<(&ASTNode, Ast)> = vec![];
+    for child in &node.children.as_ref().unwrap().nodes {
+        match child.identifier {
+            ASTNodeIdentifier::Case | ASTNodeIdentifier::Default => {
+                branches.push((child, Ast { nodes: vec![] }))
+            }
+            _ => {
+                if let Some((_, branch)) = branches.last_mut() {
+                    branch.nodes.push(child.clone());
+                }
+            }
+        }
+    }
+
+    let mut defaults = branches
+        .iter()
+        .filter(|(case, _)| case.identifier == ASTNodeIdentifier::Default);
+    if let (Some(_), Some((extra, _))) = (defaults.next(), defaults.next()) {
+        let construct_token = extra.tokens.first().unwrap();
+        return Err(Error::InvalidSyntax(format!(
+            "\nA switch can only have one {{#default#}}\nat line: {}:{}\n",
+            construct_token.line_start, construct_token.pos_start
+        )));
+    }
+
+    let mut default = None;
+    for (case, branch) in branches {
+        if case.identifier == ASTNodeIdentifier::Default {
+            default = Some(branch);
+            continue;
+        }
+
+        let values_source = case.value["{#case".len()..case.value.len() - 2].trim();
+        for expression in parse_expression_list(values_source)? {
+            let case_value = match evaluate(&expression, params, parent_params, ctx) {
+                Err(Error::UndefinedVariable(e)) => {
+                    return undefined_variable(case, values_source, e, ctx)
+                }
+                case_value => case_value?,
+            };
+            if values_equal(&value, &case_value) {
+                return render_branch(branch, params, parent_params, open_loop_stack, ctx);
+            }
+        }
+    }
+
+    match default {
+        Some(branch) => render_branch(branch, params, parent_params, open_loop_stack, ctx),
+        None => Ok("".to_owned()),
+    }
+}
+
+fn render_branch(
+    branch: Ast,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    open_loop_stack: &[String],
+    ctx: &mut RenderContext,
+) -> TemplateResult {
+    generate_template(
+        branch,
+        params.clone(),
+        parent_params.clone(),
+        open_loop_stack.to_owned(),
+        ctx,
+    )
+}
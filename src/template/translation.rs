@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+
+use crate::{
+    ast::{ASTNode, ASTNodeIdentifier, Ast},
+    catalog::{Catalog, ExtractedMessage, FluentPart, Translation},
+    error::{Error, TemplateResult},
+    template::{
+        expression::{parse_expression, Argument, Arguments, Expression},
+        is_plain_path, lookup_parameter,
+        patterns::tag_expressions,
+        plurals::{plural_category, plural_form_index, PluralCategory},
+        undefined_variable, value_to_string, variable_contents, RenderContext,
+    },
+};
+
+/// The message of a `{#trans#}` block.
+struct TransMessage {
+    /// The key given with `{#trans "key"#}`, or else the text.
+    id: String,
+    /// The contents with whitespace collapsed and variables as `${name}`.
+    text: String,
+}
+
+fn trans_error(node: &ASTNode, message: &str) -> Error {
+    let construct_token = node.tokens.first().unwrap();
+    Error::InvalidSyntax(format!(
+        "\n{}\nat line: {}:{}\n",
+        message, construct_token.line_start, construct_token.pos_start
+    ))
+}
+
+fn trans_message(node: &ASTNode) -> Result<TransMessage, Error> {
+    let key = match node.value["{#trans".len()..node.value.len() - 2].trim() {
+        "" => None,
+        key => match parse_expression(key) {
+            Ok(Expression::Literal(serde_json::Value::String(key))) => Some(key),
+            _ => {
+                return Err(trans_error(
+                    node,
+                    "{#trans#} only takes a quoted key, e.g. {#trans \"greeting\"#}",
+                ))
+            }
+        },
+    };
+
+    let mut text = String::new();
+    for child in &node.children.as_ref().unwrap().nodes {
+        match child.identifier {
+            ASTNodeIdentifier::Text | ASTNodeIdentifier::NewLine => text.push_str(&child.value),
+            ASTNodeIdentifier::Variable => {
+                let variable = variable_contents(child).trim();
+                if !is_plain_path(variable) {
+                    return Err(trans_error(
+                        child,
+                        &format!(
+                            "Only variables such as ${{name}} can be used in {{#trans#}}, not {}",
+                            child.value
+                        ),
+                    ));
+                }
+                text.push_str(&format!("${{{}}}", variable));
+            }
+            _ => {
+                return Err(trans_error(
+                    child,
+                    &format!("{} can not be used in {{#trans#}}", child.value),
+                ))
+            }
+        }
+    }
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    Ok(TransMessage {
+        id: key.unwrap_or_else(|| text.clone()),
+        text,
+    })
+}
+
+/// Writes out the `${name}` placeholders of a translation.
+fn fill_placeholders(
+    translation: &str,
+    mut value: impl FnMut(&str) -> TemplateResult,
+) -> TemplateResult {
+    let mut filled = String::new();
+    let mut rest = translation;
+    while let Some(start) = rest.find("${") {
+        filled.push_str(&rest[..start]);
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => {
+                return Err(Error::InvalidSyntax(format!(
+                    "\nThe translation '{}' has a ${{ with no closing }}\n",
+                    translation
+                )))
+            }
+        };
+
+        let name = rest[start + 2..end].trim();
+        if name.is_empty() || !is_plain_path(name) {
+            return Err(Error::InvalidSyntax(format!(
+                "\nThe translation '{}' can only use variables such as ${{name}}\n",
+                translation
+            )));
+        }
+        filled.push_str(&value(name)?);
+        rest = &rest[end + 1..];
+    }
+    filled.push_str(rest);
+
+    Ok(filled)
+}
+
+/// Reads the count of a plural message.
+fn count_number(count: &serde_json::Value) -> Result<f64, Error> {
+    let number = match count {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(text) => text.trim().parse().ok(),
+        _ => None,
+    };
+    number.ok_or_else(|| Error::TypeError(format!("\n_n needs a number to count, not {}\n", count)))
+}
+
+/// Whether the key of a Fluent variant such as `[0]` or `[male]` is the value itself.
+fn variant_matches(key: &str, value: &serde_json::Value) -> bool {
+    match value {
+        serde_json::Value::String(text) => text == key,
+        serde_json::Value::Number(number) => key
+            .parse::<f64>()
+            .is_ok_and(|key| Some(key) == number.as_f64()),
+        _ => false,
+    }
+}
+
+/// Writes out a Fluent pattern, picking each selector's variant by the exact value of
+/// its variable, then by its plural category in the locale of the catalog, and
+/// otherwise the default.
+fn fluent_text<'a>(
+    catalog: &Catalog,
+    parts: &[FluentPart],
+    lookup: &dyn Fn(&str) -> Option<&'a serde_json::Value>,
+) -> String {
+    let mut text = String::new();
+    for part in parts {
+        match part {
+            FluentPart::Text(part) => text.push_str(part),
+            FluentPart::Select {
+                variable,
+                variants,
+                default,
+            } => {
+                let chosen = lookup(variable).and_then(|value| {
+                    let category = plural_category(&catalog.locale, value);
+                    variants
+                        .iter()
+                        .position(|(key, _)| variant_matches(key, value))
+                        .or_else(|| {
+                            variants
+                                .iter()
+                                .position(|(key, _)| category.is_some_and(|c| c.name() == key))
+                        })
+                });
+                let (_, variant) = &variants[chosen.unwrap_or(*default)];
+                text.push_str(&fluent_text(catalog, variant, lookup));
+            }
+        }
+    }
+    text
+}
+
+/// The text of a translation, with the form of a gettext plural picked by the count.
+/// Without `Plural-Forms` in the catalog the plural rules of its locale pick it.
+fn translation_text<'a>(
+    (catalog, translation): (&Catalog, &Translation),
+    count: Option<&serde_json::Value>,
+    lookup: &dyn Fn(&str) -> Option<&'a serde_json::Value>,
+) -> String {
+    match translation {
+        Translation::Text(text) => text.clone(),
+        Translation::Plural(forms) => {
+            let index = count.map_or(Some(0), |count| {
+                count_number(count)
+                    .ok()
+                    .and_then(|number| catalog.plural_index(number))
+                    .or_else(|| plural_form_index(&catalog.locale, count))
+            });
+            // A count past the last form, such as a fraction in Polish, uses the last
+            match index.and_then(|index| forms.get(index)) {
+                Some(form) => form.clone(),
+                None => forms.last().unwrap().clone(),
+            }
+        }
+        Translation::Fluent(parts) => fluent_text(catalog, parts, lookup),
+    }
+}
+
+/// Renders a `{#trans#}` block in the locale of the environment, or as written when
+/// there is no translation.
+pub fn evaluate_trans(
+    node: &ASTNode,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &RenderContext,
+) -> TemplateResult {
+    let message = trans_message(node)?;
+    let lookup = |name: &str| lookup_parameter(name, params, parent_params, ctx);
+    let translation = match ctx.environment.translate(&message.id) {
+        Some(found) => translation_text(found, None, &lookup),
+        None => message.text,
+    };
+
+    let filled = fill_placeholders(&translation, |name| match lookup(name) {
+        Some(value) => Ok(value_to_string(value)),
+        None => undefined_variable(
+            node,
+            name,
+            format!("\n{} is missing from parameter data mapping.\n", name),
+            ctx,
+        ),
+    });
+
+    match filled {
+        Err(Error::InvalidSyntax(e)) => Err(trans_error(node, e.trim())),
+        filled => filled,
+    }
+}
+
+/// Fills the placeholders of a translation from `_` and `_n`.
+fn fill_call<'a>(
+    translation: &str,
+    lookup: &dyn Fn(&str) -> Option<&'a serde_json::Value>,
+) -> Result<serde_json::Value, Error> {
+    fill_placeholders(translation, |name| match lookup(name) {
+        Some(value) => Ok(value_to_string(value)),
+        None => Err(Error::UndefinedVariable(format!(
+            "\n{} is missing from parameter data mapping.\n",
+            name
+        ))),
+    })
+    .map(serde_json::Value::String)
+}
+
+/// `_("key")` or `_("Hello ${name}", name=user.name)`, where the placeholders of the
+/// translation are filled from the keyword arguments or else from the variables in scope.
+pub fn translate_call(
+    arguments: Arguments,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &RenderContext,
+) -> Result<serde_json::Value, Error> {
+    let (positional, keyword) = arguments;
+    let id = match positional.as_slice() {
+        [serde_json::Value::String(id)] => id,
+        _ => {
+            return Err(Error::InvalidApi(
+                "_ expects the message as its only positional argument, e.g. _(\"greeting\")"
+                    .to_owned(),
+            ))
+        }
+    };
+
+    let lookup = |name: &str| {
+        keyword
+            .iter()
+            .find(|(keyword, _)| keyword == name)
+            .map(|(_, value)| value)
+            .or_else(|| lookup_parameter(name, params, parent_params, ctx))
+    };
+    let translation = match ctx.environment.translate(id) {
+        Some(found) => translation_text(found, None, &lookup),
+        None => id.clone(),
+    };
+    fill_call(&translation, &lookup)
+}
+
+/// `_n("One file", "${count} files", files?size)`, which translates a message with
+/// plural forms and has the count available as `${count}`. Without a translation the
+/// singular is used for a count of 1.
+pub fn translate_plural_call(
+    arguments: Arguments,
+    params: &HashMap<String, serde_json::Value>,
+    parent_params: &HashMap<String, serde_json::Value>,
+    ctx: &RenderContext,
+) -> Result<serde_json::Value, Error> {
+    let (positional, keyword) = arguments;
+    let (singular, plural, count) = match positional.as_slice() {
+        [serde_json::Value::String(singular), serde_json::Value::String(plural), count] => {
+            (singular, plural, count)
+        }
+        _ => {
+            return Err(Error::InvalidApi(
+                "_n expects the singular, the plural and the count, e.g. _n(\"One file\", \"${count} files\", n)"
+                    .to_owned(),
+            ))
+        }
+    };
+    count_number(count)?;
+
+    let lookup = |name: &str| {
+        keyword
+            .iter()
+            .find(|(keyword, _)| keyword == name)
+            .map(|(_, value)| value)
+            .or_else(|| (name == "count").then_some(count))
+            .or_else(|| lookup_parameter(name, params, parent_params, ctx))
+    };
+    let translation = match ctx.environment.translate(singular) {
+        Some(found) => translation_text(found, Some(count), &lookup),
+        None => match plural_category("en", count) {
+            Some(PluralCategory::One) => singular.clone(),
+            _ => plural.clone(),
+        },
+    };
+    fill_call(&translation, &lookup)
+}
+
+/// The literal messages of the `_("...")` and `_n("...", "...", n)` calls in an
+/// expression, with the plural of `_n`.
+fn call_messages(expression: &Expression, ids: &mut Vec<(String, Option<String>)>) {
+    match expression {
+        Expression::Literal(_) | Expression::Path(_) => {}
+        Expression::Negate(expression) | Expression::Not(expression) => {
+            call_messages(expression, ids)
+        }
+        Expression::Binary(_, left, right) | Expression::Default(left, right) => {
+            call_messages(left, ids);
+            call_messages(right, ids);
+        }
+        Expression::Conditional(condition, then, otherwise) => {
+            call_messages(condition, ids);
+            call_messages(then, ids);
+            call_messages(otherwise, ids);
+        }
+        Expression::Call(name, arguments) => {
+            let literal = |i: usize| match arguments.get(i) {
+                Some(Argument {
+                    name: None,
+                    value: Expression::Literal(serde_json::Value::String(text)),
+                }) => Some(text.clone()),
+                _ => None,
+            };
+            match (name.as_str(), literal(0), literal(1)) {
+                ("_", Some(id), _) => ids.push((id, None)),
+                ("_n", Some(id), Some(plural)) => ids.push((id, Some(plural))),
+                _ => {}
+            }
+            arguments
+                .iter()
+                .for_each(|argument| call_messages(&argument.value, ids));
+        }
+        Expression::Builtin(target, _, arguments) => {
+            call_messages(target, ids);
+            arguments
+                .iter()
+                .for_each(|argument| call_messages(&argument.value, ids));
+        }
+    }
+}
+
+/// Finds the messages of `{#trans#}` blocks and `_("...")` calls, in the order they
+/// appear.
+pub fn extract_messages(
+    ast: &Ast,
+    name: &str,
+    messages: &mut Vec<ExtractedMessage>,
+) -> Result<(), Error> {
+    for node in &ast.nodes {
+        let line = node.tokens.first().map_or(0, |t| t.line_start);
+        let reference = format!("{}:{}", name, line);
+
+        match node.identifier {
+            ASTNodeIdentifier::Raw => continue,
+            ASTNodeIdentifier::Trans => {
+                let message = trans_message(node)?;
+                let default = (message.id != message.text).then_some(message.text);
+                messages.push(ExtractedMessage {
+                    id: message.id,
+                    plural: None,
+                    default,
+                    reference,
+                });
+                continue;
+            }
+            _ => {}
+        }
+
+        if let Ok(expressions) = tag_expressions(node) {
+            let mut ids = vec![];
+            expressions
+                .iter()
+                .for_each(|expression| call_messages(expression, &mut ids));
+            messages.extend(ids.into_iter().map(|(id, plural)| ExtractedMessage {
+                id,
+                plural,
+                default: None,
+                reference: reference.clone(),
+            }));
+        }
+
+        if let Some(children) = &node.children {
+            extract_messages(children, name, messages)?;
+        }
+    }
+
+    Ok(())
+}
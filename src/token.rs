@@ -6,6 +6,7 @@ pub enum Identifier {
     ClosedBracket,
     Pound,
     NewLine,
+    Comment,
 }
 
 #[derive(Debug, Clone)]
@@ -37,60 +38,220 @@ impl Token {
     }
 }
 
-fn check_for_special_identifier(char: &char) -> bool {
-    *char == '$' || *char == '{' || *char == '}' || *char == '#' || *char == '\n'
+#[derive(Debug, PartialEq)]
+enum Mode {
+    Text,
+    Variable,
+    Block,
+}
+
+struct Lexer {
+    tokens: Vec<Token>,
+    text: Token,
+    line: i32,
+    column: i32,
+}
+
+impl Lexer {
+    fn push_text(&mut self, char: char) {
+        if self.text.value.is_empty() {
+            self.text = Token::new(
+                Identifier::Text,
+                self.line,
+                self.line,
+                self.column,
+                self.column,
+            );
+        }
+        self.text.value.push(char);
+        self.advance(char);
+        self.text.line_end = self.line;
+        self.text.pos_end = self.column - 1;
+    }
+
+    fn push_special(&mut self, identifier: Identifier, char: char) {
+        self.flush_text();
+        let mut token = Token::new(identifier, self.line, self.line, self.column, self.column);
+        token.value.push(char);
+        self.advance(char);
+        token.line_end = self.line;
+        self.tokens.push(token);
+    }
+
+    fn push_comment(&mut self, comment: &[char]) {
+        self.flush_text();
+        let mut token = Token::new(
+            Identifier::Comment,
+            self.line,
+            self.line,
+            self.column,
+            self.column,
+        );
+        for &char in comment {
+            token.value.push(char);
+            self.advance(char);
+        }
+        token.line_end = self.line;
+        token.pos_end = self.column - 1;
+        self.tokens.push(token);
+    }
+
+    fn flush_text(&mut self) {
+        if !self.text.value.is_empty() {
+            let text = std::mem::replace(&mut self.text, Token::new(Identifier::Text, 1, 1, 1, 1));
+            self.tokens.push(text);
+        }
+    }
+
+    fn advance(&mut self, char: char) {
+        match char {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            _ => self.column += 1,
+        }
+    }
+}
+
+/// Matches a block tag such as `{#raw#}` or `{#-endraw-#}` with one of the given
+/// names at the start of `chars`, returning its length and which name matched.
+fn match_block_tag<'a>(chars: &[char], names: &[&'a str]) -> Option<(usize, &'a str)> {
+    let tag: String = chars.iter().take(20).collect();
+    let inner = tag.strip_prefix("{#")?;
+    let inner = inner.strip_prefix('-').unwrap_or(inner);
+
+    names.iter().find_map(|name| {
+        let after = inner.strip_prefix(name)?;
+        let after = after.strip_prefix('-').unwrap_or(after);
+        after
+            .starts_with("#}")
+            .then(|| (tag.chars().count() - after.chars().count() + 2, *name))
+    })
 }
 
+/// Splits a template into tokens. Only `${`, `{#` and `{!--` start kitamura syntax,
+/// everything else such as CSS braces or a `$5` price is left as text. A leading
+/// backslash e.g. `\${` keeps an opener as literal text.
 pub fn generate_tokens(template_html: String) -> Vec<Token> {
-    let mut tokens: Vec<Token> = vec![];
-    let mut token = Token::new(Identifier::Text, 1, 1, 1, 1);
-    let mut i = 1;
-    let mut current_line = 1;
-
-    for char in template_html.chars() {
-        let special_char_match = check_for_special_identifier(&char);
-
-        match special_char_match {
-            true => {
-                // Push the text token if prev token wasn't special
-                if !token.value.is_empty() {
-                    tokens.push(token);
-                }
+    let chars: Vec<char> = template_html.chars().collect();
+    let mut lexer = Lexer {
+        tokens: vec![],
+        text: Token::new(Identifier::Text, 1, 1, 1, 1),
+        line: 1,
+        column: 1,
+    };
+    let mut mode = Mode::Text;
+    let mut quote: Option<char> = None;
+    let mut index = 0;
 
-                // new token setup to account for the above
-                token = Token::new(Identifier::Text, current_line, current_line, i + 1, i + 1);
-                token.value.push(char);
-
-                match char {
-                    '$' => token.identifier = Identifier::Dollar,
-                    '{' => token.identifier = Identifier::OpenBracket,
-                    '}' => token.identifier = Identifier::ClosedBracket,
-                    '#' => token.identifier = Identifier::Pound,
-                    '\n' => {
-                        token.identifier = Identifier::NewLine;
-                        current_line += 1;
-                        token.line_end = current_line;
-                        i = 0;
-                    }
-                    _ => {}
-                }
+    while index < chars.len() {
+        let rest = &chars[index..];
+        let char = rest[0];
 
-                // Push special char token and generate new token setup
-                tokens.push(token);
+        match mode {
+            Mode::Text => {
+                if char == '\\'
+                    && (rest[1..].starts_with(&['$', '{'])
+                        || rest[1..].starts_with(&['{', '#'])
+                        || rest[1..].starts_with(&['{', '!', '-', '-']))
+                {
+                    // Escaped opener, the backslash is dropped and the opener is text
+                    lexer.advance(char);
+                    lexer.push_text(rest[1]);
+                    lexer.push_text(rest[2]);
+                    index += 3;
+                    continue;
+                } else if rest.starts_with(&['{', '!', '-', '-']) {
+                    // Comments are kept whole so that nothing inside of them is tokenised
+                    let comment_length = rest[4..]
+                        .windows(3)
+                        .position(|w| w == ['-', '-', '}'])
+                        .map(|end| end + 7)
+                        .unwrap_or(rest.len());
+                    lexer.push_comment(&rest[..comment_length]);
+                    index += comment_length;
+                    continue;
+                } else if let Some((tag_length, name)) = match_block_tag(rest, &["raw", "noparse"])
+                {
+                    // Everything up until the matching end tag is passed through as text
+                    let end_name = format!("end{}", name);
+                    lexer.push_special(Identifier::OpenBracket, '{');
+                    lexer.push_special(Identifier::Pound, '#');
+                    for &c in &rest[2..tag_length - 2] {
+                        lexer.push_text(c);
+                    }
+                    lexer.push_special(Identifier::Pound, '#');
+                    lexer.push_special(Identifier::ClosedBracket, '}');
+                    index += tag_length;
 
-                token = Token::new(Identifier::Text, current_line, current_line, i + 2, i + 2);
+                    while index < chars.len()
+                        && match_block_tag(&chars[index..], &[&end_name]).is_none()
+                    {
+                        match chars[index] {
+                            '\n' => lexer.push_special(Identifier::NewLine, '\n'),
+                            c => lexer.push_text(c),
+                        }
+                        index += 1;
+                    }
+                    continue;
+                } else if rest.starts_with(&['$', '{']) {
+                    lexer.push_special(Identifier::Dollar, '$');
+                    lexer.push_special(Identifier::OpenBracket, '{');
+                    mode = Mode::Variable;
+                    index += 2;
+                    continue;
+                } else if rest.starts_with(&['{', '#']) {
+                    lexer.push_special(Identifier::OpenBracket, '{');
+                    lexer.push_special(Identifier::Pound, '#');
+                    mode = Mode::Block;
+                    index += 2;
+                    continue;
+                } else if char == '\n' {
+                    lexer.push_special(Identifier::NewLine, char);
+                } else {
+                    lexer.push_text(char);
+                }
+            }
+            Mode::Variable | Mode::Block => {
+                if let Some(open_quote) = quote {
+                    // Quoted text may contain anything, including the closing delimiter
+                    if char == '\\' && rest.len() > 1 {
+                        lexer.push_text(char);
+                        lexer.push_text(rest[1]);
+                        index += 2;
+                        continue;
+                    }
+                    if char == open_quote {
+                        quote = None;
+                    }
+                    lexer.push_text(char);
+                } else if char == '\'' || char == '"' {
+                    quote = Some(char);
+                    lexer.push_text(char);
+                } else if mode == Mode::Variable && char == '}' {
+                    lexer.push_special(Identifier::ClosedBracket, char);
+                    mode = Mode::Text;
+                } else if mode == Mode::Block && rest.starts_with(&['#', '}']) {
+                    lexer.push_special(Identifier::Pound, '#');
+                    lexer.push_special(Identifier::ClosedBracket, '}');
+                    mode = Mode::Text;
+                    index += 2;
+                    continue;
+                } else if char == '{' {
+                    // Caught later on as an extra opening brace
+                    lexer.push_special(Identifier::OpenBracket, char);
+                } else {
+                    lexer.push_text(char);
+                }
             }
-            false => token.value.push(char),
         }
-        i += 1;
-        token.pos_end = i;
+        index += 1;
     }
 
     // Push final token builder after loop exit
-    if !token.value.is_empty() {
-        tokens.push(token);
-    }
-    tokens
+    lexer.flush_text();
+    lexer.tokens
 }
 
 pub fn parse_tokens(tokens: Vec<Token>) -> Vec<Vec<Token>> {
@@ -123,8 +284,20 @@ pub fn parse_tokens(tokens: Vec<Token>) -> Vec<Vec<Token>> {
                 }
             }
             Identifier::NewLine => variables.push(vec![token]),
+            Identifier::Comment => {
+                if !variable.is_empty() {
+                    variables.push(variable);
+                    variable = vec![];
+                }
+                variables.push(vec![token]);
+            }
         }
     }
 
+    // An opener that was never closed is kept so that it can be reported
+    if !variable.is_empty() {
+        variables.push(variable);
+    }
+
     variables
 }
@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use kitamura::render_template;
+use serde_json::json;
+
+#[test]
+fn set_with_expression() {
+    let html = "{#set total = order.subtotal + order.tax#}Total: ${total}";
+    let params = HashMap::from([("order".to_owned(), json!({"subtotal": 40, "tax": 2.5}))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Total: 42.5");
+}
+
+#[test]
+fn set_with_precedence_and_parentheses() {
+    let html = "{#set a = 2 + 3 * 4#}{#set b = (2 + 3) * -4#}{#set c = 7 / 2#}${a} ${b} ${c}";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert_eq!(rendered_html.unwrap(), "14 -20 3.5");
+}
+
+#[test]
+fn set_string_and_nested_path() {
+    let html =
+        "{#set greeting = \"Hi \\\"there\\\"\"#}{#set user = person#}${greeting} ${user.name}";
+    let params = HashMap::from([("person".to_owned(), json!({"name": "Joel"}))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Hi \"there\" Joel");
+}
+
+#[test]
+fn set_used_in_condition_and_loop() {
+    let html = "{#set fruits = basket.fruits#}{#set hungry = true#}{#if hungry#}{#for fruit of fruits#}${fruit.name};{#endfor#}{#endif#}";
+    let params = HashMap::from([(
+        "basket".to_owned(),
+        json!({"fruits": [{"name": "Lemon"}, {"name": "Pear"}]}),
+    )]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Lemon;Pear;");
+}
+
+#[test]
+fn capture_renders_into_variable() {
+    let html = "{#capture title#}<h1 class=\"title\">${name}</h1>{#endcapture#}${title}${title}";
+    let params = HashMap::from([("name".to_owned(), json!("Kitamura"))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<h1 class=\"title\">Kitamura</h1><h1 class=\"title\">Kitamura</h1>"
+    );
+}
+
+#[test]
+fn loop_control_inside_capture() {
+    let params = HashMap::from([("ns".to_owned(), json!([1, 2, 3]))]);
+    let html = "{#for n of ns#}{#capture global c#}<${n}>{#if n == 2#}{#break#}{#endif#}{#endcapture#}${n}{#endfor#}|${c}";
+    let rendered_html = render_template(html.to_owned(), params.clone());
+    assert_eq!(rendered_html.unwrap(), "1|<2>");
+
+    let html = html.replace("{#break#}", "{#continue#}");
+    let rendered_html = render_template(html, params);
+    assert_eq!(rendered_html.unwrap(), "13|<3>");
+}
+
+#[test]
+fn set_inside_loop_is_local_to_iteration() {
+    let html = "{#set label = 'outer'#}{#for fruit of fruits#}{#if fruit.ripe#}{#set label = fruit.name#}{#endif#}${label};{#endfor#}${label}";
+    let params = HashMap::from([(
+        "fruits".to_owned(),
+        json!([{"name": "Lemon", "ripe": true}, {"name": "Pear", "ripe": false}]),
+    )]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Lemon;outer;outer");
+}
+
+#[test]
+fn set_global_escapes_loop_scope() {
+    let html = "{#set last = 'none'#}{#for fruit of fruits#}{#set global last = fruit.name#}{#endfor#}{#capture global count#}${last}{#endcapture#}${count}";
+    let params = HashMap::from([(
+        "fruits".to_owned(),
+        json!([{"name": "Lemon"}, {"name": "Pear"}]),
+    )]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Pear");
+}
+
+#[test]
+fn set_errors() {
+    let missing_value = render_template("{#set total#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", missing_value.unwrap_err()).contains("Expected name = value"));
+
+    let bad_name = render_template("{#set 1x = 2#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", bad_name.unwrap_err()).contains("not a valid variable name"));
+
+    let bad_operands = render_template("{#set x = 'a' * 2#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", bad_operands.unwrap_err()).contains("Cannot apply"));
+
+    let missing = render_template("{#set x = y + 1#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", missing.unwrap_err()).contains("y is missing"));
+}
+
+#[test]
+fn capture_has_no_closing_statement() {
+    let rendered_html = render_template("{#capture x#}hello".to_owned(), HashMap::new());
+    assert!(format!("{:?}", rendered_html.unwrap_err()).contains("has no closing statement"));
+}
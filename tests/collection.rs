@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Environment, Error, UndefinedBehavior};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn size_and_length() {
+    let html =
+        "${items?size}|${items?length}|${user?size}|${name?size}|${name?length}|${empty?size}";
+    let rendered_html = render(
+        html,
+        json!({"items": [1, 2, 3], "user": {"a": 1, "b": 2}, "name": "Åsa", "empty": []}),
+    );
+    assert_eq!(rendered_html.unwrap(), "3|3|2|3|3|0");
+}
+
+#[test]
+fn join_and_map() {
+    let html = "${tags?join(', ')}|${users?map('name')?join(' & ')}|${users?join('/', attribute='address.city')}|${mixed?join}";
+    let rendered_html = render(
+        html,
+        json!({
+            "tags": ["rust", "templates"],
+            "users": [
+                {"name": "Joel", "address": {"city": "Oslo"}},
+                {"name": "Mia", "address": {"city": "Bergen"}}
+            ],
+            "mixed": ["a", 1, true, null, 2.5]
+        }),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "rust, templates|Joel & Mia|Oslo/Bergen|a1true2.5"
+    );
+
+    let rendered_html = render("${users?map('age')}", json!({"users": [{"name": "Joel"}]}));
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let rendered_html = render("${items?join}", json!({"items": [[1]]}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
+
+#[test]
+fn sum_is_exact() {
+    let html = "${lines?sum(attribute='total')}|${big?sum}|${prices?sum}|${empty?sum}|${lines?map('total')?sum}";
+    let rendered_html = render(
+        html,
+        json!({
+            "lines": [{"total": 3}, {"total": 4}],
+            "big": [9007199254740993_i64, 1, -2],
+            "prices": [1, 0.5, 0.25],
+            "empty": []
+        }),
+    );
+    assert_eq!(rendered_html.unwrap(), "7|9007199254740992|1.75|0|7");
+
+    let rendered_html = render("${big?sum}", json!({"big": [u64::MAX, 1]}));
+    assert!(matches!(rendered_html, Err(Error::Overflow(_))));
+
+    let rendered_html = render("${items?sum}", json!({"items": [1, "2"]}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
+
+#[test]
+fn min_and_max() {
+    let html = "${n?min}|${n?max}|${people?min(attribute='age')}|${people?max('name')}|${empty?min!'none'}";
+    let rendered_html = render(
+        html,
+        json!({
+            "n": [3, -1.5, 10],
+            "people": [{"name": "Joel", "age": 30}, {"name": "Mia", "age": 25}],
+            "empty": []
+        }),
+    );
+    assert_eq!(rendered_html.unwrap(), "-1.5|10|25|Mia|none");
+}
+
+#[test]
+fn empty_lists_have_no_min_max_first_or_last() {
+    let params = json!({"empty": []});
+    for builtin in ["min", "max", "first", "last"] {
+        let html = format!("[${{empty?{}}}]", builtin);
+        let rendered_html = render(&html, params.clone());
+        assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+        let html = format!(
+            "[${{empty?{} ?? 0}}|${{empty?{}?exists}}]",
+            builtin, builtin
+        );
+        assert_eq!(render(&html, params.clone()).unwrap(), "[0|false]");
+    }
+
+    let params: HashMap<String, serde_json::Value> =
+        serde_json::from_value(json!({"empty": []})).unwrap();
+    let rendered_html = Environment::new()
+        .undefined_behavior(UndefinedBehavior::Lenient)
+        .render_template("[${empty?max}]".to_owned(), params.clone());
+    assert_eq!(rendered_html.unwrap(), "[]");
+    let rendered_html = Environment::new()
+        .undefined_behavior(UndefinedBehavior::Debug)
+        .render_template("[${empty?max}]".to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "[[[undefined: empty?max]]]");
+}
+
+#[test]
+fn filter_sort_and_unique() {
+    let params = json!({
+        "users": [
+            {"name": "Joel", "active": true, "role": "admin"},
+            {"name": "Mia", "active": false, "role": "user"},
+            {"name": "Ada", "active": true, "role": "user"}
+        ],
+        "n": [3, 1, 3, 2]
+    });
+    let html = "${users?filter('active')?map('name')?join(',')}|${users?filter('role', 'user')?size}|${users?sort('name')?map('name')?join(',')}|${n?sort(reverse=true)?join}|${n?unique?join}|${users?unique('role')?size}";
+    let rendered_html = render(html, params);
+    assert_eq!(rendered_html.unwrap(), "Joel,Ada|2|Ada,Joel,Mia|3321|312|2");
+}
+
+#[test]
+fn builtins_sort_and_unique_like_loop_modifiers() {
+    let params = json!({"items": [
+        {"k": "b", "n": 1}, {"k": 2, "n": 2}, {"n": 3}, {"k": "b", "n": 4},
+        {"k": true, "n": 5}, {"k": 2.0, "n": 6}, {"k": null, "n": 7}
+    ]});
+    let html = "${items?sort('k')?map('n')?join}|{#for i of items sorted by i.k#}${i.n}{#endfor#}\
+        |${items?sort('k', reverse=true)?map('n')?join}|{#for i of items sorted by i.k desc#}${i.n}{#endfor#}\
+        |${items?unique('k')?map('n')?join}|{#for i of items unique by i.k#}${i.n}{#endfor#}";
+    let rendered_html = render(html, params).unwrap();
+    let parts: Vec<&str> = rendered_html.split('|').collect();
+    assert_eq!(parts[0], "3752614");
+    assert_eq!(parts[0], parts[1]);
+    assert_eq!(parts[2], parts[3]);
+    assert_eq!(parts[4], "1235");
+    assert_eq!(parts[4], parts[5]);
+}
+
+#[test]
+fn sequences_and_objects() {
+    let html = "${n?first}|${n?last}|${n?reverse?join('-')}|${empty?first!'-'}|${user?keys?sort?join(',')}|${user?values?sort?join(',')}";
+    let rendered_html = render(
+        html,
+        json!({"n": [1, 2, 3], "empty": [], "user": {"b": "2", "a": "1"}}),
+    );
+    assert_eq!(rendered_html.unwrap(), "1|3|3-2-1|-|a,b|1,2");
+}
+
+#[test]
+fn in_conditions() {
+    let html = "{#if items?size > 1 && items?map('price')?sum >= 10#}big{#endif#}|{#if tags?sort?first == 'a'#}first{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"items": [{"price": 4}, {"price": 6}], "tags": ["b", "a"]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "big|first");
+
+    let rendered_html = render("${n?map('x')}", json!({"n": "text"}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+
+use kitamura::render_template;
+use serde_json::json;
+
+#[test]
+fn comment_is_not_rendered() {
+    let html = "<p>Hello {!-- greet the customer --}${first_name}</p>";
+    let params = HashMap::from([("first_name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "<p>Hello Joel</p>");
+}
+
+#[test]
+fn comment_contents_are_not_parsed() {
+    let html = "<p>{!-- ${not_provided} {#for x of y#} { # } $ --}done</p>";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert_eq!(rendered_html.unwrap(), "<p>done</p>");
+}
+
+#[test]
+fn comment_spanning_lines_on_its_own_leaves_no_blank_lines() {
+    let html = "<ul>\n  {!--\n    One item per fruit.\n  --}\n  {#for fruit of fruits#}\n  <li>${fruit.name}</li>\n  {#endfor#}\n</ul>";
+    let params = HashMap::from([("fruits".to_owned(), json!([{"name": "Lemon"}]))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "<ul>\n  <li>Lemon</li>\n</ul>");
+}
+
+#[test]
+fn comment_missing_end() {
+    let html = "<p>{!-- never closed ${first_name}</p>";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert!(rendered_html.is_err());
+}
+
+#[test]
+fn comment_lines_still_count_towards_positions() {
+    let html = "{!--\n\n--}\n  {#unknown#}";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    let error = format!("{:?}", rendered_html.unwrap_err());
+    assert!(error.contains("at line 4:"), "{}", error);
+}
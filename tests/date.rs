@@ -0,0 +1,139 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use kitamura::{render_template, Environment, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+/// Renders with the clock stopped at 2024-03-15T12:00:00Z.
+fn render_at_noon(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    Environment::new()
+        .clock(|| SystemTime::UNIX_EPOCH + Duration::from_secs(1_710_504_000))
+        .render_template(html.to_owned(), params)
+}
+
+#[test]
+fn parse_iso_and_epoch() {
+    let html = "${a?date_format}|${b?date_format('yyyy-MM-dd HH:mm:ss')}|${c?date_format('HH:mm xxx')}|${d?date_format('yyyy-MM-dd HH:mm:ss.SSS')}|${e?date_format('d.M.yy H:m')}";
+    let rendered_html = render(
+        html,
+        json!({
+            "a": "2024-05-01",
+            "b": "2024-05-01T08:30:05",
+            "c": "2024-05-01T08:30:00+02:00",
+            "d": 1714552200.25,
+            "e": 1714552200
+        }),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "2024-05-01|2024-05-01 08:30:05|08:30 +02:00|2024-05-01 08:30:00.250|1.5.24 8:30"
+    );
+}
+
+#[test]
+fn cldr_and_strftime_patterns() {
+    let html = "${d?date_format(\"EEEE, d MMMM yyyy 'at' h:mm a\")}|${d?date_format(\"EEE d MMM ''yy\")}|${d?date_format('%A %d %B %Y, %I:%M %p')}|${d?date_format('%a %b %-d, 100%%')}";
+    let rendered_html = render(html, json!({"d": "2024-03-05T14:07:00Z"}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Tuesday, 5 March 2024 at 2:07 PM|Tue 5 Mar '24|Tuesday 05 March 2024, 02:07 PM|Tue Mar 5, 100%"
+    );
+}
+
+#[test]
+fn locales() {
+    let html = "${d?date_format('EEEE d MMMM yyyy')}|${d?date_format('%A %d %B', locale='fr')}|${d?date_format('d MMMM', locale='ru')}";
+    let params: HashMap<String, serde_json::Value> =
+        serde_json::from_value(json!({"d": "2024-03-05"})).unwrap();
+    let rendered_html = Environment::new()
+        .locale("de-DE")
+        .render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Dienstag 5 März 2024|mardi 05 mars|5 марта"
+    );
+}
+
+#[test]
+fn timezones() {
+    let html = "${d?date_format('yyyy-MM-dd HH:mm z', timezone='Europe/Berlin')}|${s?date_format('HH:mm z', timezone='Europe/Berlin')}|${d?timezone('America/New_York')}|${d?timezone('America/New_York')?date_format('HH:mm XXX')}";
+    let rendered_html = render(
+        html,
+        json!({"d": "2024-01-15T23:30:00Z", "s": "2024-07-01T12:00:00Z"}),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "2024-01-16 00:30 CET|14:00 CEST|2024-01-15T18:30:00-05:00|18:30 -05:00"
+    );
+
+    let rendered_html = render("${d?timezone('Mars/Olympus')}", json!({"d": "2024-01-15"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+}
+
+#[test]
+fn relative_to_clock() {
+    let html =
+        "${a?relative}|${b?relative}|${c?relative}|${d?relative}|${e?relative}|${f?relative}";
+    let rendered_html = render_at_noon(
+        html,
+        json!({
+            "a": "2024-03-15T11:59:50Z",
+            "b": "2024-03-15T11:20:00Z",
+            "c": "2024-03-12T12:00:00Z",
+            "d": "2024-03-15T14:00:00Z",
+            "e": "2023-12-15T12:00:00Z",
+            "f": "2021-03-15"
+        }),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "just now|40 minutes ago|3 days ago|in 2 hours|3 months ago|3 years ago"
+    );
+}
+
+#[test]
+fn arithmetic() {
+    let html = "${d?date_add(1, 'month')}|${d?date_add(-2, 'days')?date_format}|${d?date_add(90, 'minutes')}|${d?date_diff('2024-01-01', 'days')}|${d?date_diff('2023-02-01', 'months')}|${d?date_diff('2023-02-01', 'years')}|${d?epoch}";
+    let rendered_html = render(html, json!({"d": "2024-01-31T10:00:00Z"}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "2024-02-29T10:00:00Z|2024-01-29|2024-01-31T11:30:00Z|30|11|0|1706695200"
+    );
+
+    let rendered_html = render("${d?date_add(1, 'fortnight')}", json!({"d": "2024-01-31"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+}
+
+#[test]
+fn comparisons_in_conditions() {
+    let html = "{#if due?before#}overdue{#endif#}{#if not due?before#}pending{#endif#}|{#if shipped?after(ordered)#}ok{#endif#}|{#if due?date_diff('2024-03-01') >= 7#}week{#endif#}|{#if shipped?epoch > ordered?epoch#}later{#endif#}";
+    let rendered_html = render_at_noon(
+        html,
+        json!({
+            "due": "2024-03-10",
+            "ordered": "2024-03-01T09:00:00+01:00",
+            "shipped": "2024-03-01T08:30:00Z"
+        }),
+    );
+    assert_eq!(rendered_html.unwrap(), "overdue|ok|week|later");
+}
+
+#[test]
+fn invalid_dates() {
+    let rendered_html = render("${d?date_format}", json!({"d": "yesterday"}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render("${d?date_format('yyyy Q')}", json!({"d": "2024-01-01"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("${d?date_format}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+}
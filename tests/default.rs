@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn default_for_missing_variable() {
+    let rendered_html = render("Hi ${nickname!\"friend\"}!", json!({}));
+    assert_eq!(rendered_html.unwrap(), "Hi friend!");
+
+    let rendered_html = render("Hi ${nickname!\"friend\"}!", json!({"nickname": "Jo"}));
+    assert_eq!(rendered_html.unwrap(), "Hi Jo!");
+}
+
+#[test]
+fn default_without_value_is_empty() {
+    let rendered_html = render("[${nickname!}]", json!({}));
+    assert_eq!(rendered_html.unwrap(), "[]");
+}
+
+#[test]
+fn null_coalescing() {
+    let html = "${nickname ?? user.name} ${user.title ?? user.nickname ?? 'none'}";
+    let rendered_html = render(html, json!({"user": {"name": "Joel", "title": null}}));
+    assert_eq!(rendered_html.unwrap(), "Joel none");
+}
+
+#[test]
+fn default_for_missing_path_segments() {
+    let html = "${order.customer.name!'guest'} ${order.customer.name ?? 'guest'}";
+    let rendered_html = render(html, json!({"order": {}}));
+    assert_eq!(rendered_html.unwrap(), "guest guest");
+
+    let rendered_html = render(html, json!({}));
+    assert_eq!(rendered_html.unwrap(), "guest guest");
+}
+
+#[test]
+fn default_is_only_evaluated_when_needed() {
+    let rendered_html = render("${count ?? 1 / 0}", json!({"count": 0}));
+    assert_eq!(rendered_html.unwrap(), "0");
+
+    let rendered_html = render("${count ?? 1 / 0}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::DivisionByZero(_))));
+}
+
+#[test]
+fn defaults_in_expressions_and_loops() {
+    let html = "{#for line of lines#}${line.price * line.quantity!1};{#endfor#}{#if (flag ?? false) == false#}off{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"lines": [{"price": 2, "quantity": 3}, {"price": 5}]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "6;5;off");
+}
+
+#[test]
+fn has_content() {
+    let html = "{#if a?has_content#}a{#endif#}{#if b?has_content#}b{#endif#}{#if c?has_content#}c{#endif#}{#if d?has_content#}d{#endif#}{#if e?has_content#}e{#endif#}{#if f?has_content#}f{#endif#}{#if g?has_content#}g{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"a": "text", "b": "", "c": null, "d": [], "e": [1], "f": 0, "g": {}}),
+    );
+    assert_eq!(rendered_html.unwrap(), "aef");
+}
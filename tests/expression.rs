@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn arithmetic_in_variables() {
+    let rendered_html = render(
+        "${price * quantity} ${(a + b) / 2} ${count % 3} ${(-price) + 1}",
+        json!({"price": 2.5, "quantity": 4, "a": 3, "b": 4, "count": 10}),
+    );
+    assert_eq!(rendered_html.unwrap(), "10.0 3.5 1 -1.5");
+}
+
+#[test]
+fn integer_arithmetic_stays_integer() {
+    let rendered_html = render("${a / b} ${a * b} ${a - 10}", json!({"a": 6, "b": 2}));
+    assert_eq!(rendered_html.unwrap(), "3 12 -4");
+}
+
+#[test]
+fn string_concatenation() {
+    let rendered_html = render(
+        "${first_name ~ \" \" ~ last_name} ${'#' ~ (n + 1)}",
+        json!({"first_name": "Joel", "last_name": "Wi", "n": 1}),
+    );
+    assert_eq!(rendered_html.unwrap(), "Joel Wi #2");
+}
+
+#[test]
+fn expressions_in_loops() {
+    let rendered_html = render(
+        "{#for line of lines#}${line.price * line.quantity};{#endfor#}",
+        json!({"lines": [{"price": 2, "quantity": 3}, {"price": 5, "quantity": 1}]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "6;5;");
+}
+
+#[test]
+fn expressions_in_conditions() {
+    let html = "{#if total * 2 >= 10 && not (name ~ '!' == 'Joel')#}yes{#endif#}{#if count % 2 == 1 or missing#}odd{#endif#}";
+    let rendered_html = render(html, json!({"total": 5, "name": "Joel", "count": 3}));
+    assert_eq!(rendered_html.unwrap(), "yesodd");
+}
+
+#[test]
+fn division_by_zero_is_typed_error() {
+    let rendered_html = render("${a / b}", json!({"a": 1, "b": 0}));
+    assert!(matches!(rendered_html, Err(Error::DivisionByZero(_))));
+
+    let rendered_html = render("${a % b}", json!({"a": 1.5, "b": 0.0}));
+    assert!(matches!(rendered_html, Err(Error::DivisionByZero(_))));
+}
+
+#[test]
+fn overflow_is_typed_error() {
+    let rendered_html = render("${a + 1}", json!({"a": u64::MAX}));
+    assert!(matches!(rendered_html, Err(Error::Overflow(_))));
+
+    let rendered_html = render("${a - 1}", json!({"a": i64::MIN}));
+    assert!(matches!(rendered_html, Err(Error::Overflow(_))));
+
+    let rendered_html = render("${a * a}", json!({"a": 1e200}));
+    assert!(matches!(rendered_html, Err(Error::Overflow(_))));
+}
+
+#[test]
+fn integers_beyond_i64_are_exact() {
+    let html = "${a + 1}|${b - 1}|${b % 10}|${b / 5}|${b - b}|${0 - a - 1}";
+    let rendered_html = render(html, json!({"a": i64::MAX, "b": u64::MAX}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "9223372036854775808|18446744073709551614|5|3689348814741910323|0|-9223372036854775808"
+    );
+}
+
+#[test]
+fn exponent_literals() {
+    let rendered_html = render("${1e3}|${2.5E-1 * 4}|${x + 1e2}", json!({"x": 1}));
+    assert_eq!(rendered_html.unwrap(), "1000.0|1.0|101.0");
+}
+
+#[test]
+fn type_errors_and_missing_variables() {
+    let rendered_html = render("${name * 2}", json!({"name": "Joel"}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render("{#if name < 2#}{#endif#}", json!({"name": "Joel"}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render("${missing + 1}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let rendered_html = render("${(1 + 2}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+}
+
+#[test]
+fn error_displays_message() {
+    let error = render("${a / 0}", json!({"a": 1})).unwrap_err();
+    assert_eq!(error.to_string(), "1 / 0 divides by zero");
+}
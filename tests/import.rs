@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use kitamura::Environment;
+use serde_json::json;
+
+fn environment() -> Environment {
+    Environment::new()
+        .template(
+            "forms.html",
+            "<h1>Forms library</h1>\n{#import \"ui.html\" as ui#}\n{#macro field(name, label)#}<label>${label}</label>${ui.input(name)}{#endmacro#}\n",
+        )
+        .template(
+            "ui.html",
+            "{#macro input(name, type=\"text\")#}<input name=\"${name}\" type=\"${type}\">{#endmacro#}{#macro badge(n)#}<b>${n}</b>{#endmacro#}",
+        )
+}
+
+#[test]
+fn import_as_namespace() {
+    let html = "{#import \"forms.html\" as forms#}\n${forms.field(\"email\", \"Email\")}";
+    let rendered_html = environment().render_template(html.to_owned(), HashMap::new());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<label>Email</label><input name=\"email\" type=\"text\">"
+    );
+}
+
+#[test]
+fn from_import_names() {
+    let html = "{#from 'ui.html' import input, badge as count#}${input(\"q\", type=\"search\")}${count(n)}";
+    let params = HashMap::from([("n".to_owned(), json!(4))]);
+    let rendered_html = environment().render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<input name=\"q\" type=\"search\"><b>4</b>"
+    );
+}
+
+#[test]
+fn imported_namespaces_do_not_collide() {
+    let html = "{#import \"forms.html\" as ui#}{#macro input(x)#}mine{#endmacro#}${input(1)} ${ui.field(\"a\", \"A\")}";
+    let rendered_html = environment().render_template(html.to_owned(), HashMap::new());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "mine <label>A</label><input name=\"a\" type=\"text\">"
+    );
+}
+
+#[test]
+fn imported_macro_in_call_block_with_loader() {
+    let html = "{#import \"layout.html\" as layout#}{#call layout.card(\"Fruit\")#}{#for fruit of fruits#}${fruit.name};{#endfor#}{#endcall#}";
+    let params = HashMap::from([(
+        "fruits".to_owned(),
+        json!([{"name": "Lemon"}, {"name": "Pear"}]),
+    )]);
+    let rendered_html = Environment::new()
+        .loader(|name| {
+            (name == "layout.html")
+                .then(|| "{#macro card(title)#}[${title}: ${caller()}]{#endmacro#}".to_owned())
+        })
+        .render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "[Fruit: Lemon;Pear;]");
+}
+
+#[test]
+fn import_errors() {
+    let missing = environment().render_template(
+        "{#import \"nope.html\" as nope#}".to_owned(),
+        HashMap::new(),
+    );
+    assert!(
+        format!("{:?}", missing.unwrap_err()).contains("Template 'nope.html' could not be found")
+    );
+
+    let missing_macro = environment().render_template(
+        "{#from \"ui.html\" import button#}".to_owned(),
+        HashMap::new(),
+    );
+    assert!(format!("{:?}", missing_macro.unwrap_err())
+        .contains("'ui.html' does not define the macro 'button'"));
+
+    let no_namespace =
+        environment().render_template("{#import \"ui.html\"#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", no_namespace.unwrap_err()).contains("Expected as <namespace>"));
+}
+
+#[test]
+fn import_cycle_shows_chain() {
+    let environment = Environment::new()
+        .template("a.html", "{#import \"b.html\" as b#}")
+        .template("b.html", "{#from \"c.html\" import m#}")
+        .template(
+            "c.html",
+            "{#import \"a.html\" as a#}{#macro m()#}{#endmacro#}",
+        );
+
+    let rendered_html =
+        environment.render_template("{#import \"a.html\" as a#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", rendered_html.unwrap_err())
+        .contains("Import cycle detected: a.html -> b.html -> c.html -> a.html"));
+}
@@ -93,8 +93,33 @@ fn loop_can_be_in_or_of() {
 }
 
 #[test]
-fn loop_data_not_an_object() {
-    let html = "<html>{#for person of persons#}${person}{#endfor#}</html>";
+fn loop_over_a_path_into_a_root_object() {
+    let html = "{#for o of user.orders#}${o.id};{#endfor#}|{#for p of people#}{#for t of p.tags#}${t}{#endfor#};{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "user".to_string(),
+        json!({"orders": [{"id": 1}, {"id": 2}]}),
+    );
+    params.insert(
+        "people".to_string(),
+        json!([{"tags": ["a", "b"]}, {"tags": ["c"]}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "1;2;|ab;c;");
+}
+
+#[test]
+fn loop_items_that_are_not_objects_render_as_themselves() {
+    let html = "<html>{#for person of persons#}${person};{#endfor#}</html>";
+    let mut params = HashMap::new();
+    params.insert("persons".to_string(), json!(["Joel", 7, true]));
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "<html>Joel;7;true;</html>");
+}
+
+#[test]
+fn loop_items_that_are_not_objects_have_no_properties() {
+    let html = "<html>{#for person of persons#}${person.name}{#endfor#}</html>";
     let mut params = HashMap::new();
     params.insert("persons".to_string(), json!(["Joel"]));
     let rendered_html = render_template(html.to_string(), params);
@@ -269,3 +294,227 @@ fn nested_loops_referencing_the_same_dataset_holds_same_references() {
     let rendered_html = render_template(html.to_string(), params);
     assert_eq!(rendered_html.unwrap(), expected_output);
 }
+
+#[test]
+fn loop_break_inside_condition() {
+    let html = "<html>{#for person of persons#}${person.name}{#if person.stop#}{#break#}{#endif#},{#endfor#}</html>";
+    let mut params = HashMap::new();
+    params.insert(
+        "persons".to_string(),
+        json!([{"name": "Joel"}, {"name": "Ami", "stop": true}, {"name": "Ken"}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "<html>Joel,Ami</html>");
+}
+
+#[test]
+fn loop_continue_inside_nested_condition() {
+    let html = "<html>{#for person of persons#}{#if person.name?exists#}{#if person.hidden#}{#continue#}{#endif#}{#endif#}${person.name},{#endfor#}</html>";
+    let mut params = HashMap::new();
+    params.insert(
+        "persons".to_string(),
+        json!([{"name": "Joel"}, {"name": "Ami", "hidden": true}, {"name": "Ken", "hidden": false}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "<html>Joel,Ken,</html>");
+}
+
+#[test]
+fn loop_break_only_exits_inner_loop() {
+    let html = "{#for group of groups#}[{#for person of group.persons#}${person.name}{#break#}{#endfor#}]{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "groups".to_string(),
+        json!([{"persons": [{"name": "Joel"}, {"name": "Ami"}]}, {"persons": [{"name": "Ken"}]}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "[Joel][Ken]");
+}
+
+#[test]
+fn loop_break_outside_of_loop() {
+    let html = "<html>{#if first_name?exists#}{#break#}{#endif#}</html>";
+    let params = HashMap::from([("first_name".to_string(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert!(rendered_html.is_err());
+}
+
+#[test]
+fn loop_filter_clause() {
+    let html = "<html>{#for person of persons if person.active#}${loop.index}/${loop.length} ${person.name}{#if loop.last#}.{#endif#}{#if loop.first#}, {#endif#}{#endfor#}</html>";
+    let mut params = HashMap::new();
+    params.insert(
+        "persons".to_string(),
+        json!([
+            {"name": "Joel", "active": true},
+            {"name": "Ami", "active": false},
+            {"name": "Ken", "active": true},
+            {"name": "Yui"}
+        ]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "<html>1/2 Joel, 2/2 Ken.</html>");
+}
+
+#[test]
+fn loop_filter_clause_with_builtin() {
+    let html = "{#for person of persons if person.nickname?exists && person.nickname?not_empty#}${person.nickname}{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "persons".to_string(),
+        json!([{"nickname": "Jo"}, {"nickname": ""}, {"name": "Ken"}, {"nickname": "Yu"}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "JoYu");
+}
+
+#[test]
+fn loop_sorted_by_descending() {
+    let html = "{#for fruit of fruits sorted by price desc#}${fruit.name},{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "fruits".to_string(),
+        json!([{"name": "Lemon", "price": 2}, {"name": "Lychee", "price": 5.5}, {"name": "Lime", "price": 2}, {"name": "Kiwi", "price": 3}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "Lychee,Kiwi,Lemon,Lime,");
+}
+
+#[test]
+fn loop_sorted_by_is_stable_across_mixed_types() {
+    let html = "{#for fruit of fruits sorted by fruit.price#}${fruit.name},{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "fruits".to_string(),
+        json!([
+            {"name": "Lemon", "price": "2"},
+            {"name": "Lychee", "price": 5},
+            {"name": "Lime"},
+            {"name": "Kiwi", "price": null},
+            {"name": "Fig", "price": true}
+        ]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "Lime,Kiwi,Fig,Lychee,Lemon,");
+}
+
+#[test]
+fn loop_limit_and_offset() {
+    let html = "{#for fruit of fruits limit 2 offset 1#}${loop.index}:${fruit.name},{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "fruits".to_string(),
+        json!([{"name": "Lemon"}, {"name": "Lychee"}, {"name": "Lime"}, {"name": "Kiwi"}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "1:Lychee,2:Lime,");
+}
+
+#[test]
+fn loop_unique_by() {
+    let html = "{#for line of lines unique by sku#}${line.sku},{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "lines".to_string(),
+        json!([{"sku": "A1"}, {"sku": "B2"}, {"sku": "A1"}, {"sku": "C3"}, {"sku": "B2"}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "A1,B2,C3,");
+}
+
+#[test]
+fn loop_grouped_by() {
+    let html = "{#for group of products sorted by price grouped by category#}${group.key}:{#for product of group.items#} ${product.name}{#endfor#};{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "products".to_string(),
+        json!([
+            {"name": "Apple", "category": "fruit", "price": 3},
+            {"name": "Leek", "category": "vegetable", "price": 2},
+            {"name": "Fig", "category": "fruit", "price": 1},
+            {"name": "Kale", "category": "vegetable", "price": 4}
+        ]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "fruit: Fig Apple;vegetable: Leek Kale;"
+    );
+}
+
+#[test]
+fn loop_modifiers_after_filter() {
+    let html = "{#for fruit of fruits if fruit.ripe sorted by name limit 2#}${fruit.name}{#if loop.last#}.{#endif#}{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "fruits".to_string(),
+        json!([
+            {"name": "Lime", "ripe": true},
+            {"name": "Kiwi", "ripe": false},
+            {"name": "Fig", "ripe": true},
+            {"name": "Apple", "ripe": true}
+        ]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "AppleFig.");
+}
+
+#[test]
+fn loop_unknown_modifier() {
+    let html = "{#for fruit of fruits shuffled#}${fruit.name}{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert("fruits".to_string(), json!([{"name": "Lime"}]));
+    let rendered_html = render_template(html.to_string(), params);
+    assert!(rendered_html.is_err());
+}
+
+#[test]
+fn loop_chunked_rows() {
+    let html = "{#for row of products?chunk(2)#}<div class=\"row\">{#for product of row#}<span>${product.name}</span>{#endfor#}</div>{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "products".to_string(),
+        json!([{"name": "Lemon"}, {"name": "Lychee"}, {"name": "Lime"}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<div class=\"row\"><span>Lemon</span><span>Lychee</span></div><div class=\"row\"><span>Lime</span></div>"
+    );
+}
+
+#[test]
+fn loop_chunked_rows_with_fill() {
+    let html = "{#for row of products?chunk(3, null)#}[{#for product of row#}{#if product#}${product.name}{#endif#},{#endfor#}]{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "products".to_string(),
+        json!([{"name": "Lemon"}, {"name": "Lychee"}, {"name": "Lime"}, {"name": "Kiwi"}]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "[Lemon,Lychee,Lime,][Kiwi,,,]");
+}
+
+#[test]
+fn loop_chunked_rows_nested_in_loop() {
+    let html = "{#for shelf of shelves#}${shelf.name}:{#for row of shelf.products?chunk(2)#}({#for product of row#}${product.name}{#endfor#}){#endfor#};{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert(
+        "shelves".to_string(),
+        json!([
+            {"name": "Top", "products": [{"name": "A"}, {"name": "B"}, {"name": "C"}]},
+            {"name": "Bottom", "products": [{"name": "D"}]}
+        ]),
+    );
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "Top:(AB)(C);Bottom:(D);");
+}
+
+#[test]
+fn loop_chunk_size_must_be_positive() {
+    let html = "{#for row of products?chunk(0)#}{#endfor#}";
+    let mut params = HashMap::new();
+    params.insert("products".to_string(), json!([{"name": "Lemon"}]));
+    let rendered_html = render_template(html.to_string(), params);
+    assert!(rendered_html.is_err());
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Environment, Error};
+use serde_json::json;
+
+#[test]
+fn macro_with_positional_keyword_and_default_arguments() {
+    let html = "{#macro field(name, label, type=\"text\")#}<label>${label}</label><input name=\"${name}\" type=\"${type}\">{#endmacro#}${field(\"email\", \"Email\")}|${field(\"pw\", label=\"Password\", type=\"password\")}";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<label>Email</label><input name=\"email\" type=\"text\">|<label>Password</label><input name=\"pw\" type=\"password\">"
+    );
+}
+
+#[test]
+fn macro_can_be_called_before_definition() {
+    let html = "${badge(count)}\n{#macro badge(n)#}<b>${n}</b>{#endmacro#}\n";
+    let params = HashMap::from([("count".to_owned(), json!(3))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "<b>3</b>\n");
+}
+
+#[test]
+fn macro_arguments_from_loop_and_globals() {
+    let html = "{#macro item(fruit, sep=\", \")#}${fruit.name} (${shop})${sep}{#endmacro#}{#for fruit of fruits#}${item(fruit)}{#endfor#}";
+    let params = HashMap::from([
+        ("shop".to_owned(), json!("Market")),
+        (
+            "fruits".to_owned(),
+            json!([{"name": "Lemon"}, {"name": "Pear"}]),
+        ),
+    ]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Lemon (Market), Pear (Market), ");
+}
+
+#[test]
+fn macro_defaults_see_render_parameters_not_local_variables() {
+    let html = "{#macro f(a, b=site)#}${a}${b}{#endmacro#}${f(1)}|${f(2, b='x')}";
+    let params = HashMap::from([("site".to_owned(), json!("S"))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "1S|2x");
+
+    let html =
+        "{#macro f(a, b=x)#}${a}${b}{#endmacro#}{#for i of xs#}{#set x = i#}${f(1)}{#endfor#}";
+    let params = HashMap::from([("xs".to_owned(), json!([7, 8]))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let html = "{#set global x = 'g'#}{#macro f(b=x)#}${b}{#endmacro#}{#for i of xs#}{#set x = i#}${f()}${x}{#endfor#}";
+    let params = HashMap::from([("xs".to_owned(), json!([7, 8]))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "g7g8");
+}
+
+#[test]
+fn call_block_renders_caller() {
+    let html = "{#macro panel(title)#}<div><h2>${title}</h2>${caller()}</div>{#endmacro#}{#set who = 'Joel'#}{#call panel(\"Hello\")#}<p>Hi ${who}</p>{#endcall#}";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<div><h2>Hello</h2><p>Hi Joel</p></div>"
+    );
+}
+
+#[test]
+fn loop_control_inside_call_block() {
+    let params = HashMap::from([(
+        "ps".to_owned(),
+        json!([{"n": 1, "stop": false}, {"n": 2, "stop": true}, {"n": 3, "stop": false}]),
+    )]);
+    let html = "{#macro m()#}[${caller()}]{#if true#}!{#endif#}{#endmacro#}{#for p of ps#}{#call m()#}{#if p.stop#}{#break#}{#endif#}{#endcall#}${p.n}{#endfor#}";
+    let rendered_html = render_template(html.to_owned(), params.clone());
+    assert_eq!(rendered_html.unwrap(), "[]!1[]!");
+
+    let html = html.replace("{#break#}", "{#continue#}");
+    let rendered_html = render_template(html, params);
+    assert_eq!(rendered_html.unwrap(), "[]!1[]![]!3");
+}
+
+#[test]
+fn recursive_macro_renders_tree() {
+    let html = "{#macro tree(node)#}<li>${node.name}{#if node.children?not_empty#}<ul>{#for child of node.children#}${tree(child)}{#endfor#}</ul>{#endif#}</li>{#endmacro#}<ul>${tree(menu)}</ul>";
+    let params = HashMap::from([(
+        "menu".to_owned(),
+        json!({"name": "Home", "children": [
+            {"name": "Shop", "children": [{"name": "Fruit", "children": []}]},
+            {"name": "About", "children": []}
+        ]}),
+    )]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<ul><li>Home<ul><li>Shop<ul><li>Fruit</li></ul></li><li>About</li></ul></li></ul>"
+    );
+}
+
+#[test]
+fn recursion_depth_limit() {
+    let html = "{#macro forever(n)#}${forever(n + 1)}{#endmacro#}${forever(0)}";
+
+    let rendered_html = Environment::new()
+        .max_recursion_depth(10)
+        .render_template(html.to_owned(), HashMap::new());
+    assert!(format!("{:?}", rendered_html.unwrap_err())
+        .contains("exceeded the maximum recursion depth of 10"));
+
+    let html = "{#macro count(n)#}{#if n?exists#}${n}{#endif#}{#endmacro#}${count(1)}";
+    let rendered_html = Environment::new()
+        .max_recursion_depth(1)
+        .render_template(html.to_owned(), HashMap::new());
+    assert_eq!(rendered_html.unwrap(), "1");
+}
+
+#[test]
+fn macro_argument_errors() {
+    let html = "{#macro field(name, label)#}${name}{#endmacro#}";
+
+    let missing = render_template(format!("{}${{field(\"a\")}}", html), HashMap::new());
+    assert!(format!("{:?}", missing.unwrap_err()).contains("missing the argument 'label'"));
+
+    let too_many = render_template(format!("{}${{field(1, 2, 3)}}", html), HashMap::new());
+    assert!(format!("{:?}", too_many.unwrap_err()).contains("takes 2 arguments but 3"));
+
+    let unknown = render_template(format!("{}${{field(1, 2, size=3)}}", html), HashMap::new());
+    assert!(format!("{:?}", unknown.unwrap_err()).contains("has no parameter 'size'"));
+
+    let undefined = render_template("${nope(1)}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", undefined.unwrap_err()).contains("Macro 'nope' is not defined"));
+
+    let no_caller = render_template(
+        "{#macro m()#}${caller()}{#endmacro#}${m()}".to_owned(),
+        HashMap::new(),
+    );
+    assert!(format!("{:?}", no_caller.unwrap_err()).contains("{#call#} block"));
+}
+
+#[test]
+fn macro_has_no_closing_statement() {
+    let rendered_html = render_template("{#macro m()#}hello".to_owned(), HashMap::new());
+    assert!(format!("{:?}", rendered_html.unwrap_err()).contains("has no closing statement"));
+}
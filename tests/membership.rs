@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn contains_on_lists_compares_elements() {
+    let html = "{#if user.roles?contains('adm')#}a{#endif#}{#if user.roles?contains('admin')#}b{#endif#}{#if ids?contains(2)#}c{#endif#}{#if ids?contains('2')#}d{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"user": {"roles": ["admin", "editor"]}, "ids": [1, 2.0]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "bc");
+}
+
+#[test]
+fn contains_on_strings_and_objects() {
+    let html = "{#if title?contains(\"it's (new)\")#}a{#endif#}{#if user?contains('name')#}b{#endif#}{#if user?contains('Joel')#}c{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"title": "Sale: it's (new)", "user": {"name": "Joel"}}),
+    );
+    assert_eq!(rendered_html.unwrap(), "ab");
+}
+
+#[test]
+fn has_key() {
+    let html = "{#if user?has_key('email')#}a{#endif#}{#if user?has_key('phone')#}b{#endif#}{#if missing?has_key('x')#}c{#endif#}";
+    let rendered_html = render(html, json!({"user": {"email": null}}));
+    assert_eq!(rendered_html.unwrap(), "a");
+
+    let rendered_html = render(
+        "{#if roles?has_key('a')#}{#endif#}",
+        json!({"roles": ["a"]}),
+    );
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
+
+#[test]
+fn in_and_not_in() {
+    let html = "{#if 'admin' in user.roles#}a{#endif#}{#if 'owner' not in user.roles#}b{#endif#}{#if 'Jo' in user.name and 'email' in user#}c{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"user": {"name": "Joel", "email": "j@x", "roles": ["admin"]}}),
+    );
+    assert_eq!(rendered_html.unwrap(), "abc");
+}
+
+#[test]
+fn in_within_expressions_and_loops() {
+    let html = "{#for fruit of fruits if fruit.name not in skip#}${fruit.name in favourites ? '*' : ''}${fruit.name};{#endfor#}";
+    let rendered_html = render(
+        html,
+        json!({
+            "fruits": [{"name": "Lemon"}, {"name": "Pear"}, {"name": "Lychee"}],
+            "skip": ["Pear"],
+            "favourites": ["Lychee"]
+        }),
+    );
+    assert_eq!(rendered_html.unwrap(), "Lemon;*Lychee;");
+}
+
+#[test]
+fn membership_type_errors() {
+    let rendered_html = render("{#if 1 in count#}{#endif#}", json!({"count": 10}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render("{#if 1 in name#}{#endif#}", json!({"name": "a1"}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Environment, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+fn render_in(locale: &str, html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    Environment::new()
+        .locale(locale)
+        .render_template(html.to_owned(), params)
+}
+
+#[test]
+fn patterns() {
+    let html = "${a?string('#,##0.00')}|${a?string('0')}|${b?string('000.#')}|${c?string('#,##0.00')}|${d?string('#.##')}";
+    let rendered_html = render(
+        html,
+        json!({"a": 1234.5, "b": 7, "c": 1234567890123456789_i64, "d": 0.5}),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "1,234.50|1234|007|1,234,567,890,123,456,789.00|.5"
+    );
+}
+
+#[test]
+fn prefix_suffix_and_percent() {
+    let html = "${a?string('€ #,##0.00')}|${a?string('#,##0.00 €')}|${r?string('0.#%')}|${r?string('percent')}|${neg?string('$#,##0.00')}";
+    let rendered_html = render(html, json!({"a": 1234.5, "r": 0.1234, "neg": -5}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "€ 1,234.50|1,234.50 €|12.3%|12%|-$5.00"
+    );
+
+    let html = "${a?string('Rs. #,##0')}|${a?string('No. 0')}";
+    let rendered_html = render(html, json!({"a": 1234}));
+    assert_eq!(rendered_html.unwrap(), "Rs. 1,234|No. 1234");
+}
+
+#[test]
+fn locales() {
+    let html = "${a?string('#,##0.00 €')} ${a?string}";
+    assert_eq!(
+        render_in("de-DE", html, json!({"a": 1234.5})).unwrap(),
+        "1.234,50 € 1.234,5"
+    );
+    assert_eq!(
+        render_in("fr_FR", html, json!({"a": 1234.5})).unwrap(),
+        "1\u{202f}234,50 € 1\u{202f}234,5"
+    );
+    assert_eq!(
+        render_in("en-IN", "${a?string}", json!({"a": 12345678})).unwrap(),
+        "1,23,45,678"
+    );
+    assert_eq!(
+        render_in("xx", "${a?string}", json!({"a": 1234.5})).unwrap(),
+        "1,234.5"
+    );
+    assert_eq!(
+        render("${a?string('#,##0.00', locale='de')}", json!({"a": 1234.5})).unwrap(),
+        "1.234,50"
+    );
+}
+
+#[test]
+fn compact() {
+    let html = "{#for n of numbers#}${n?string('compact')};{#endfor#}";
+    let rendered_html = render(
+        html,
+        json!({"numbers": [999, 1234, 15000, 999950, 1500000, 2500000000_i64, -4200]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "999;1.2K;15K;1M;1.5M;2.5B;-4.2K;");
+
+    let rendered_html = render(
+        html,
+        json!({"numbers": [1500000000000_i64, 999950000000_i64, 1e15]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "1.5T;1T;1000T;");
+
+    let rendered_html = render_in("de", "${n?string('compact')}", json!({"n": 1500000}));
+    assert_eq!(rendered_html.unwrap(), "1,5\u{a0}Mio.");
+    let rendered_html = render_in("de", "${n?string('compact')}", json!({"n": 2e12}));
+    assert_eq!(rendered_html.unwrap(), "2\u{a0}Bio.");
+}
+
+#[test]
+fn rounding_is_exact() {
+    let html = "${a?string('0.00')} ${a?string('0.00', rounding='half_up')} ${b?string('0')} ${c?string('0')}";
+    let rendered_html = render(html, json!({"a": 1.005, "b": 2.5, "c": 3.5}));
+    assert_eq!(rendered_html.unwrap(), "1.00 1.01 2 4");
+}
+
+#[test]
+fn rounding_modes() {
+    let modes = [
+        "half_even",
+        "half_up",
+        "half_down",
+        "up",
+        "down",
+        "ceiling",
+        "floor",
+    ];
+    let mut output = vec![];
+    for mode in modes {
+        let html = format!(
+            "${{a?string('0', rounding='{0}')}},${{b?string('0', rounding='{0}')}},${{c?string('0', rounding='{0}')}}",
+            mode
+        );
+        output.push(render(&html, json!({"a": 1.5, "b": -1.5, "c": 1.2})).unwrap());
+    }
+    assert_eq!(
+        output,
+        ["2,-2,1", "2,-2,1", "1,-1,1", "2,-2,2", "1,-1,1", "2,-1,2", "1,-2,1"]
+    );
+}
+
+#[test]
+fn number_formatting_errors() {
+    let rendered_html = render("${a?string('0')}", json!({"a": "1"}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render("${a?string('abc')}", json!({"a": 1}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("${a?string('0', rounding='nearest')}", json!({"a": 1}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Environment, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn matches_regex() {
+    let html = r"{#for p of postcodes#}{#if p.code?matches('^[A-Z]{2}\\d+$')#}${p.code};{#endif#}{#endfor#}";
+    let rendered_html = render(
+        html,
+        json!({"postcodes": [{"code": "AB123"}, {"code": "a1"}, {"code": "XY9"}, {"code": 42}]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "AB123;XY9;");
+}
+
+#[test]
+fn starts_with_and_ends_with() {
+    let html = "{#if sku?starts_with('SKU-')#}a{#endif#}{#if file?ends_with('.pdf')#}b{#endif#}{#if file?ends_with('.PDF')#}c{#endif#}{#if missing?starts_with('x')#}d{#endif#}";
+    let rendered_html = render(html, json!({"sku": "SKU-1", "file": "report.pdf"}));
+    assert_eq!(rendered_html.unwrap(), "ab");
+}
+
+#[test]
+fn ignore_case() {
+    let html = "{#if email?ends_with('@EXAMPLE.com', ignore_case=true)#}a{#endif#}{#if sku?matches('^sku-', ignore_case=true)#}b{#endif#}{#if sku?starts_with('sku', ignore_case=false)#}c{#endif#}";
+    let rendered_html = render(html, json!({"email": "jo@example.COM", "sku": "SKU-1"}));
+    assert_eq!(rendered_html.unwrap(), "ab");
+}
+
+#[test]
+fn patterns_in_expressions() {
+    let html = "${email?matches('@example\\.com$') ? 'internal' : 'external'}";
+    let rendered_html = render(html, json!({"email": "jo@example.com"}));
+    assert_eq!(rendered_html.unwrap(), "internal");
+}
+
+#[test]
+fn dynamic_patterns() {
+    let html = "{#if code?matches(pattern)#}yes{#endif#}";
+    let rendered_html = render(html, json!({"code": "A1", "pattern": "^[A-Z]\\d$"}));
+    assert_eq!(rendered_html.unwrap(), "yes");
+
+    let rendered_html = render(html, json!({"code": "A1", "pattern": "(["}));
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+}
+
+#[test]
+fn invalid_pattern_is_reported_before_rendering() {
+    let html = "Hi\n{#if false#}\n  {#for x of xs if x?matches('[a-')#}${x}{#endfor#}\n{#endif#}";
+    let error = render(html, json!({})).unwrap_err();
+    assert!(matches!(error, Error::InvalidSyntax(_)));
+    let error = error.to_string();
+    assert!(error.contains("Invalid pattern '[a-'"), "{}", error);
+    assert!(error.ends_with("at line: 3:3"), "{}", error);
+}
+
+#[test]
+fn invalid_pattern_in_call_or_macro_default_is_reported_before_rendering() {
+    let templates = [
+        "{#macro m(x)#}${x}{#endmacro#}\n{#if false#}\n  {#call m(y?matches('('))#}{#endcall#}\n{#endif#}",
+        "Hi\n\n  {#macro m(x=y?matches('('))#}${x}{#endmacro#}",
+    ];
+    for html in templates {
+        let error = render(html, json!({})).unwrap_err();
+        assert!(matches!(error, Error::InvalidSyntax(_)), "{}", html);
+        let error = error.to_string();
+        assert!(error.contains("Invalid pattern '('"), "{}", error);
+        assert!(error.ends_with("at line: 3:3"), "{}", error);
+    }
+}
+
+#[test]
+fn templates_are_compiled_once_per_environment() {
+    let html = "{#for c of codes#}\n{#if c?matches('^[A-Z]+$')#}${c};{#endif#}\n{#endfor#}";
+    let params = HashMap::from([("codes".to_owned(), json!(["AB", "c1", "XY"]))]);
+
+    let environment = Environment::new();
+    for _ in 0..2 {
+        let rendered_html = environment.render_template(html.to_owned(), params.clone());
+        assert_eq!(rendered_html.unwrap(), "AB;XY;");
+    }
+
+    // Parsing depends on these settings, so a template compiled without them is not reused
+    let environment = environment.trim_blocks(false).lstrip_blocks(false);
+    let rendered_html = environment.render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "\nAB;\n\n\n\nXY;\n");
+
+    let rendered_html =
+        environment.render_template("${'a'?matches('(')}".to_owned(), HashMap::new());
+    assert!(rendered_html.is_err());
+    let rendered_html =
+        environment.render_template("${'a'?matches('(')}".to_owned(), HashMap::new());
+    assert!(rendered_html.is_err());
+}
+
+#[test]
+fn invalid_pattern_in_imported_template() {
+    let environment =
+        Environment::new().template("ui.html", "{#macro m(x)#}${x?matches('(')}{#endmacro#}");
+    let rendered_html =
+        environment.render_template("{#import \"ui.html\" as ui#}".to_owned(), HashMap::new());
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+}
+
+#[test]
+fn pattern_argument_errors() {
+    let rendered_html = render("{#if a?matches(1)#}{#endif#}", json!({"a": "1"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("{#if a?starts_with('x')#}{#endif#}", json!({"a": ["x"]}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use kitamura::{Environment, Error};
+use serde_json::json;
+
+const FORMS: &str = "{#for n of counts#}{#plural n#}{#zero#}zero{#one#}one{#two#}two{#few#}few{#many#}many{#other#}other{#endplural#} {#endfor#}";
+
+fn render_in(locale: &str, html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    Environment::new()
+        .locale(locale)
+        .render_template(html.to_owned(), params)
+}
+
+#[test]
+fn categories_by_locale() {
+    let counts = json!({"counts": [0, 1, 2, 3, 5, 11, 12, 21, 22, 100, 1000000, 1.5, "1.0"]});
+    let cases = [
+        (
+            "en",
+            "other one other other other other other other other other other other other ",
+        ),
+        (
+            "de-DE",
+            "other one other other other other other other other other other other other ",
+        ),
+        (
+            "fr",
+            "one one other other other other other other other other many one one ",
+        ),
+        (
+            "pl",
+            "many one few few many many many many few many many other other ",
+        ),
+        (
+            "ru_RU",
+            "many one few few many many many one few many many other other ",
+        ),
+        (
+            "ar",
+            "zero one two few few many many many many other other other one ",
+        ),
+        (
+            "ja",
+            "other other other other other other other other other other other other other ",
+        ),
+        (
+            "xx",
+            "other one other other other other other other other other other other other ",
+        ),
+    ];
+
+    for (locale, expected) in cases {
+        let rendered_html = render_in(locale, FORMS, counts.clone());
+        assert_eq!(rendered_html.unwrap(), expected, "{}", locale);
+    }
+}
+
+#[test]
+fn plural_blocks() {
+    let html = "{#plural items?size#}\n  {#one#}${items?size} item\n  {#other#}${items?size} items\n{#endplural#}";
+    let rendered_html = render_in("en", html, json!({"items": ["a"]}));
+    assert_eq!(rendered_html.unwrap(), "1 item\n");
+
+    // Forms that the language does not use fall back to other
+    let html = "{#plural n#}{#one#}plik{#other#}plików{#endplural#}";
+    let rendered_html = render_in("pl", html, json!({"n": 3}));
+    assert_eq!(rendered_html.unwrap(), "plików");
+
+    let html = "{#for c of counts#}{#plural c.n#}{#one#}{#continue#}{#other#}${c.n}{#endplural#}{#endfor#}";
+    let rendered_html = render_in(
+        "en",
+        html,
+        json!({"counts": [{"n": 1}, {"n": 2}, {"n": 1}, {"n": 3}]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "23");
+}
+
+#[test]
+fn plural_builtin() {
+    let html = "${n?plural(one='plik', few='pliki', many='plików', other='pliku')}|${5?plural(one=1, other=2)}|${'2'?plural(one='a', other='b')}";
+    let rendered_html = render_in("pl", html, json!({"n": 22}));
+    assert_eq!(rendered_html.unwrap(), "pliki|2|b");
+
+    let rendered_html = render_in("en", "${n?plural(one='x')}", json!({"n": 1}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render_in("en", "${n?plural('x', other='y')}", json!({"n": 1}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render_in(
+        "en",
+        "${n?plural(one='x', other='y')}",
+        json!({"n": "many"}),
+    );
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
+
+#[test]
+fn invalid_plurals() {
+    let cases = [
+        "{#plural n#}{#one#}x{#endplural#}",
+        "{#plural n#}{#one#}x{#one#}y{#other#}z{#endplural#}",
+        "{#one#}x",
+        "{#switch n#}{#one#}x{#endswitch#}",
+        "{#plural n#}{#other#}x",
+    ];
+    for html in cases {
+        let rendered_html = render_in("en", html, json!({"n": 1}));
+        assert!(
+            matches!(rendered_html, Err(Error::InvalidSyntax(_))),
+            "{}",
+            html
+        );
+    }
+
+    let rendered_html = render_in(
+        "en",
+        "{#plural n#}{#other#}x{#endplural#}",
+        json!({"n": [1]}),
+    );
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+
+use kitamura::render_template;
+use serde_json::json;
+
+#[test]
+fn raw_block_is_passed_through() {
+    let html = "<script type=\"text/x-handlebars\">{#raw#}{{#each items}}${item} {#if x#}{!-- --}{{/each}}{#endraw#}</script>";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<script type=\"text/x-handlebars\">{{#each items}}${item} {#if x#}{!-- --}{{/each}}</script>"
+    );
+}
+
+#[test]
+fn noparse_block_is_passed_through() {
+    let html = "Write {#noparse#}${first_name}{#endraw#}{#endnoparse#} to print ${first_name}";
+    let params = HashMap::from([("first_name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Write ${first_name}{#endraw#} to print Joel"
+    );
+}
+
+#[test]
+fn raw_block_keeps_inner_whitespace() {
+    let html = "<pre>\n  {#raw#}\n  {#for x of xs#}\n    ${x}\n  {#endfor#}\n  {#endraw#}\n</pre>";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<pre>\n  {#for x of xs#}\n    ${x}\n  {#endfor#}\n</pre>"
+    );
+}
+
+#[test]
+fn raw_block_inside_loop() {
+    let html = "{#for fruit of fruits#}${fruit.name}:{#raw#}${fruit.name}{#endraw#};{#endfor#}";
+    let params = HashMap::from([("fruits".to_owned(), json!([{"name": "Lemon"}]))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Lemon:${fruit.name};");
+}
+
+#[test]
+fn raw_block_missing_end() {
+    let html = "<p>\n  {#raw#}${first_name}</p>";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    let error = format!("{:?}", rendered_html.unwrap_err());
+    assert!(
+        error.contains("'{#raw#}' has no closing statement"),
+        "{}",
+        error
+    );
+    assert!(error.contains("at line: 2:3"), "{}", error);
+}
+
+#[test]
+fn closing_statement_without_opening() {
+    let html = "<p>{#endif#}</p>";
+    let rendered_html = render_template(html.to_owned(), HashMap::new());
+    assert!(rendered_html.is_err());
+}
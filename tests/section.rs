@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use kitamura::{render_sections, render_template, Error};
+use serde_json::json;
+
+fn sections(html: &str, params: serde_json::Value) -> Result<HashMap<String, String>, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_sections(html.to_owned(), params)
+}
+
+#[test]
+fn email_sections() {
+    let html = r##"{#set total = items?sum(attribute='price')#}
+{#section "subject"#}Order ${id}: ${total} EUR{#endsection#}
+{#section "text"#}
+Hi ${name},
+{#for item of items#}
+- ${item.name}
+{#endfor#}
+{#endsection#}
+{#section "html"#}
+<ul>{#for item of items#}<li>${item.name}</li>{#endfor#}</ul>
+{#endsection#}
+"##;
+    let params = json!({"id": 7, "name": "Joel", "items": [
+        {"name": "Tea", "price": 4},
+        {"name": "Cups", "price": 12}
+    ]});
+
+    let rendered = sections(html, params).unwrap();
+    assert_eq!(rendered.len(), 4);
+    assert_eq!(rendered["subject"], "Order 7: 16 EUR");
+    assert_eq!(rendered["text"], "Hi Joel,\n- Tea\n- Cups\n");
+    assert_eq!(rendered["html"], "<ul><li>Tea</li><li>Cups</li></ul>\n");
+    assert_eq!(rendered["default"], "");
+}
+
+#[test]
+fn default_section() {
+    let html =
+        "Hello {#section \"a\"#}A{#endsection#}${name}{#section \"default\"#}!{#endsection#}";
+    let rendered = sections(html, json!({"name": "Joel"})).unwrap();
+    assert_eq!(rendered["default"], "Hello Joel!");
+    assert_eq!(rendered["a"], "A");
+
+    // Rendering normally keeps the sections in place
+    let params = serde_json::from_value(json!({"name": "Joel"})).unwrap();
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Hello AJoel!");
+}
+
+#[test]
+fn sections_add_up() {
+    let html = "{#for u of users#}{#if u.admin#}{#section \"admins\"#}${u.name};{#endsection#}{#endif#}{#section \"all\"#}${u.name};{#endsection#}{#endfor#}{#if false#}{#section \"never\"#}x{#endsection#}{#endif#}";
+    let params = json!({"users": [
+        {"name": "Joel", "admin": true},
+        {"name": "Mia", "admin": false},
+        {"name": "Ada", "admin": true}
+    ]});
+
+    let rendered = sections(html, params).unwrap();
+    assert_eq!(rendered["admins"], "Joel;Ada;");
+    assert_eq!(rendered["all"], "Joel;Mia;Ada;");
+    assert!(!rendered.contains_key("never"));
+}
+
+#[test]
+fn sections_from_macros() {
+    let html = "{#macro title(text)#}{#section \"subject\"#}${text}{#endsection#}<h1>${text}</h1>{#endmacro#}${title('Welcome')}";
+    let rendered = sections(html, json!({})).unwrap();
+    assert_eq!(rendered["subject"], "Welcome");
+    assert_eq!(rendered["default"], "<h1>Welcome</h1>");
+}
+
+#[test]
+fn invalid_sections() {
+    for html in [
+        "{#section subject#}x{#endsection#}",
+        "{#section \"a\"#}x",
+        "x{#endsection#}",
+    ] {
+        let rendered = sections(html, json!({"subject": "a"}));
+        assert!(matches!(rendered, Err(Error::InvalidSyntax(_))), "{}", html);
+    }
+
+    let rendered = sections("{#section \"a\"#}${missing}{#endsection#}", json!({}));
+    assert!(matches!(rendered, Err(Error::UndefinedVariable(_))));
+}
@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Environment, Error, UndefinedBehavior};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn case() {
+    let html = "${name?upper} ${name?lower} ${name?title} ${name?capitalize}";
+    let rendered_html = render(html, json!({"name": "éLLA o'brien-smith"}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "ÉLLA O'BRIEN-SMITH élla o'brien-smith Élla O'brien-Smith Élla o'brien-smith"
+    );
+}
+
+#[test]
+fn trim() {
+    let html = "[${s?trim}][${s?trim_start}][${s?trim_end}]";
+    let rendered_html = render(html, json!({"s": "  a b \n"}));
+    assert_eq!(rendered_html.unwrap(), "[a b][a b \n][  a b]");
+}
+
+#[test]
+fn pad() {
+    let html = "${n?pad_left(5, '0')} [${s?pad_right(4)}] [${s?pad_left(width=1)}]";
+    let rendered_html = render(html, json!({"n": "42", "s": "ab"}));
+    assert_eq!(rendered_html.unwrap(), "00042 [ab  ] [ab]");
+}
+
+#[test]
+fn truncate() {
+    let html = "${s?truncate(12)}|${s?truncate(12, words=true)}|${s?truncate(9, ellipsis='…')}|${s?truncate(50)}|${long?truncate(8, words=true)}";
+    let rendered_html = render(
+        html,
+        json!({"s": "The quick brown fox", "long": "Unbreakable"}),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "The quick...|The quick...|The quic…|The quick brown fox|Unbre..."
+    );
+
+    let html = "${s?truncate(0)}|${s?truncate(2)}|${s?truncate(3)}|${s?truncate(4)}|${s?truncate(2, words=true)}|${s?truncate(1, ellipsis='…')}";
+    let rendered_html = render(html, json!({"s": "abcdef"}));
+    assert_eq!(rendered_html.unwrap(), "|ab|abc|a...|ab|a");
+}
+
+#[test]
+fn word_wrap_and_indent() {
+    let html = "${s?word_wrap(10)}\n${s?word_wrap(10)?indent(2)}\n${s?word_wrap(10)?indent(2, first=true)}";
+    let rendered_html = render(html, json!({"s": "one two three four\n\nfive"}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "one two\nthree four\n\nfive\none two\n  three four\n\n  five\n  one two\n  three four\n\n  five"
+    );
+}
+
+#[test]
+fn slugify_replace_split_substring() {
+    let html = "${title?slugify} ${title?replace('!', '?')} ${tags?split(',')} ${word?substring(1, 3)} ${word?substring(2)}";
+    let rendered_html = render(
+        html,
+        json!({"title": "  Hello, Wörld!  Ünïcode  ", "tags": "a,b", "word": "日本語です"}),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "hello-wörld-ünïcode   Hello, Wörld?  Ünïcode   [a,b] 本語 語です"
+    );
+}
+
+#[test]
+fn length_counts_unicode_scalar_values() {
+    let rendered_html = render("${s?length} ${s?length > 3}", json!({"s": "日本語"}));
+    assert_eq!(rendered_html.unwrap(), "3 false");
+}
+
+#[test]
+fn string_builtins_in_other_expressions() {
+    let html = "{#set slug = title?slugify#}{#if slug?starts_with('my-')#}${slug?upper}{#endif#}";
+    let rendered_html = render(html, json!({"title": "My Page"}));
+    assert_eq!(rendered_html.unwrap(), "MY-PAGE");
+}
+
+#[test]
+fn type_and_argument_errors() {
+    let rendered_html = render("${n?upper}", json!({"n": 1}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render("${s?pad_left(-1)}", json!({"s": "a"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("${s?pad_left(3, 'ab')}", json!({"s": "a"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("${s?replace('a')}", json!({"s": "a"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("${s?upper(1)}", json!({"s": "a"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    let rendered_html = render("${s?truncate(3, size=2)}", json!({"s": "a"}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+}
+
+#[test]
+fn missing_values_follow_undefined_behavior() {
+    let rendered_html = render("${name?upper}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let rendered_html = Environment::new()
+        .undefined_behavior(UndefinedBehavior::Lenient)
+        .render_template("[${name?upper}]".to_owned(), HashMap::new());
+    assert_eq!(rendered_html.unwrap(), "[]");
+}
@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use kitamura::render_template;
+use serde_json::json;
+
+fn badge(status: serde_json::Value) -> String {
+    let html = "{#switch order.status#}\n  {#case \"paid\"#}\n  <b>Paid</b>\n  {#case \"refunded\", \"void\"#}\n  <i>Cancelled</i>\n  {#default#}\n  <u>Pending</u>\n{#endswitch#}\n";
+    let params = HashMap::from([("order".to_owned(), json!({ "status": status }))]);
+    render_template(html.to_owned(), params).unwrap()
+}
+
+#[test]
+fn switch_renders_matching_case() {
+    assert_eq!(badge(json!("paid")), "  <b>Paid</b>\n");
+}
+
+#[test]
+fn switch_multi_value_case() {
+    assert_eq!(badge(json!("refunded")), "  <i>Cancelled</i>\n");
+    assert_eq!(badge(json!("void")), "  <i>Cancelled</i>\n");
+}
+
+#[test]
+fn switch_falls_back_to_default() {
+    assert_eq!(badge(json!("new")), "  <u>Pending</u>\n");
+}
+
+#[test]
+fn switch_uses_typed_equality() {
+    let html = "{#switch count#}{#case '1'#}string{#case 1#}number{#case true#}bool{#endswitch#}";
+
+    let params = HashMap::from([("count".to_owned(), json!(1.0))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "number");
+
+    let params = HashMap::from([("count".to_owned(), json!("1"))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "string");
+
+    let params = HashMap::from([("count".to_owned(), json!(2))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "");
+}
+
+#[test]
+fn switch_inside_loop_with_break() {
+    let html = "{#for item of items#}{#switch item.kind#}{#case 'stop'#}{#break#}{#default#}${item.name};{#endswitch#}{#endfor#}";
+    let params = HashMap::from([(
+        "items".to_owned(),
+        json!([
+            {"kind": "fruit", "name": "Lemon"},
+            {"kind": "stop", "name": "-"},
+            {"kind": "fruit", "name": "Pear"}
+        ]),
+    )]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "Lemon;");
+}
+
+#[test]
+fn equality_in_conditions_is_typed() {
+    let html = "{#if age == 30#}a{#endif#}{#if age == '30'#}b{#endif#}{#if age != 31#}c{#endif#}";
+    let params = HashMap::from([("age".to_owned(), json!(30))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "ac");
+}
+
+#[test]
+fn switch_errors() {
+    let case_outside = render_template("{#case 'a'#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", case_outside.unwrap_err()).contains("not directly inside of a switch"));
+
+    let two_defaults = render_template(
+        "{#switch 1#}{#case 1#}{#default#}{#default#}{#endswitch#}".to_owned(),
+        HashMap::new(),
+    );
+    assert!(format!("{:?}", two_defaults.unwrap_err()).contains("only have one {#default#}"));
+
+    let unclosed = render_template("{#switch 1#}{#case 1#}".to_owned(), HashMap::new());
+    assert!(format!("{:?}", unclosed.unwrap_err()).contains("has no closing statement"));
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+#[test]
+fn ternary_chooses_a_branch() {
+    let html =
+        "<li class=\"${is_active ? \"active\" : \"\"}\">${count} item${count == 1 ? '' : 's'}</li>";
+    let rendered_html = render(html, json!({"is_active": true, "count": 3}));
+    assert_eq!(rendered_html.unwrap(), "<li class=\"active\">3 items</li>");
+
+    let rendered_html = render(html, json!({"is_active": false, "count": 1}));
+    assert_eq!(rendered_html.unwrap(), "<li class=\"\">1 item</li>");
+}
+
+#[test]
+fn then_builtin() {
+    let html = "${is_active?then(\"active\", \"inactive\")} ${(count > 1)?then('many', 'one')}";
+    let rendered_html = render(html, json!({"is_active": false, "count": 2}));
+    assert_eq!(rendered_html.unwrap(), "inactive many");
+}
+
+#[test]
+fn only_the_chosen_branch_is_evaluated() {
+    let html = "${ready ? name : missing} ${ready?then(name, 1 / 0)}";
+    let rendered_html = render(html, json!({"ready": true, "name": "Joel"}));
+    assert_eq!(rendered_html.unwrap(), "Joel Joel");
+}
+
+#[test]
+fn nested_ternaries_group_to_the_right() {
+    let html = "${score >= 90 ? 'A' : score >= 80 ? 'B' : 'C'}";
+    assert_eq!(render(html, json!({"score": 95})).unwrap(), "A");
+    assert_eq!(render(html, json!({"score": 85})).unwrap(), "B");
+    assert_eq!(render(html, json!({"score": 10})).unwrap(), "C");
+}
+
+#[test]
+fn ternary_anywhere_an_expression_is_allowed() {
+    let html = "{#set label = admin ? 'Admin' : 'User'#}${label}{#for user of users#}{#if (user.admin ? 1 : 0) == 1#};${user.name}{#endif#}{#endfor#}";
+    let rendered_html = render(
+        html,
+        json!({"users": [{"name": "Ann", "admin": true}, {"name": "Bo"}]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "User;Ann");
+}
+
+#[test]
+fn ternary_errors() {
+    let rendered_html = render("${a ? 'x'}", json!({"a": true}));
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+
+    let rendered_html = render("${a?then('x')}", json!({"a": true}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+}
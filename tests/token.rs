@@ -14,3 +14,78 @@ fn variable_renders_successfully() {
         "<html> order reference #: Joel</html>"
     );
 }
+
+#[test]
+fn inline_css_is_left_as_text() {
+    let html = "<style>body { color: #333 } h1{font-weight:bold}</style><h1 style=\"color: #${colour}\">${title}</h1>";
+    let params = HashMap::from([
+        ("colour".to_owned(), json!("f00")),
+        ("title".to_owned(), json!("Hi")),
+    ]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<style>body { color: #333 } h1{font-weight:bold}</style><h1 style=\"color: #f00\">Hi</h1>"
+    );
+}
+
+#[test]
+fn javascript_objects_are_left_as_text() {
+    let html = "<script>const config = { user: \"${first_name}\", flags: {a: 1} };\nif (x) { y(); }</script>";
+    let params = HashMap::from([("first_name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "<script>const config = { user: \"Joel\", flags: {a: 1} };\nif (x) { y(); }</script>"
+    );
+}
+
+#[test]
+fn dollar_amounts_are_left_as_text() {
+    let html =
+        "Only $5! Was $10, save ${saving}$ # today {#if first_name?exists#}${first_name}{#endif#}";
+    let params = HashMap::from([
+        ("saving".to_owned(), json!(5)),
+        ("first_name".to_owned(), json!("Joel")),
+    ]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Only $5! Was $10, save 5$ # today Joel"
+    );
+}
+
+#[test]
+fn escaped_openers_are_literal() {
+    let html = "Use \\${name} or \\{#if x#}...\\{#endif#} and \\{!-- --}, not ${name}";
+    let params = HashMap::from([("name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Use ${name} or {#if x#}...{#endif#} and {!-- --}, not Joel"
+    );
+}
+
+#[test]
+fn closing_delimiters_inside_quotes_do_not_close_tags() {
+    let html = "{#if title == '#}'#}matched{#endif#}";
+    let params = HashMap::from([("title".to_owned(), json!("#}"))]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert!(rendered_html.is_ok());
+}
+
+#[test]
+fn unclosed_variable() {
+    let html = "<p>Hello ${first_name</p>";
+    let params = HashMap::from([("first_name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert!(rendered_html.is_err());
+}
+
+#[test]
+fn non_ascii_text_is_kept() {
+    let html = "北村 — ${first_name} ✓";
+    let params = HashMap::from([("first_name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_string(), params);
+    assert_eq!(rendered_html.unwrap(), "北村 — Joel ✓");
+}
@@ -0,0 +1,293 @@
+use std::collections::HashMap;
+
+use kitamura::{extract_messages, messages_template, Catalog, Environment, Error};
+use serde_json::json;
+
+const GERMAN_PO: &str = r#"# German translations
+msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+
+#: email.html:1
+msgid "Hello ${name}!"
+msgstr "Hallo ${name}!"
+
+msgid "greeting"
+msgstr ""
+"Guten Tag, "
+"${user.name}"
+
+#, fuzzy
+msgid "Goodbye"
+msgstr "Tschüss"
+
+msgid "Thanks"
+msgstr ""
+
+msgctxt "menu"
+msgid "Open"
+msgstr "Öffnen"
+
+msgid "Say \"hi\""
+msgstr "Sag \"hallo\"\n"
+"#;
+
+const FRENCH_FTL: &str = r#"# French translations
+-brand = Kitamura
+hello = Bonjour { $name } !
+welcome = Bienvenue sur { -brand }
+login = Connexion
+    .title = Se connecter à { -brand }
+intro =
+    Première ligne
+    deuxième ligne
+quoted = { "{" } littéral
+"#;
+
+const POLISH_PO: &str = r#"msgid ""
+msgstr ""
+"Content-Type: text/plain; charset=UTF-8\n"
+"Plural-Forms: nplurals=3; plural=(n==1 ? 0 : n%10>=2 && n%10<=4 && "
+"(n%100<10 || n%100>=20) ? 1 : 2);\n"
+
+msgid "One file"
+msgid_plural "${count} files"
+msgstr[0] "Jeden plik"
+msgstr[1] "${count} pliki"
+msgstr[2] "${count} plików"
+
+msgid "One folder"
+msgid_plural "${count} folders"
+msgstr[0] "Jeden folder"
+msgstr[1] ""
+msgstr[2] ""
+"#;
+
+const FRENCH_SELECTORS: &str = r#"files = { $count ->
+    [0] Aucun fichier
+    [one] Un fichier
+   *[other] { $count } fichiers
+}
+greeting =
+    { $gender ->
+        [female] Chère { $name },
+       *[other] Cher { $name },
+    }
+"#;
+
+fn render_in(
+    environment: &Environment,
+    html: &str,
+    params: serde_json::Value,
+) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    environment.render_template(html.to_owned(), params)
+}
+
+fn environment() -> Environment {
+    Environment::new()
+        .catalog("de", Catalog::from_po(GERMAN_PO).unwrap())
+        .catalog("fr", Catalog::from_ftl(FRENCH_FTL).unwrap())
+}
+
+#[test]
+fn trans_blocks() {
+    let html = "{#trans#}Hello ${name}!{#endtrans#}|{#trans#}\n  Hello\n  ${ name }!\n{#endtrans#}|{#trans \"greeting\"#}Good day, ${user.name}{#endtrans#}";
+    let params = json!({"name": "Joel", "user": {"name": "Mia"}});
+
+    let rendered_html = render_in(&environment().locale("de"), html, params.clone());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Hallo Joel!|Hallo Joel!|Guten Tag, Mia"
+    );
+
+    let rendered_html = render_in(&environment(), html, params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Hello Joel!|Hello Joel!|Good day, Mia"
+    );
+}
+
+#[test]
+fn gettext_function() {
+    let html = "${_('Hello ${name}!', name=user.name)}|${_('hello', name='Ada')}|${_('Goodbye')}|${_('Thanks')}|${_('welcome')}|${_('login.title')}|${_('Say \"hi\"')}|${_('Open')}";
+    let params = json!({"user": {"name": "Joel"}});
+
+    let rendered_html = render_in(&environment().locale("de"), html, params.clone());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Hallo Joel!|hello|Goodbye|Thanks|welcome|login.title|Sag \"hallo\"\n|Open"
+    );
+
+    let rendered_html = render_in(&environment().locale("fr_FR"), html, params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Hello Joel!|Bonjour Ada !|Goodbye|Thanks|Bienvenue sur Kitamura|Se connecter à Kitamura|Say \"hi\"|Open"
+    );
+}
+
+#[test]
+fn fluent_multiline_and_literals() {
+    let rendered_html = render_in(
+        &environment().locale("fr"),
+        "${_('intro')}|${_('quoted')}",
+        json!({}),
+    );
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Première ligne\ndeuxième ligne|{ littéral"
+    );
+}
+
+#[test]
+fn fallback_locales() {
+    let environment = environment().fallback_locales(&["fr", "de"]).locale("nl");
+    let html = "${_('hello', name='Joel')}|${_('Hello ${name}!', name='Joel')}|${_('missing')}";
+    let rendered_html = render_in(&environment, html, json!({}));
+    assert_eq!(rendered_html.unwrap(), "Bonjour Joel !|Hallo Joel!|missing");
+}
+
+#[test]
+fn invalid_translations() {
+    let rendered_html = render_in(
+        &environment(),
+        "{#trans#}Hi ${name?upper}{#endtrans#}",
+        json!({"name": "x"}),
+    );
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+
+    let rendered_html = render_in(
+        &environment(),
+        "{#trans#}{#if a#}x{#endif#}{#endtrans#}",
+        json!({"a": true}),
+    );
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+
+    let rendered_html = render_in(&environment(), "{#trans#}Hi ${name}{#endtrans#}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let environment = Environment::new()
+        .catalog("de", Catalog::new().message("Hi", "Hallo ${name?upper}"))
+        .locale("de");
+    let rendered_html = render_in(&environment, "${_('Hi', name='x')}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::InvalidSyntax(_))));
+
+    assert!(Catalog::from_po("msgid \"a\"\nmsgstr b\n").is_err());
+    assert!(Catalog::from_ftl("hello = { $count ->\n [other] many\n}\n").is_err());
+    assert!(Catalog::from_ftl("hello = { -brand ->\n *[other] many\n}\n").is_err());
+    assert!(Catalog::from_ftl("a = { b }\nb = { a }\n").is_err());
+}
+
+#[test]
+fn plural_messages() {
+    let html = "{#for f of folders#}${_n('One file', '${count} files', f.files)}/${_n('One folder', '${count} folders', f.files)}|{#endfor#}";
+    let params = json!({"folders": [{"files": 1}, {"files": 3}, {"files": 5}, {"files": 22}]});
+
+    let environment = Environment::new()
+        .catalog("pl", Catalog::from_po(POLISH_PO).unwrap())
+        .locale("pl");
+    let rendered_html = render_in(&environment, html, params.clone());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Jeden plik/One folder|3 pliki/3 folders|5 plików/5 folders|22 pliki/22 folders|"
+    );
+
+    let rendered_html = render_in(&Environment::new(), html, params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "One file/One folder|3 files/3 folders|5 files/5 folders|22 files/22 folders|"
+    );
+
+    let rendered_html = render_in(&Environment::new(), "${_n('a', 'b', 'x')}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+
+    let rendered_html = render_in(&Environment::new(), "${_n('a', 2)}", json!({}));
+    assert!(matches!(rendered_html, Err(Error::InvalidApi(_))));
+
+    assert!(Catalog::from_po(
+        "msgid \"\"\nmsgstr \"Plural-Forms: nplurals=2; plural=n >> 1;\\n\"\n"
+    )
+    .is_err());
+}
+
+#[test]
+fn plural_messages_without_plural_forms_use_the_locale() {
+    let polish = "msgid \"One file\"\nmsgid_plural \"${count} files\"\nmsgstr[0] \"Jeden plik\"\nmsgstr[1] \"${count} pliki\"\nmsgstr[2] \"${count} plików\"\n";
+    let html = "{#for n of counts#}${_n('One file', '${count} files', n)}|{#endfor#}";
+    let params = json!({"counts": [1, 3, 5, 22, 112, 1.5]});
+
+    let environment = Environment::new()
+        .catalog("pl", Catalog::from_po(polish).unwrap())
+        .locale("pl-PL");
+    let rendered_html = render_in(&environment, html, params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Jeden plik|3 pliki|5 plików|22 pliki|112 plików|1.5 plików|"
+    );
+
+    let english = "msgid \"One file\"\nmsgid_plural \"${count} files\"\nmsgstr[0] \"A file\"\nmsgstr[1] \"${count} files!\"\n";
+    let environment = Environment::new()
+        .catalog("en", Catalog::from_po(english).unwrap())
+        .locale("en");
+    let rendered_html = render_in(&environment, html, json!({"counts": [1, 0, 2]}));
+    assert_eq!(rendered_html.unwrap(), "A file|0 files!|2 files!|");
+}
+
+#[test]
+fn fluent_selectors() {
+    let environment = Environment::new()
+        .catalog("fr", Catalog::from_ftl(FRENCH_SELECTORS).unwrap())
+        .locale("fr-CA");
+    let html = "{#for f of folders#}${_('files', count=f.files)}|{#endfor#}${_('greeting', gender='female', name='Ada')} ${_('greeting', name='Joel')}";
+    let params = json!({"folders": [{"files": 0}, {"files": 1}, {"files": 1.5}, {"files": 7}]});
+
+    let rendered_html = render_in(&environment, html, params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "Aucun fichier|Un fichier|Un fichier|7 fichiers|Chère Ada, Cher Joel,"
+    );
+}
+
+#[test]
+fn extract() {
+    let html = "<h1>{#trans#}Hello ${name}!{#endtrans#}</h1>\n{#if show#}\n  <p>${_('Read more')}</p>\n{#endif#}\n{#trans \"footer\"#}\n  See you\n{#endtrans#}\n{#raw#}${_('not this')}{#endraw#}\n{#set title = _('Read more') ~ '!'#}\n${_n('One file', '${count} files', n)}";
+    let messages = extract_messages("email.html", html).unwrap();
+    let found: Vec<(&str, Option<&str>, &str)> = messages
+        .iter()
+        .map(|m| (m.id.as_str(), m.default.as_deref(), m.reference.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Hello ${name}!", None, "email.html:1"),
+            ("Read more", None, "email.html:3"),
+            ("footer", Some("See you"), "email.html:5"),
+            ("Read more", None, "email.html:9"),
+            ("One file", None, "email.html:10"),
+        ]
+    );
+
+    assert_eq!(
+        messages_template(&messages),
+        "msgid \"\"\nmsgstr \"\"\n\"Content-Type: text/plain; charset=UTF-8\\n\"\n\n#: email.html:1\nmsgid \"Hello ${name}!\"\nmsgstr \"\"\n\n#: email.html:3 email.html:9\nmsgid \"Read more\"\nmsgstr \"\"\n\n#. See you\n#: email.html:5\nmsgid \"footer\"\nmsgstr \"\"\n\n#: email.html:10\nmsgid \"One file\"\nmsgid_plural \"${count} files\"\nmsgstr[0] \"\"\nmsgstr[1] \"\"\n"
+    );
+}
+
+#[test]
+fn extract_from_calls_and_macro_defaults() {
+    let html = "{#macro panel(title=_('Untitled'))#}${title}${caller()}{#endmacro#}\n{#call panel(_n('One item', '${count} items', n))#}x{#endcall#}\n{#switch _('Menu')#}{#case _('Open')#}{#endswitch#}";
+    let messages = extract_messages("page.html", html).unwrap();
+    let found: Vec<(&str, Option<&str>, &str)> = messages
+        .iter()
+        .map(|m| (m.id.as_str(), m.plural.as_deref(), m.reference.as_str()))
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("Untitled", None, "page.html:1"),
+            ("One item", Some("${count} items"), "page.html:2"),
+            ("Menu", None, "page.html:3"),
+            ("Open", None, "page.html:3"),
+        ]
+    );
+}
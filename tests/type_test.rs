@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Error};
+use serde_json::json;
+
+fn render(html: &str, params: serde_json::Value) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    render_template(html.to_owned(), params)
+}
+
+fn values() -> serde_json::Value {
+    json!({"values": [
+        {"name": "text", "value": "a"},
+        {"name": "number", "value": 1.5},
+        {"name": "bool", "value": false},
+        {"name": "null", "value": null},
+        {"name": "array", "value": [1]},
+        {"name": "object", "value": {"a": 1}},
+        {"name": "missing"}
+    ]})
+}
+
+#[test]
+fn type_tests() {
+    let html = "{#for v of values#}${v.name}:{#if v.value?is_string#}s{#endif#}{#if v.value?is_number#}n{#endif#}{#if v.value?is_bool#}b{#endif#}{#if v.value?is_null#}0{#endif#}{#if v.value?is_array#}a{#endif#}{#if v.value?is_object#}o{#endif#};{#endfor#}";
+    let rendered_html = render(html, values());
+    assert_eq!(
+        rendered_html.unwrap(),
+        "text:s;number:n;bool:b;null:0;array:a;object:o;missing:;"
+    );
+}
+
+#[test]
+fn truthiness_follows_json_types() {
+    let html = "{#for v of values#}{#if v.value#}${v.name};{#endif#}{#endfor#}";
+    let params = json!({"values": [
+        {"name": "null", "value": null},
+        {"name": "false", "value": false},
+        {"name": "true", "value": true},
+        {"name": "zero", "value": 0},
+        {"name": "zero float", "value": 0.0},
+        {"name": "number", "value": -2},
+        {"name": "empty string", "value": ""},
+        {"name": "string", "value": "0"},
+        {"name": "empty array", "value": []},
+        {"name": "array", "value": [false]},
+        {"name": "empty object", "value": {}},
+        {"name": "object", "value": {"a": null}},
+        {"name": "missing"}
+    ]});
+    let rendered_html = render(html, params);
+    assert_eq!(rendered_html.unwrap(), "true;number;string;array;object;");
+}
+
+#[test]
+fn is_empty() {
+    let html = "{#if a?is_empty#}a{#endif#}{#if b?is_empty#}b{#endif#}{#if c?is_empty#}c{#endif#}{#if d?is_empty#}d{#endif#}{#if e?is_empty#}e{#endif#}{#if missing?is_empty#}m{#endif#}";
+    let rendered_html = render(
+        html,
+        json!({"a": "", "b": [], "c": {}, "d": "x", "e": [null]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "abcm");
+
+    let rendered_html = render("{#if n?is_empty#}{#endif#}", json!({"n": 0}));
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
+
+#[test]
+fn not_empty_by_type() {
+    let html = "{#for v of values#}{#if v.value?not_empty#}${v.name};{#endif#}{#endfor#}";
+    let params = json!({"values": [
+        {"name": "zero", "value": 0},
+        {"name": "five", "value": 5},
+        {"name": "false", "value": false},
+        {"name": "one char", "value": "a"},
+        {"name": "empty string", "value": ""},
+        {"name": "empty array", "value": []},
+        {"name": "null", "value": null}
+    ]});
+    let rendered_html = render(html, params);
+    assert_eq!(rendered_html.unwrap(), "zero;five;false;one char;");
+}
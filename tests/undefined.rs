@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use kitamura::{Environment, Error, UndefinedBehavior};
+use serde_json::json;
+
+fn render(
+    undefined_behavior: UndefinedBehavior,
+    html: &str,
+    params: serde_json::Value,
+) -> Result<String, Error> {
+    let params: HashMap<String, serde_json::Value> = serde_json::from_value(params).unwrap();
+    Environment::new()
+        .undefined_behavior(undefined_behavior)
+        .render_template(html.to_owned(), params)
+}
+
+#[test]
+fn strict_is_the_default() {
+    let rendered_html = kitamura::render_template("${name}".to_owned(), HashMap::new());
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+}
+
+#[test]
+fn strict_errors_point_at_the_tag() {
+    let error = render(UndefinedBehavior::Strict, "Hi\n  ${nickname}", json!({})).unwrap_err();
+    assert!(matches!(error, Error::UndefinedVariable(_)));
+    assert!(error.to_string().ends_with("at line: 2:3"));
+
+    let html = "{#for a of as#}{#for b of bs#}${a.x}{#endfor#}{#endfor#}";
+    let rendered_html = render(
+        UndefinedBehavior::Strict,
+        html,
+        json!({"as": [{"y": 1}], "bs": [{"x": 2}]}),
+    );
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let rendered_html = render(
+        UndefinedBehavior::Strict,
+        "{#for x of xs#}{#endfor#}",
+        json!({}),
+    );
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+
+    let rendered_html = render(
+        UndefinedBehavior::Strict,
+        "{#if age > 3#}{#endif#}",
+        json!({}),
+    );
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+}
+
+#[test]
+fn strict_conditions_may_test_missing_values() {
+    let html = "{#if user.admin || not user.name#}hidden{#endif#}";
+    let rendered_html = render(
+        UndefinedBehavior::Strict,
+        html,
+        json!({"user": {"name": "Jo"}}),
+    );
+    assert_eq!(rendered_html.unwrap(), "");
+}
+
+#[test]
+fn lenient_renders_nothing() {
+    let html = "[${name}][${user.name}][${count + 1}]{#for x of xs#}${x}{#endfor#}{#if age > 3#}old{#endif#}";
+    let rendered_html = render(UndefinedBehavior::Lenient, html, json!({}));
+    assert_eq!(rendered_html.unwrap(), "[][][]");
+}
+
+#[test]
+fn lenient_loop_item_properties() {
+    let html = "{#for person of persons#}${person.name}:${person.nickname};{#endfor#}";
+    let rendered_html = render(
+        UndefinedBehavior::Lenient,
+        html,
+        json!({"persons": [{"name": "Joel"}, {"name": "Ann", "nickname": "A"}]}),
+    );
+    assert_eq!(rendered_html.unwrap(), "Joel:;Ann:A;");
+}
+
+#[test]
+fn debug_renders_markers() {
+    let html = "${name} ${user.email}{#for x of xs#}${x}{#endfor#}";
+    let rendered_html = render(UndefinedBehavior::Debug, html, json!({"user": {}}));
+    assert_eq!(
+        rendered_html.unwrap(),
+        "[[undefined: name]] [[undefined: user.email]][[undefined: xs]]"
+    );
+}
+
+#[test]
+fn other_errors_are_not_hidden() {
+    let rendered_html = render(UndefinedBehavior::Lenient, "${a / 0}", json!({"a": 1}));
+    assert!(matches!(rendered_html, Err(Error::DivisionByZero(_))));
+
+    let rendered_html = render(
+        UndefinedBehavior::Lenient,
+        "{#for x of name#}{#endfor#}",
+        json!({"name": "Jo"}),
+    );
+    assert!(matches!(rendered_html, Err(Error::TypeError(_))));
+}
+
+#[test]
+fn root_object_properties() {
+    let params = json!({"user": {"name": "Jo", "tags": ["a", "b"], "address": {"city": "Oslo"}}});
+    let html = "${user.name}|${user.tags.1}|${user.address.city}|${user.name?upper}";
+    for behavior in [
+        UndefinedBehavior::Strict,
+        UndefinedBehavior::Lenient,
+        UndefinedBehavior::Debug,
+    ] {
+        let rendered_html = render(behavior, html, params.clone());
+        assert_eq!(rendered_html.unwrap(), "Jo|b|Oslo|JO", "{:?}", behavior);
+    }
+
+    let html = "[${user.nickname}]";
+    let rendered_html = render(UndefinedBehavior::Strict, html, params.clone());
+    assert!(matches!(rendered_html, Err(Error::UndefinedVariable(_))));
+    let rendered_html = render(UndefinedBehavior::Lenient, html, params.clone());
+    assert_eq!(rendered_html.unwrap(), "[]");
+    let rendered_html = render(UndefinedBehavior::Debug, html, params);
+    assert_eq!(rendered_html.unwrap(), "[[[undefined: user.nickname]]]");
+}
+
+#[test]
+fn block_operands_follow_the_behavior() {
+    let templates = [
+        (
+            "{#switch kind#}{#case 1#}one{#endswitch#}!",
+            "!",
+            "[[undefined: kind]]!",
+        ),
+        (
+            "{#set x = total + 1#}[${x}]",
+            "[]",
+            "[[undefined: total + 1]][[[undefined: x]]]",
+        ),
+        (
+            "{#plural n#}{#one#}1{#other#}n{#endplural#}!",
+            "!",
+            "[[undefined: n]]!",
+        ),
+    ];
+    for (html, lenient, debug) in templates {
+        let error = render(
+            UndefinedBehavior::Strict,
+            &format!("a\n {}", html),
+            json!({}),
+        );
+        let error = error.unwrap_err();
+        assert!(matches!(error, Error::UndefinedVariable(_)), "{}", html);
+        assert!(error.to_string().ends_with("at line: 2:2"), "{}", error);
+
+        let rendered_html = render(UndefinedBehavior::Lenient, html, json!({}));
+        assert_eq!(rendered_html.unwrap(), lenient);
+        let rendered_html = render(UndefinedBehavior::Debug, html, json!({}));
+        assert_eq!(rendered_html.unwrap(), debug);
+    }
+}
+
+#[test]
+fn case_values_follow_the_behavior() {
+    let html = "{#switch 1#}\n  {#case 2, missing#}two{#case 1#}one{#endswitch#}!";
+    let error = render(UndefinedBehavior::Strict, html, json!({})).unwrap_err();
+    assert!(matches!(error, Error::UndefinedVariable(_)));
+    assert!(error.to_string().ends_with("at line: 2:3"), "{}", error);
+
+    let rendered_html = render(UndefinedBehavior::Lenient, html, json!({}));
+    assert_eq!(rendered_html.unwrap(), "!");
+    let rendered_html = render(UndefinedBehavior::Debug, html, json!({}));
+    assert_eq!(rendered_html.unwrap(), "[[undefined: 2, missing]]!");
+
+    let rendered_html = render(UndefinedBehavior::Strict, html, json!({"missing": 1}));
+    assert_eq!(rendered_html.unwrap(), "two!");
+}
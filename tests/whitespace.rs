@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+
+use kitamura::{render_template, Environment};
+use serde_json::json;
+
+#[test]
+fn block_markers_trim_surrounding_whitespace() {
+    let html =
+        "<ul>\n    {#-for item of items-#}\n    <li>${item.name}</li>\n    {#-endfor#}\n</ul>";
+    let params = HashMap::from([("items".to_owned(), json!([{"name": "a"}, {"name": "b"}]))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "<ul><li>a</li><li>b</li></ul>");
+}
+
+#[test]
+fn spaced_block_markers_trim_surrounding_whitespace() {
+    let html = "<ul>\n  {#- for item of items -#}\n  {#- if item.shown -#}\n  <li>${item.name}</li>\n  {#- endif -#}\n  {#- endfor -#}\n</ul>";
+    let params = HashMap::from([(
+        "items".to_owned(),
+        json!([{"name": "a", "shown": true}, {"name": "b", "shown": false}]),
+    )]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "<ul><li>a</li></ul>");
+}
+
+#[test]
+fn variable_markers_trim_surrounding_whitespace() {
+    let html = "Hello   \n  ${- first_name -}  \n  !";
+    let params = HashMap::from([("first_name".to_owned(), json!("Joel"))]);
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "HelloJoel!");
+}
+
+#[test]
+fn minus_in_a_variable_is_a_negation() {
+    let params = HashMap::from([("a".to_owned(), json!(5))]);
+    let html = "[${-a}]|x  ${-a}|${-a -}  !|${2-a}";
+    let rendered_html = render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "[-5]|x  -5|-5!|-3");
+}
+
+#[test]
+fn lstrip_blocks_only_strips_at_the_start_of_a_line() {
+    let html = "a {#if b?exists#}b{#endif#}\n  {#if b?exists#}\nc\n  {#endif#}\n";
+    let params = HashMap::from([("b".to_owned(), json!(true))]);
+    let environment = Environment::new().trim_blocks(false);
+    let rendered_html = environment.render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "a b\n\nc\n\n");
+}
+
+#[test]
+fn environment_without_trim_or_lstrip_is_byte_exact() {
+    let html =
+        "services:\n  {#for service of services#}\n  - name: ${service.name}\n  {#endfor#}\n";
+    let params = HashMap::from([(
+        "services".to_owned(),
+        json!([{"name": "web"}, {"name": "db"}]),
+    )]);
+    let environment = Environment::new().trim_blocks(false).lstrip_blocks(false);
+    let rendered_html = environment.render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "services:\n  \n  - name: web\n  \n  - name: db\n  \n"
+    );
+}
+
+#[test]
+fn environment_with_trim_blocks_only() {
+    let html =
+        "services:\n  {#for service of services#}\n  - name: ${service.name}\n  {#endfor#}\n";
+    let params = HashMap::from([(
+        "services".to_owned(),
+        json!([{"name": "web"}, {"name": "db"}]),
+    )]);
+    let environment = Environment::new().lstrip_blocks(false);
+    let rendered_html = environment.render_template(html.to_owned(), params);
+    assert_eq!(
+        rendered_html.unwrap(),
+        "services:\n    - name: web\n    - name: db\n  "
+    );
+}
+
+#[test]
+fn environment_markers_still_apply_when_settings_are_off() {
+    let html = "services:\n  {#-for service of services#}\n  - ${service.name}\n  {#-endfor#}\n";
+    let params = HashMap::from([(
+        "services".to_owned(),
+        json!([{"name": "web"}, {"name": "db"}]),
+    )]);
+    let environment = Environment::new().trim_blocks(false).lstrip_blocks(false);
+    let rendered_html = environment.render_template(html.to_owned(), params);
+    assert_eq!(rendered_html.unwrap(), "services:\n  - web\n  - db\n");
+}